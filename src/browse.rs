@@ -0,0 +1,364 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --browse (the "browse" feature). All of the input files are read and the
+// pages are listed on the terminal, with a preview of the selected one (as -a
+// shows it) beside the list. Typing after / narrows the list down to the pages
+// whose names or briefs have that in them. The terminal is driven with termios
+// and ANSI escapes, there's nothing here that needs a TUI library.
+
+use std::collections::BTreeMap;
+use std::io::{self, Error, IsTerminal, Write};
+use doxygen2man::cli::Opt;
+use doxygen2man::state::RunState;
+use doxygen2man::model::*;
+use doxygen2man::render::preview_page;
+use super::{read_input, set_current_input, translate_functions};
+#[cfg(feature = "json")]
+use super::apply_filters;
+
+// What was read from one input file
+struct Header {
+    name: String,
+    functions: Vec<FunctionInfo>,
+    structures: BTreeMap<String, StructureInfo>,
+}
+
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Escape,
+    Backspace,
+    Char(char),
+    Quit,
+}
+
+// The terminal in raw mode on the alternate screen, until it's dropped
+struct Screen {
+    saved: libc::termios,
+}
+
+impl Screen {
+    fn start() -> Result<Screen, Error>
+    {
+        // SAFETY: termios is plain data, tcgetattr fills it in
+        let mut t: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut t) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        let saved = t;
+        t.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        t.c_iflag &= !(libc::IXON | libc::ICRNL);
+        // Reads wait a tenth of a second at most, so a lone Escape can be told
+        // apart from the start of an arrow key
+        t.c_cc[libc::VMIN] = 0;
+        t.c_cc[libc::VTIME] = 1;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &t) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()?;
+        Ok(Screen {saved})
+    }
+
+    // Rows and columns
+    fn size(&self) -> (usize, usize)
+    {
+        // SAFETY: winsize is plain data, TIOCGWINSZ fills it in
+        let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } != 0 || ws.ws_row == 0 {
+            return (24, 80);
+        }
+        (ws.ws_row as usize, ws.ws_col as usize)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self)
+    {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.saved);
+        }
+    }
+}
+
+// A byte from the terminal, or None if nothing was typed for a while
+fn read_byte() -> Result<Option<u8>, Error>
+{
+    let mut b = 0u8;
+    match unsafe { libc::read(libc::STDIN_FILENO, &mut b as *mut u8 as *mut libc::c_void, 1) } {
+        1 => Ok(Some(b)),
+        0 => Ok(None),
+        _ => {
+            let e = Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted { Ok(None) } else { Err(e) }
+        }
+    }
+}
+
+fn read_key() -> Result<Key, Error>
+{
+    let b = loop {
+        if let Some(b) = read_byte()? {
+            break b;
+        }
+    };
+    Ok(match b {
+        0x1b => match read_byte()? {
+            Some(b'[') | Some(b'O') => match read_byte()? {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                Some(n @ b'1'..=b'8') => {
+                    // ESC [ n ~
+                    let _tilde = read_byte()?;
+                    match n {
+                        b'5' => Key::PageUp,
+                        b'6' => Key::PageDown,
+                        b'1' | b'7' => Key::Home,
+                        b'4' | b'8' => Key::End,
+                        _ => Key::Char('\0'),
+                    }
+                }
+                _ => Key::Char('\0'),
+            },
+            _ => Key::Escape,
+        },
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x03 | 0x04 => Key::Quit,
+        b if b.is_ascii_graphic() || b == b' ' => Key::Char(b as char),
+        _ => Key::Char('\0'),
+    })
+}
+
+// A line with ANSI escapes cut down to width visible characters
+fn clip(line: &str, width: usize) -> String
+{
+    let mut out = String::new();
+    let mut shown = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            out.push(c);
+            for e in chars.by_ref() {
+                out.push(e);
+                if e.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        if shown == width {
+            break;
+        }
+        out.push(c);
+        shown += 1;
+    }
+    out.push_str("\x1b[0m");
+    out
+}
+
+struct Browser {
+    headers: Vec<Header>,
+    // (header, function) of every page, in the order they were read
+    all: Vec<(usize, usize)>,
+    // The ones that match the search
+    shown: Vec<(usize, usize)>,
+    search: String,
+    searching: bool,
+    selected: usize,
+    top: usize,
+    scroll: usize,
+}
+
+impl Browser {
+    fn function(&self, entry: (usize, usize)) -> &FunctionInfo
+    {
+        &self.headers[entry.0].functions[entry.1]
+    }
+
+    fn filter(&mut self)
+    {
+        let search = self.search.to_lowercase();
+        self.shown = self.all.iter().copied().filter(|&e| {
+            let f = self.function(e);
+            f.fn_name.to_lowercase().contains(&search) || f.fn_brief.to_lowercase().contains(&search)
+        }).collect();
+        self.selected = 0;
+        self.top = 0;
+        self.scroll = 0;
+    }
+
+    fn select(&mut self, selected: usize)
+    {
+        let selected = selected.min(self.shown.len().saturating_sub(1));
+        if selected != self.selected {
+            self.selected = selected;
+            self.scroll = 0;
+        }
+    }
+
+    fn draw(&mut self, opt: &mut Opt, rows: usize, cols: usize) -> Result<(), Error>
+    {
+        let list_rows = rows.saturating_sub(2).max(1);
+        let list_width = (cols / 3).clamp(12, 32);
+        let preview_width = cols.saturating_sub(list_width + 3).max(20);
+
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + list_rows {
+            self.top = self.selected + 1 - list_rows;
+        }
+
+        let preview: Vec<String> = match self.shown.get(self.selected) {
+            Some(&(h, i)) => {
+                let header = &self.headers[h];
+                opt.headerfile = header.name.clone();
+                preview_page(opt, &header.functions[i], &header.functions, &header.structures, true, preview_width)
+                    .lines().map(|l| l.to_string()).collect()
+            }
+            None => Vec::new(),
+        };
+        self.scroll = self.scroll.min(preview.len().saturating_sub(list_rows));
+
+        let mut screen = String::new();
+        let title = format!(" {}: {} of {} pages", opt.package_name, self.shown.len(), self.all.len());
+        screen.push_str(&format!("\x1b[1;1H\x1b[2K\x1b[7m{}", clip(&format!("{title:cols$}"), cols)));
+        for r in 0..list_rows {
+            screen.push_str(&format!("\x1b[{};1H\x1b[2K", r + 2));
+            if let Some(&e) = self.shown.get(self.top + r) {
+                let name: String = self.function(e).fn_name.chars().take(list_width).collect();
+                if self.top + r == self.selected {
+                    screen.push_str(&format!("\x1b[7m{name:list_width$}\x1b[0m"));
+                } else {
+                    screen.push_str(&format!("{name:list_width$}"));
+                }
+            } else {
+                screen.push_str(&" ".repeat(list_width));
+            }
+            screen.push_str(" \u{2502} ");
+            if let Some(l) = preview.get(self.scroll + r) {
+                screen.push_str(&clip(l, preview_width));
+            }
+        }
+        let status = if self.searching {
+            format!("/{}", self.search)
+        } else if !self.search.is_empty() {
+            format!("/{}   Escape: all pages  /: search  j/k: move  space/b: scroll  q: quit", self.search)
+        } else {
+            "/: search  j/k: move  space/b: scroll  g/G: first/last  q: quit".to_string()
+        };
+        screen.push_str(&format!("\x1b[{};1H\x1b[2K{}", rows, clip(&status, cols)));
+
+        let mut stdout = io::stdout();
+        stdout.write_all(screen.as_bytes())?;
+        stdout.flush()
+    }
+
+    // Returns false when it's time to stop
+    fn key(&mut self, key: Key, page: usize) -> bool
+    {
+        if self.searching {
+            match key {
+                Key::Enter => self.searching = false,
+                Key::Escape => {
+                    self.searching = false;
+                    self.search.clear();
+                    self.filter();
+                }
+                Key::Backspace => {
+                    self.search.pop();
+                    self.filter();
+                }
+                Key::Char(c) if c != '\0' => {
+                    self.search.push(c);
+                    self.filter();
+                }
+                Key::Quit => return false,
+                _ => {}
+            }
+            return true;
+        }
+        match key {
+            Key::Quit | Key::Char('q') => return false,
+            Key::Up | Key::Char('k') => self.select(self.selected.saturating_sub(1)),
+            Key::Down | Key::Char('j') => self.select(self.selected + 1),
+            Key::Home | Key::Char('g') => self.select(0),
+            Key::End | Key::Char('G') => self.select(self.shown.len()),
+            Key::PageDown | Key::Char(' ') => self.scroll += page,
+            Key::PageUp | Key::Char('b') => self.scroll = self.scroll.saturating_sub(page),
+            Key::Char('/') => self.searching = true,
+            Key::Escape if !self.search.is_empty() => {
+                self.search.clear();
+                self.filter();
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+// Read all of the input files
+fn read_headers(opt: &mut Opt, state: &mut RunState) -> Vec<Header>
+{
+    let mut headers = Vec::new();
+    let mut structure_cache = BTreeMap::<String, StructureInfo>::new();
+    for in_file in &opt.xml_files.clone() {
+        set_current_input(opt, state, in_file);
+        let (functions, structures) = match read_input(opt, state, in_file, &mut structure_cache) {
+            Some(m) => m,
+            None => continue,
+        };
+        #[cfg(feature = "json")]
+        let (functions, structures) = match apply_filters(opt, in_file, functions, structures) {
+            Some(m) => m,
+            None => continue,
+        };
+        let functions = translate_functions(state, functions);
+        headers.push(Header {name: opt.headerfile.clone(), functions, structures});
+    }
+    headers
+}
+
+// Browse the pages until q is pressed
+pub fn browse(opt: &mut Opt, state: &mut RunState) -> Result<(), Error>
+{
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(Error::other("--browse needs a terminal"));
+    }
+    let headers = read_headers(opt, state);
+    let all: Vec<(usize, usize)> = headers.iter().enumerate()
+        .flat_map(|(h, header)| (0..header.functions.len()).map(move |i| (h, i)))
+        .collect();
+    if all.is_empty() {
+        return Err(Error::other("there are no pages to browse"));
+    }
+    let mut browser = Browser {headers, shown: all.clone(), all, search: String::new(), searching: false,
+                               selected: 0, top: 0, scroll: 0};
+
+    let screen = Screen::start()?;
+    loop {
+        let (rows, cols) = screen.size();
+        browser.draw(opt, rows, cols)?;
+        let page = rows.saturating_sub(3).max(1);
+        if !browser.key(read_key()?, page) {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+//! Generating man pages from a cargo build script, for Rust crates that
+//! wrap a C library and want to ship its API pages.
+//!
+//! ```no_run
+//! let out_dir = std::env::var("OUT_DIR").unwrap();
+//! let outputs = doxygen2man::build::ManPages::new("doc/xml")
+//!     .xml_file("qbipcs_8h.xml")
+//!     .output_dir(&out_dir)
+//!     .package_name("libqb")
+//!     .generate()
+//!     .unwrap();
+//! outputs.rerun_if_changed();
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+use crate::cli::Opt;
+use crate::state::RunState;
+use crate::model::*;
+use crate::parser::read_header_xml;
+use crate::render::{print_man_pages, wants_page};
+
+/// Builds a doxygen2man run. Anything without its own method can be
+/// given as a command-line option with [`ManPages::arg`].
+#[derive(Debug, Clone)]
+pub struct ManPages {
+    xml_dir: String,
+    output_dir: String,
+    xml_files: Vec<String>,
+    args: Vec<String>,
+}
+
+/// What a [`ManPages::generate`] run read and wrote.
+#[derive(Debug, Clone, Default)]
+pub struct Outputs {
+    /// The man pages, including any that were already up to date
+    pub pages: Vec<PathBuf>,
+    /// The XML (and header) files the pages were made from
+    pub inputs: Vec<PathBuf>,
+}
+
+impl Outputs {
+    /// Tell cargo to run the build script again if any of the inputs change
+    pub fn rerun_if_changed(&self)
+    {
+        for i in &self.inputs {
+            println!("cargo:rerun-if-changed={}", i.display());
+        }
+    }
+}
+
+impl ManPages {
+    /// Read XML files from `xml_dir`, the doxygen XML output directory
+    pub fn new(xml_dir: &str) -> ManPages
+    {
+        ManPages {
+            xml_dir: xml_dir.to_string(),
+            output_dir: "./".to_string(),
+            xml_files: Vec::new(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Add a header's XML file (eg qbipcs_8h.xml) to the run
+    pub fn xml_file(mut self, xml_file: &str) -> ManPages
+    {
+        self.xml_files.push(xml_file.to_string());
+        self
+    }
+
+    /// Write the pages to `dir`
+    pub fn output_dir(mut self, dir: &str) -> ManPages
+    {
+        self.output_dir = dir.to_string();
+        self
+    }
+
+    /// Write the pages into man section `section`
+    pub fn section(self, section: u32) -> ManPages
+    {
+        self.arg("-s").arg(&section.to_string())
+    }
+
+    /// Name of the package for these man pages
+    pub fn package_name(self, name: &str) -> ManPages
+    {
+        self.arg("-p").arg(name)
+    }
+
+    /// Prefix for the include file, eg qb/
+    pub fn header_prefix(self, prefix: &str) -> ManPages
+    {
+        self.arg("-i").arg(prefix)
+    }
+
+    /// Company name in the copyright line
+    pub fn company(self, company: &str) -> ManPages
+    {
+        self.arg("-C").arg(company)
+    }
+
+    /// Pass any other doxygen2man command-line option
+    pub fn arg(mut self, arg: &str) -> ManPages
+    {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Write the man pages
+    pub fn generate(self) -> Result<Outputs, Error>
+    {
+        let mut args = vec!["doxygen2man".to_string(), "-m".to_string(),
+                            "-d".to_string(), self.xml_dir,
+                            "-o".to_string(), self.output_dir];
+        args.extend(self.args);
+        args.extend(self.xml_files);
+
+        let mut opt = match Opt::from_iter_safe(args) {
+            Ok(o) => o,
+            Err(e) => return Err(Error::other(e.message)),
+        };
+        let mut state = RunState::default();
+        #[cfg(feature = "templates")]
+        {
+            state.templates = crate::render::template::load_templates(&opt);
+        }
+        opt.source_version = crate::render::resolve_source_version(&opt);
+        if !opt.preamble.is_empty() {
+            state.preamble_text = std::fs::read_to_string(&opt.preamble)?;
+        }
+        if !opt.rename_map.is_empty() {
+            state.renames = crate::render::read_rename_map(&opt.rename_map)?;
+        }
+
+        let mut outputs = Outputs::default();
+        let mut structure_cache = BTreeMap::<String, StructureInfo>::new();
+        for in_file in &opt.xml_files.clone() {
+            state.current_input = format!("{}/{}", opt.xml_dir, in_file);
+
+            let (mut functions, structures) = read_header_xml(&opt.xml_dir, in_file, &mut opt.headerfile, &mut structure_cache)?;
+            functions.retain(|f| wants_page(&opt, &state, f));
+            let pages = match print_man_pages(&opt, &state, &functions, &structures) {
+                Ok((p, 0)) => p,
+                _ => return Err(Error::other(format!("Error writing man pages for {in_file}"))),
+            };
+            for p in pages {
+                for i in p.inputs {
+                    let i = PathBuf::from(i);
+                    if !outputs.inputs.contains(&i) {
+                        outputs.inputs.push(i);
+                    }
+                }
+                outputs.pages.push(PathBuf::from(p.file));
+            }
+        }
+        Ok(outputs)
+    }
+}
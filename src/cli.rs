@@ -0,0 +1,437 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+use structopt::StructOpt;
+
+// The subcommands, and the options they stand for. The rest of the command line
+// is the same as without one, so "check <dir>" is "--verify <dir>".
+const SUBCOMMANDS: &[(&str, &[&str])] = &[
+    ("generate", &["--print-man"]),
+    ("check", &["--verify"]),
+    ("list", &["--list"]),
+    ("lint", &["--lint", "--dry-run"]),
+    ("dump", &["--print-ascii"]),
+];
+
+// Replace a subcommand at the start of the command line with its options
+pub fn expand_subcommand(args: Vec<String>) -> Vec<String>
+{
+    let sub = args.get(1).and_then(|a| SUBCOMMANDS.iter().find(|(name, _)| a == name));
+    match sub {
+        Some((_, opts)) => {
+            let mut expanded = vec![args[0].clone()];
+            expanded.extend(opts.iter().map(|o| o.to_string()));
+            expanded.extend(args[2..].iter().cloned());
+            expanded
+        }
+        None => args,
+    }
+}
+
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(name = "doxygen2man", about = "Convert doxygen files to man pages",
+            after_help = "SUBCOMMANDS (the options can be used without them too):
+    generate    Write the man pages (-m)
+    check <dir> Compare the pages with the ones in <dir> (--verify <dir>)
+    list        Print the names of the pages that would be written (--list)
+    lint        Check the pages for problems without writing them (--lint --dry-run)
+    dump        Print a preview of the pages made from the XML files (-a)",
+            long_about = "This is a tool to generate API manpages from a doxygen-annotated header file. \
+First run doxygen on the file and then run this program against the main XML file \
+it created and the directory containing the ancilliary files. It will then \
+output a lot of *.3 man page files which you can then ship with your library.
+
+Doxygen creates an .xml file for each .h file in your project, pass these .xml \
+files into this program - they are usually called something like \
+<include-file>_8h.xml, eg qbipcs_8h.xml, you can pass multiple XML files to \
+doxygen2man on the command-line.
+
+If you want HTML output then simply use nroff on the generated files as you \
+would do with any other man page.")]
+pub struct Opt {
+    #[structopt (short="a", long="print-ascii", help="Print a preview of the man pages to stdout, in colour on a terminal unless NO_COLOR is set")]
+    pub print_ascii: bool,
+
+    #[structopt (short="m", long="print-man", help="Write man page files to <output-dir>")]
+    pub print_man: bool,
+
+    #[structopt (short="P", long="print-params", help="print PARAMS section")]
+    pub print_params: bool,
+
+    #[structopt (long="prototype-style", default_value="aligned", possible_values=&["aligned", "fit", "compact"], help="How to lay out the SYNOPSIS prototypes: aligned puts each parameter on its own line, fit puts them on one line if they fit in --prototype-width and wraps them if not, compact is as the header declares them")]
+    pub prototype_style: String,
+
+    #[structopt (long="prototype-width", default_value="72", help="Width of the SYNOPSIS prototypes for --prototype-style fit")]
+    pub prototype_width: usize,
+
+    #[structopt (long="reflow", default_value="lines", possible_values=&["lines", "paragraphs", "full"], help="Where the descriptions get paragraph breaks: lines after every line of text, paragraphs between doxygen's paragraphs (with lists as lists), full only at blank lines")]
+    pub reflow: String,
+
+    #[structopt (long="minimal", help="Leave out the boilerplate sections: DEFINES, COPYRIGHT, and the other functions in SEE ALSO")]
+    pub minimal: bool,
+
+    #[structopt (long="no-tidy", help="Don't tidy up the whitespace and paragraph breaks in the pages before writing them")]
+    pub no_tidy: bool,
+
+    #[structopt (long="language", default_value="any", possible_values=&["any", "c", "c++"], help="Skip XML files for compounds that aren't C or C++, and with c take the C++ members (namespaces, classes, operators, templates) out of the rest and the global :: and extern \"C\" off their names")]
+    pub language: String,
+
+    #[structopt (long="compat", default_value="none", possible_values=&["none", "c"], help="Make the man pages as close as possible to the ones from the C doxygen2man in libqb (c): no tidying, no links to the other pages and no fonts for code and parameter names in the text")]
+    pub compat: String,
+
+    #[structopt (long="metadata", help="Say which doxygen2man version and XML file made each page, and a hash of its contents, in comments at the top")]
+    pub metadata: bool,
+
+    #[structopt (long="symbols", default_value="keep", possible_values=&["keep", "strip", "text", "escape"], help="What to do with emoji and typographic symbols in the pages: keep them, strip them, write them as text (eg :warning: or --) or as \\[uXXXX] escapes. Without --utf8 the characters that are kept are written as \\[uXXXX] escapes")]
+    pub symbols: String,
+
+    #[structopt (long="source-width", default_value="0", help="Split the text lines of the man page source at <source-width> columns, at spaces where it doesn't change how the page looks (0 leaves them alone)")]
+    pub source_width: usize,
+
+    #[structopt (long="section-markers", help="Put \".\\\" d2m:section NAME begin\" and \"end\" comments around each section of the man pages, for tools that change them")]
+    pub section_markers: bool,
+
+    #[structopt (long="no-hyphenation", help="Turn off hyphenation for the whole of the man pages, so long identifiers aren't split")]
+    pub no_hyphenation: bool,
+
+    #[structopt (long="preamble", default_value="", help="File of troff (eg macro definitions or .ds strings) to put after the .TH line of every man page")]
+    pub preamble: String,
+
+    #[structopt (long="utf8", help="Declare the man pages as UTF-8 for groff's preconv and keep all their characters (--symbols is ignored), for systems where man is set up for UTF-8")]
+    pub utf8: bool,
+
+    #[structopt (long="link-defines", help="Add the #defines that a function's prototype or documentation mentions to a DEFINES section on its page")]
+    pub link_defines: bool,
+
+    #[structopt (long="example-macros", help="Use .EX and .EE for program listings rather than .nf and .fi, for a constant-width font with groff and mandoc")]
+    pub example_macros: bool,
+
+    #[structopt (long="listing-line-numbers", help="Number the lines of program listings (eg from \\code), for descriptions that refer to them")]
+    pub listing_line_numbers: bool,
+
+    #[structopt (long="params-table", help="Print PARAMETERS as a table of the names, directions (from @param[in] etc) and descriptions, rather than a list")]
+    pub params_table: bool,
+
+    #[structopt (long="retval-table", help="Print the \\retval values in RETURN VALUE as a table of the values and what they mean, rather than a list")]
+    pub retval_table: bool,
+
+    #[structopt (short="g", long="print-general", help="Print general man page for the whole header file")]
+    pub print_general: bool,
+
+    #[structopt (long="general-synopsis", help="List the prototypes of all of the functions in the SYNOPSIS of the general page, one per line")]
+    pub general_synopsis: bool,
+
+    #[structopt (long="constants", help="Add a CONSTANTS section to the general page listing the enum values and #defines with their brief descriptions")]
+    pub constants: bool,
+
+    #[structopt (long="defines-page", default_value="", help="Put the #defines on their own page, <header>_defines, in this section (eg 3 or 7) rather than on the general page")]
+    pub defines_page: String,
+
+    #[structopt (long="only", alias="function", use_delimiter=true, number_of_values=1, help="Only write the pages for these functions (comma-separated, can be repeated). The general page is still written with -g")]
+    pub only: Vec<String>,
+
+    #[structopt (long="type-pages", help="Also write a page for each structure and enum, eg <name>.3type")]
+    pub type_pages: bool,
+
+    #[structopt (long="enum-pages", help="Also write a page for each documented enum, eg <name>.3type, and refer to it from the functions that take it")]
+    pub enum_pages: bool,
+
+    #[structopt (long="callback-pages", help="Also write a page for each documented callback (function pointer) typedef")]
+    pub callback_pages: bool,
+
+    #[structopt (long="main-page", help="Also write <package-name>.7, an overview page made from doxygen's \\mainpage with an index of all the pages written")]
+    pub main_page: bool,
+
+    #[structopt (long="removed", number_of_values=1, help="Also write a page for a function that has been removed, as <name>=<version>[:<replacement>], eg old_open=2.0:new_open. Can be repeated")]
+    pub removed: Vec<String>,
+
+    #[structopt (long="removed-file", default_value="", help="Read the functions that have been removed from <removed-file>, one \"<name> <version> [<replacement>]\" per line, and write pages for them")]
+    pub removed_file: String,
+
+    #[structopt (long="index-page", help="Also write <package-name>_index.<section>, listing every function page in alphabetical order with its description")]
+    pub index_page: bool,
+
+    #[structopt (long="doc-pages", help="Also write a section 7 page for each doxygen \\page, eg tutorials, named after the page")]
+    pub doc_pages: bool,
+
+    #[structopt (long="include-static", help="Write pages for static (usually static inline) functions in the headers. This is the default")]
+    pub include_static: bool,
+
+    #[structopt (long="public-prefix", use_delimiter=true, number_of_values=1, help="Only write pages for the functions and types whose names start with one of these prefixes (comma-separated, can be repeated), eg qb_")]
+    pub public_prefix: Vec<String>,
+
+    #[structopt (long="invert-prefix", help="Only write pages for the functions and types whose names don't start with one of the --public-prefix prefixes")]
+    pub invert_prefix: bool,
+
+    #[structopt (long="exclude-static", help="Don't write pages for static (usually static inline) functions in the headers")]
+    pub exclude_static: bool,
+
+    #[structopt (long="include-internal", help="Include \\internal documentation (from doxygen's INTERNAL_DOCS) in the pages, and write pages for functions that only have internal documentation")]
+    pub include_internal: bool,
+
+    #[structopt (long="enabled-sections", use_delimiter=true, number_of_values=1, help="Leave out functions in \\cond blocks whose section labels are not in <enabled-sections> (comma-separated, can be repeated, empty for none). The header files must be in --header_src_dir or where doxygen found them")]
+    pub enabled_sections: Vec<String>,
+
+    #[structopt (short="q", long="quiet", help="Run quietly, no progress info printed")]
+    pub _quiet: bool,
+
+    #[structopt (long="run-doxygen", help="Input files are header files or a Doxyfile, run doxygen on them first")]
+    pub run_doxygen: bool,
+
+    #[structopt (long="kernel-doc", help="Input files are C files with kernel-doc comments, read them directly instead of doxygen's XML")]
+    pub kernel_doc: bool,
+
+    #[cfg(feature = "libclang")]
+    #[structopt (long="clang", help="Input files are C header files, read them with libclang instead of doxygen")]
+    pub clang: bool,
+
+    #[cfg(feature = "libclang")]
+    #[structopt (long="clang-arg", number_of_values=1, help="Pass <clang-arg> to libclang when reading headers with --clang (eg -Iinclude), can be repeated")]
+    pub clang_args: Vec<String>,
+
+    #[structopt (long="streaming", help="Write each page as soon as it has been read, to save memory with very large XML files")]
+    pub streaming: bool,
+
+    #[structopt (long="no-clobber", help="Don't overwrite existing man pages that were not generated by doxygen2man")]
+    pub no_clobber: bool,
+
+    #[structopt (long="ref-map", number_of_values=1, help="Read a map of refids or names to pages in other libraries from <ref-map>, one \"<refid or name> <page>(<section>)\" per line, can be repeated")]
+    pub ref_map: Vec<String>,
+
+    #[structopt (long="rename-map", default_value="", help="Read functions that have been renamed from <rename-map>, one \"<old name> <new name>\" per line. The old names get .so pages for the new ones")]
+    pub rename_map: String,
+
+    #[structopt (long="tagfile", number_of_values=1, help="Link references to the functions and #defines in the doxygen tag file <tagfile> to their pages. Use <file>=<section> if they are not in section 3. Can be repeated")]
+    pub tagfiles: Vec<String>,
+
+    #[structopt (short="x", long="xref-sections", help="Print xrefsects (eg from custom ALIASES) in sections named after their titles")]
+    pub xref_sections: bool,
+
+    #[structopt (short="c", long="use-header-copyright", help="Use the Copyright date from the header file (if one can be found)")]
+    pub use_header_copyright: bool,
+
+    #[structopt (long="copyright-regex", default_value="", help="Regular expression for the copyright lines in the header file with -c (its first group is used if it has one)")]
+    pub copyright_regex: String,
+
+    #[structopt (short="I", long="headerfile", default_value="unknown.h", help="Set include filename (default taken from XML)")]
+    pub headerfile: String,
+
+    #[structopt (short="i", long="header-prefix", default_value="", help="prefix for includefile. eg qb/")]
+    pub header_prefix: String,
+
+    #[structopt (short="s", long="section", default_value="3", help="write man pages into section <section>")]
+    pub man_section: u32,
+
+    #[structopt (long="years-from-git", help="Take the copyright years from the git history of the header file (in --header_src_dir) instead of -S and -Y")]
+    pub years_from_git: bool,
+
+    #[structopt (short="S", long="start-year", default_value="2010", help="Start year to print at end of copyright line")]
+    pub start_year: u32,
+
+    #[structopt (short="d", long="xml-dir", default_value="./xml/", help="Directory for XML files")]
+    pub xml_dir: String,
+
+    #[structopt (short="D", long="manpage-date", default_value="2010", help="Date to print at top of man pages (format not checked, empty for today or SOURCE_DATE_EPOCH)")]
+    pub manpage_date: String,
+
+    #[structopt (long="date-from-git", help="Date the pages with the last commit to the header file (in --header_src_dir) instead of -D")]
+    pub date_from_git: bool,
+
+    #[structopt (short="Y", long="manpage-year", default_value="2010", help="Year to print at end of copyright line (0 for this year or SOURCE_DATE_EPOCH)")]
+    pub manpage_year: i32,
+
+    #[structopt (short="p", long="package-name", default_value="Package", help="Name of package for these man pages")]
+    pub package_name: String,
+
+    #[structopt (long="source-version", default_value="", help="Version of the package for the top of the pages, or \"auto\" for git describe in --header_src_dir")]
+    pub source_version: String,
+
+    #[structopt (long="version-section", help="Add a VERSION section with --source-version to the function pages")]
+    pub version_section: bool,
+
+    #[structopt (short="H", long="header-name", default_value="Programmer's Manual", help="Header text")]
+    pub header: String,
+
+    #[structopt (short="o", long="output_dir", default_value="./", help="Write all man pages to <dir>")]
+    pub output_dir: String,
+
+    #[structopt (short="O", long="header_src_dir", default_value="./", help="Directory for the original header files (often needed by -c above)")]
+    pub header_src_dir: String,
+
+    #[structopt (short="C", long="company", default_value="Red Hat Inc", help="Company name in copyright")]
+    pub company: String,
+
+    #[cfg(feature = "json")]
+    #[structopt (short="j", long="write-json", help="Write the parsed model to <output-dir>/<headerfile>.json")]
+    pub write_json: bool,
+
+    #[cfg(feature = "json")]
+    #[structopt (long="from-json", help="Input files are JSON models written by --write-json, not XML")]
+    pub from_json: bool,
+
+    #[cfg(feature = "json")]
+    #[structopt (long="filter", number_of_values=1, help="Pass the model as JSON through <filter> (a shell command) before writing the pages, can be repeated")]
+    pub filter: Vec<String>,
+
+    #[cfg(feature = "wasm")]
+    #[structopt (long="wasm-filter", number_of_values=1, help="Pass the model through the WASM module <wasm-filter> before writing the pages, can be repeated")]
+    pub wasm_filter: Vec<String>,
+
+    #[cfg(feature = "templates")]
+    #[structopt (short="T", long="template-dir", default_value="", help="Directory containing function.<section>.tera and/or general.<section>.tera page templates")]
+    pub template_dir: String,
+
+    #[cfg(feature = "tarball")]
+    #[structopt (long="output-tar", default_value="", help="Write the pages into the tar file <output-tar> (gzipped if it ends in .gz or .tgz) instead of <output-dir>")]
+    pub output_tar: String,
+
+    #[cfg(feature = "docset")]
+    #[structopt (long="docset", default_value="", help="Also write HTML versions of the pages into the Dash/Zeal docset <docset> (eg libqb.docset), with a search index of the functions, types and macros")]
+    pub docset: String,
+
+    #[cfg(feature = "browse")]
+    #[structopt (long="browse", help="Rather than writing the pages, list them on the terminal with a preview of the selected one, / searches the list")]
+    pub browse: bool,
+
+    #[structopt (long="devhelp", default_value="", help="Also write HTML versions of the pages into the Devhelp book directory <devhelp>, with the index in <devhelp>/<package-name>.devhelp2")]
+    pub devhelp: String,
+
+    #[structopt (long="depfile", default_value="", help="Write a make-style depfile of the pages and the files they were made from, for Meson or CMake")]
+    pub depfile: String,
+
+    #[structopt (short="M", long="write-deps", default_value="", help="Write make rules for each page on the XML and header files it was made from to <write-deps>")]
+    pub write_deps: String,
+
+    #[structopt (long="output-list", default_value="", help="Write the names of all the pages written to <output-list>, one per line")]
+    pub output_list: String,
+
+    #[structopt (long="manifest", default_value="", help="Write a JSON manifest of the pages written to <manifest>, with the XML file and header each was made from, its section and the symbols it documents")]
+    pub manifest: String,
+
+    #[structopt (long="preview", default_value="", help="Write the pages to a temporary directory and show the one for <preview> (a function or header name) with --preview-command")]
+    pub preview: String,
+
+    #[structopt (long="preview-command", default_value="man -l", help="Command used by --preview, the page file is added to the end")]
+    pub preview_command: String,
+
+    #[structopt (long="stdout", default_value="", help="Print the man page for <stdout> (a function or header name) to stdout instead of writing any files")]
+    pub stdout: String,
+
+    #[structopt (long="packaging-lists", help="Write <package-name>.manpages (for Debian) and <package-name>.files (for RPM %files) to <output-dir>")]
+    pub packaging_lists: bool,
+
+    #[structopt (long="rpm-man-suffix", default_value="*", help="Added to the page names in the RPM %files list, for the compressed pages")]
+    pub rpm_man_suffix: String,
+
+    #[structopt (long="diagnostics", default_value="text", possible_values=&["text", "json"], help="Format of the warnings and errors about the input: text, or json for JSON Lines with severity, rule, input, function and message fields")]
+    pub diagnostics: String,
+
+    #[structopt (long="diagnostics-file", default_value="", help="Write the warnings and errors about the input to <diagnostics-file> instead of stderr")]
+    pub diagnostics_file: String,
+
+    #[structopt (long="sarif", default_value="", help="Also write the warnings and errors about the input to <sarif> as a SARIF log, at the header file and line of their functions, for code scanning")]
+    pub sarif: String,
+
+    #[structopt (long="strict-empty", help="Make an input file with no functions, structures or #defines in it an error (and the exit status 1) rather than a warning")]
+    pub strict_empty: bool,
+
+    #[structopt (long="coverage", default_value="", help="Write how much of each function is documented to <coverage>, as JSON if it ends in .json or CSV otherwise")]
+    pub coverage: String,
+
+    #[structopt (long="min-coverage", default_value="0", help="Fail if less than this percentage of the functions are fully documented")]
+    pub min_coverage: f64,
+
+    #[structopt (long="min-param-coverage", default_value="0", help="Fail if less than this percentage of the parameters are documented")]
+    pub min_param_coverage: f64,
+
+    #[structopt (long="baseline", default_value="", help="File of symbols that are allowed to be undocumented (eg legacy API). Anything else that isn't documented is an error")]
+    pub baseline: String,
+
+    #[structopt (long="write-baseline", default_value="", help="Write everything that isn't documented to <write-baseline>, for --baseline")]
+    pub write_baseline: String,
+
+    #[structopt (long="write-pot", default_value="", help="Write the paragraphs of the documentation to <write-pot> as a gettext POT file, for translators")]
+    pub write_pot: String,
+
+    #[structopt (long="po", default_value="", help="Use the translations in the gettext PO file <po> for the documentation in the pages")]
+    pub po: String,
+
+    #[structopt (long="serve", help="Serve the pages as HTML on http://localhost:<port>/ with an index, making them again when the input changes")]
+    pub serve: bool,
+
+    #[structopt (long="port", default_value="8080", help="The port for --serve")]
+    pub port: u16,
+
+    #[structopt (long="dry-run", help="Write the pages to a temporary directory that is removed at the end, eg for --lint")]
+    pub dry_run: bool,
+
+    #[structopt (long="list", help="Print the names of the pages that would be written, without writing them")]
+    pub list: bool,
+
+    #[structopt (long="verify", default_value="", help="Don't write the pages, compare them with the ones in the directory <verify> and print the differences")]
+    pub verify: String,
+
+    #[structopt (long="lint", help="Check the pages written for problems, eg NAME lines that apropos can't read")]
+    pub lint: bool,
+
+    #[structopt (long="spellcheck", help="Check the spelling of the documentation with hunspell and report the words it doesn't know")]
+    pub spellcheck: bool,
+
+    #[structopt (long="spell-dict", default_value="", help="hunspell dictionary for --spellcheck, eg en_GB (default is hunspell's, from the locale)")]
+    pub spell_dict: String,
+
+    #[structopt (long="spell-words", default_value="", help="File of the project's own words for --spellcheck, one per line")]
+    pub spell_words: String,
+
+    #[structopt (long="update-mandb", help="Update the man page index for <output-dir> when finished, so the pages can be found with man -k")]
+    pub update_mandb: bool,
+
+    #[structopt (long="mandb-command", default_value="mandb -q", help="Command used by --update-mandb, the manpath is added to the end (eg makewhatis)")]
+    pub mandb_command: String,
+
+    #[structopt (long="timings", help="Print how long was spent parsing, reading structures, rendering and writing, for each file and in total")]
+    pub timings: bool,
+
+    #[structopt (short="f", long="force", help="Write all pages, even if they are newer than their input files")]
+    pub force: bool,
+
+    // Positional parameters
+    #[structopt (help="XML files to process", required = true)]
+    pub xml_files: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(a: &[&str]) -> Vec<String>
+    {
+        a.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn subcommands()
+    {
+        assert_eq!(expand_subcommand(args(&["doxygen2man", "check", "man", "-d", "xml", "a_8h.xml"])),
+                   args(&["doxygen2man", "--verify", "man", "-d", "xml", "a_8h.xml"]));
+        assert_eq!(expand_subcommand(args(&["doxygen2man", "lint", "a_8h.xml"])),
+                   args(&["doxygen2man", "--lint", "--dry-run", "a_8h.xml"]));
+        // Only at the start, so an XML file called "list" is left alone
+        assert_eq!(expand_subcommand(args(&["doxygen2man", "-m", "list"])), args(&["doxygen2man", "-m", "list"]));
+        assert_eq!(expand_subcommand(args(&["doxygen2man"])), args(&["doxygen2man"]));
+    }
+
+    #[test]
+    fn subcommand_options()
+    {
+        let opt = Opt::from_iter(expand_subcommand(args(&["doxygen2man", "generate", "a_8h.xml"])));
+        assert!(opt.print_man);
+        assert_eq!(opt.xml_files, ["a_8h.xml"]);
+    }
+}
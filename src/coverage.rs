@@ -0,0 +1,181 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// How much of each function is documented, for --coverage, and what isn't,
+// for --baseline.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Error, Write};
+use crate::diagnostics::json_string;
+use crate::model::FunctionInfo;
+use crate::render::{is_noreturn, write_atomically};
+
+// The documentation coverage of one function
+#[derive(Debug, Clone)]
+pub struct FunctionCoverage {
+    pub function: String,
+    pub header: String,
+    pub has_brief: bool,
+    pub has_detail: bool,
+    // false for void (and noreturn) functions, which don't need their return value documented
+    pub returns_value: bool,
+    pub has_return_doc: bool,
+    pub params: usize,
+    pub documented_params: usize,
+}
+
+impl FunctionCoverage {
+    pub fn new(header: &str, f: &FunctionInfo) -> FunctionCoverage
+    {
+        let args: Vec<_> = f.fn_args.iter().filter(|p| !p.par_name.is_empty()).collect();
+        FunctionCoverage {
+            function: f.fn_name.clone(),
+            header: header.to_string(),
+            has_brief: !f.fn_brief.trim().is_empty(),
+            has_detail: !f.fn_detail.trim().is_empty(),
+            returns_value: f.fn_type.trim() != "void" && !is_noreturn(f),
+            has_return_doc: !f.fn_returnval.trim().is_empty() || !f.fn_retvals.is_empty(),
+            params: args.len(),
+            documented_params: args.iter().filter(|p| !p.par_desc.trim().is_empty()).count(),
+        }
+    }
+
+    // Whether everything that needs documenting is: a description, all the
+    // parameters and the return value (if it has one)
+    pub fn is_documented(&self) -> bool
+    {
+        (self.has_brief || self.has_detail) &&
+            self.documented_params == self.params &&
+            (self.has_return_doc || !self.returns_value)
+    }
+}
+
+// The totals for a set of functions
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CoverageTotals {
+    pub functions: usize,
+    pub documented_functions: usize,
+    pub params: usize,
+    pub documented_params: usize,
+}
+
+impl CoverageTotals {
+    pub fn new(entries: &[FunctionCoverage]) -> CoverageTotals
+    {
+        CoverageTotals {
+            functions: entries.len(),
+            documented_functions: entries.iter().filter(|c| c.is_documented()).count(),
+            params: entries.iter().map(|c| c.params).sum(),
+            documented_params: entries.iter().map(|c| c.documented_params).sum(),
+        }
+    }
+
+    // The percentage of the functions that are documented (0 if there are none)
+    pub fn function_percent(&self) -> f64
+    {
+        percent(self.documented_functions, self.functions)
+    }
+
+    // The percentage of the parameters that are documented (0 if there are none)
+    pub fn param_percent(&self) -> f64
+    {
+        percent(self.documented_params, self.params)
+    }
+}
+
+fn percent(n: usize, total: usize) -> f64
+{
+    if total == 0 {
+        0.0
+    } else {
+        n as f64 * 100.0 / total as f64
+    }
+}
+
+// Something in a function that isn't documented. The symbol is the function
+// name for its description, or "function:parameter" or "function:return".
+#[derive(Debug, Clone)]
+pub struct Undocumented {
+    pub rule: &'static str,
+    pub symbol: String,
+    pub message: String,
+}
+
+// The things in a function that aren't documented
+pub fn undocumented_symbols(f: &FunctionInfo) -> Vec<Undocumented>
+{
+    let c = FunctionCoverage::new("", f);
+    let mut symbols = Vec::new();
+    if !c.has_brief && !c.has_detail {
+        symbols.push(Undocumented {rule: "undocumented-function", symbol: f.fn_name.clone(),
+                                   message: "function is not documented".to_string()});
+    }
+    for p in f.fn_args.iter().filter(|p| !p.par_name.is_empty() && p.par_desc.trim().is_empty()) {
+        symbols.push(Undocumented {rule: "undocumented-param", symbol: format!("{}:{}", f.fn_name, p.par_name),
+                                   message: format!("parameter '{}' is not documented", p.par_name)});
+    }
+    if c.returns_value && !c.has_return_doc {
+        symbols.push(Undocumented {rule: "undocumented-return", symbol: format!("{}:return", f.fn_name),
+                                   message: "return value is not documented".to_string()});
+    }
+    symbols
+}
+
+// Read a baseline file: one symbol per line, blank lines and lines starting with # are ignored
+pub fn read_baseline(baseline_file: &str) -> Result<BTreeSet<String>, Error>
+{
+    Ok(fs::read_to_string(baseline_file)?
+       .lines()
+       .map(|l| l.trim())
+       .filter(|l| !l.is_empty() && !l.starts_with('#'))
+       .map(|l| l.to_string())
+       .collect())
+}
+
+// Write the symbols that aren't documented as a baseline file, for --baseline
+pub fn write_baseline(baseline_file: &str, symbols: &BTreeSet<String>) -> Result<(), Error>
+{
+    write_atomically(baseline_file, |f| {
+        writeln!(f, "# Symbols that are allowed to be undocumented, written by doxygen2man --write-baseline.")?;
+        writeln!(f, "# \"function\" is its description, \"function:param\" a parameter and \"function:return\" its return value.")?;
+        for s in symbols {
+            writeln!(f, "{s}")?;
+        }
+        Ok(())
+    })
+}
+
+// Write a coverage report, as JSON if the file name ends in .json and CSV otherwise
+pub fn write_coverage(coverage_file: &str, entries: &[FunctionCoverage]) -> Result<(), Error>
+{
+    if coverage_file.ends_with(".json") {
+        write_atomically(coverage_file, |f| {
+            writeln!(f, "[")?;
+            for (i, c) in entries.iter().enumerate() {
+                let delim = if i + 1 == entries.len() { "" } else { "," };
+                writeln!(f, "  {{\"function\": {}, \"header\": {}, \"has_brief\": {}, \"has_detail\": {}, \"returns_value\": {}, \"has_return_doc\": {}, \"params\": {}, \"documented_params\": {}, \"documented\": {}}}{}",
+                         json_string(&c.function), json_string(&c.header), c.has_brief, c.has_detail,
+                         c.returns_value, c.has_return_doc, c.params, c.documented_params, c.is_documented(), delim)?;
+            }
+            writeln!(f, "]")?;
+            Ok(())
+        })
+    } else {
+        write_atomically(coverage_file, |f| {
+            writeln!(f, "function,header,has_brief,has_detail,returns_value,has_return_doc,params,documented_params,documented")?;
+            for c in entries {
+                writeln!(f, "{},{},{},{},{},{},{},{},{}",
+                         c.function, c.header, c.has_brief, c.has_detail,
+                         c.returns_value, c.has_return_doc, c.params, c.documented_params, c.is_documented())?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,131 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Lists of the pages we wrote and the files they came from, for build systems
+// and packaging
+
+use std::io::Write;
+use std::path::Path;
+use doxygen2man::cli::Opt;
+use doxygen2man::diagnostics::json_string;
+use doxygen2man::render::{write_atomically, ManPage};
+
+// Escape a filename for a make-style rule
+fn make_escape(filename: &str) -> String
+{
+    filename.replace('$', "$$").replace(' ', "\\ ").replace('#', "\\#")
+}
+
+// Add the files from a list to a make rule, one per continuation line
+fn write_make_files<W: Write, S: AsRef<str>>(f: &mut W, files: &[S]) -> Result<(), std::io::Error>
+{
+    for (i, name) in files.iter().enumerate() {
+        if i > 0 {
+            write!(f, " \\\n ")?;
+        }
+        write!(f, "{}", make_escape(name.as_ref()))?;
+    }
+    Ok(())
+}
+
+// Write a depfile (as used by Meson custom_target and CMake add_custom_command) with
+// all the pages as targets of all the files they were made from
+pub fn write_depfile(depfile: &str, pages: &[ManPage]) -> Result<(), std::io::Error>
+{
+    let outputs: Vec<&str> = pages.iter().map(|p| p.file.as_str()).collect();
+    let mut inputs = Vec::<&str>::new();
+    for p in pages {
+        for i in &p.inputs {
+            if !inputs.contains(&i.as_str()) {
+                inputs.push(i);
+            }
+        }
+    }
+
+    write_atomically(depfile, |f| {
+        write_make_files(f, &outputs)?;
+        write!(f, ":")?;
+        if !inputs.is_empty() {
+            write!(f, " \\\n ")?;
+            write_make_files(f, &inputs)?;
+        }
+        writeln!(f)
+    })
+}
+
+// Write make rules for each page on the files it was made from
+pub fn write_make_deps(deps_file: &str, pages: &[ManPage]) -> Result<(), std::io::Error>
+{
+    write_atomically(deps_file, |f| {
+        for p in pages {
+            write!(f, "{}:", make_escape(&p.file))?;
+            if !p.inputs.is_empty() {
+                write!(f, " \\\n ")?;
+                write_make_files(f, &p.inputs)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    })
+}
+
+// Write the names of all the pages, one per line
+pub fn write_output_list(list_file: &str, pages: &[ManPage]) -> Result<(), std::io::Error>
+{
+    write_atomically(list_file, |f| {
+        for p in pages {
+            writeln!(f, "{}", p.file)?;
+        }
+        Ok(())
+    })
+}
+
+// Write a JSON manifest of the pages: the file, the XML file and header it was
+// made from, its section and the symbols it documents
+pub fn write_manifest(manifest_file: &str, pages: &[ManPage]) -> Result<(), std::io::Error>
+{
+    let json_list = |l: &[String]| l.iter().map(|s| json_string(s)).collect::<Vec<String>>().join(", ");
+    write_atomically(manifest_file, |f| {
+        writeln!(f, "[")?;
+        for (i, p) in pages.iter().enumerate() {
+            let delim = if i + 1 == pages.len() { "" } else { "," };
+            let source = p.inputs.first().map_or("", |s| s.as_str());
+            let section = Path::new(&p.file).extension().map_or(String::new(), |e| e.to_string_lossy().to_string());
+            writeln!(f, "  {{\"page\": {}, \"kind\": {}, \"source\": {}, \"header\": {}, \"section\": {}, \"symbols\": [{}]}}{}",
+                     json_string(&p.file), json_string(p.kind.name()), json_string(source), json_string(&p.header),
+                     json_string(&section), json_list(&p.symbols), delim)?;
+        }
+        writeln!(f, "]")?;
+        Ok(())
+    })
+}
+
+// Write <package>.manpages (for dh_installman) and <package>.files (an RPM %files
+// fragment) into the output directory, so packagers don't need to keep the
+// lists of pages up to date by hand
+pub fn write_packaging_lists(opt: &Opt, pages: &[ManPage]) -> Result<(), std::io::Error>
+{
+    let debian_list = format!("{}/{}.manpages", opt.output_dir, opt.package_name);
+    write_atomically(&debian_list, |f| {
+        for p in pages {
+            writeln!(f, "{}", p.file)?;
+        }
+        Ok(())
+    })?;
+
+    let rpm_list = format!("{}/{}.files", opt.output_dir, opt.package_name);
+    write_atomically(&rpm_list, |f| {
+        for p in pages {
+            if let Some(n) = Path::new(&p.file).file_name() {
+                writeln!(f, "%{{_mandir}}/man{}/{}{}", opt.man_section, n.to_string_lossy(), opt.rpm_man_suffix)?;
+            }
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,255 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Warnings and errors about the input, eg parameters that don't match the
+// documentation or structure files that are missing. They are printed as text
+// on stderr, or with --diagnostics json as JSON Lines (one object per line, with
+// severity, rule, input, function and message) for CI tools to annotate with.
+// With --sarif they are also kept, and written as a SARIF log at the end with
+// the header file and line of their functions, for code scanning.
+//
+// They come from deep in the parser (and its threads) where there is no Opt,
+// so where they go is set once at the start of the run.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{Error, Write};
+use std::sync::Mutex;
+use crate::model::FunctionInfo;
+use crate::render::write_atomically;
+
+// The kinds of diagnostic, as rule ids and descriptions for SARIF
+pub const RULES: &[(&str, &str)] = &[
+    ("unknown-param", "A documented parameter does not match any of the function's arguments"),
+    ("unknown-param-ref", "A parameter reference in the text does not match any of the function's arguments"),
+    ("undocumented-param", "A parameter of a function with a man page has no description"),
+    ("undocumented-function", "A function with a man page has no description, and is not in the --baseline file"),
+    ("undocumented-return", "The return value of a function with a man page is not documented, and is not in the --baseline file"),
+    ("unsupported-markup", "doxygen markup that is not supported, only its text is kept"),
+    ("missing-structure", "A structure's XML file could not be read"),
+    ("unreadable-file", "A file needed for an option could not be read"),
+    ("input-error", "An input file could not be read"),
+    ("empty-input", "An input file has no functions, structures or #defines in it"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn name(self) -> &'static str
+    {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    fn title(self) -> &'static str
+    {
+        match self {
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        }
+    }
+}
+
+// A diagnostic kept for --sarif
+struct Record {
+    severity: Severity,
+    rule: &'static str,
+    input: String,
+    function: String,
+    // Where the function is, filled in by locate() once the file has been read
+    file: String,
+    line: u32,
+    message: String,
+}
+
+struct Diagnostics {
+    json: bool,
+    file: Option<File>,
+    // The file being read, for the "input" field
+    input: String,
+    // Things that only need saying once for each input file
+    said: BTreeSet<String>,
+    keep: bool,
+    records: Vec<Record>,
+}
+
+static DIAGNOSTICS: Mutex<Diagnostics> = Mutex::new(Diagnostics {
+    json: false,
+    file: None,
+    input: String::new(),
+    said: BTreeSet::new(),
+    keep: false,
+    records: Vec::new(),
+});
+
+// Set the format ("text" or "json") and where they go (a file, or stderr if it's empty)
+pub fn set_output(format: &str, file: &str) -> Result<(), Error>
+{
+    let file = if file.is_empty() {
+        None
+    } else {
+        Some(File::create(file)?)
+    };
+    if let Ok(mut d) = DIAGNOSTICS.lock() {
+        d.json = format == "json";
+        d.file = file;
+    }
+    Ok(())
+}
+
+// Keep the diagnostics, to write them with write_sarif() at the end
+pub fn keep_records()
+{
+    if let Ok(mut d) = DIAGNOSTICS.lock() {
+        d.keep = true;
+    }
+}
+
+// Set the input file that the diagnostics are about
+pub fn set_input(input: &str)
+{
+    if let Ok(mut d) = DIAGNOSTICS.lock() {
+        d.input = input.to_string();
+        d.said.clear();
+    }
+}
+
+// s as a JSON string, with its quotes
+pub fn json_string(s: &str) -> String
+{
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Report something. rule is one of the RULES, function can be empty if it isn't about one.
+pub fn report(severity: Severity, rule: &'static str, function: &str, message: &str)
+{
+    let mut d = match DIAGNOSTICS.lock() {
+        Ok(d) => d,
+        Err(_) => return,
+    };
+    if d.keep {
+        let input = d.input.clone();
+        d.records.push(Record {severity, rule, input, function: function.to_string(),
+                               file: String::new(), line: 0, message: message.to_string()});
+    }
+    let line = if d.json {
+        format!("{{\"severity\":{},\"rule\":{},\"input\":{},\"function\":{},\"message\":{}}}",
+                json_string(severity.name()), json_string(rule), json_string(&d.input), json_string(function), json_string(message))
+    } else if function.is_empty() {
+        format!("{}: {}", severity.title(), message)
+    } else {
+        format!("{}: {}: {}", severity.title(), function, message)
+    };
+    match &mut d.file {
+        Some(f) => {
+            let _ = writeln!(f, "{line}");
+        }
+        None => eprintln!("{line}"),
+    }
+}
+
+pub fn warning(rule: &'static str, function: &str, message: &str)
+{
+    report(Severity::Warning, rule, function, message);
+}
+
+// A warning that is only given once for each input file, eg for each kind of
+// element we don't understand rather than every one of them
+pub fn warning_once(rule: &'static str, function: &str, message: &str)
+{
+    let first = match DIAGNOSTICS.lock() {
+        Ok(mut d) => d.said.insert(message.to_string()),
+        Err(_) => false,
+    };
+    if first {
+        report(Severity::Warning, rule, function, message);
+    }
+}
+
+pub fn error(rule: &'static str, function: &str, message: &str)
+{
+    report(Severity::Error, rule, function, message);
+}
+
+// Fill in where the functions that the kept diagnostics for the current input
+// are about are. The parser doesn't know that until it has read all of the function.
+pub fn locate(functions: &[FunctionInfo])
+{
+    let mut d = match DIAGNOSTICS.lock() {
+        Ok(d) => d,
+        Err(_) => return,
+    };
+    let input = d.input.clone();
+    for r in d.records.iter_mut().filter(|r| r.input == input && r.file.is_empty()) {
+        if let Some(f) = functions.iter().find(|f| f.fn_name == r.function) {
+            r.file = f.fn_file.clone();
+            r.line = f.fn_line;
+        }
+    }
+}
+
+// Write the kept diagnostics as a SARIF 2.1.0 log. Those that aren't about a
+// function (or one whose location we don't know) are put at the start of the
+// input file.
+pub fn write_sarif(sarif_file: &str) -> Result<(), Error>
+{
+    let d = match DIAGNOSTICS.lock() {
+        Ok(d) => d,
+        Err(_) => return Err(Error::other("diagnostics are not available")),
+    };
+    write_atomically(sarif_file, |f| {
+        writeln!(f, "{{")?;
+        writeln!(f, "  \"version\": \"2.1.0\",")?;
+        writeln!(f, "  \"$schema\": \"https://json.schemastore.org/sarif-2.1.0.json\",")?;
+        writeln!(f, "  \"runs\": [{{")?;
+        writeln!(f, "    \"tool\": {{\"driver\": {{")?;
+        writeln!(f, "      \"name\": \"doxygen2man\",")?;
+        writeln!(f, "      \"version\": {},", json_string(env!("CARGO_PKG_VERSION")))?;
+        writeln!(f, "      \"rules\": [")?;
+        for (i, (id, desc)) in RULES.iter().enumerate() {
+            let delim = if i + 1 == RULES.len() { "" } else { "," };
+            writeln!(f, "        {{\"id\": {}, \"shortDescription\": {{\"text\": {}}}}}{}", json_string(id), json_string(desc), delim)?;
+        }
+        writeln!(f, "      ]")?;
+        writeln!(f, "    }}}},")?;
+        writeln!(f, "    \"results\": [")?;
+        for (i, r) in d.records.iter().enumerate() {
+            let delim = if i + 1 == d.records.len() { "" } else { "," };
+            let (uri, line) = if r.file.is_empty() { (&r.input, 1) } else { (&r.file, r.line.max(1)) };
+            let message = if r.function.is_empty() { r.message.clone() } else { format!("{}: {}", r.function, r.message) };
+            writeln!(f, "      {{\"ruleId\": {}, \"level\": {}, \"message\": {{\"text\": {}}},",
+                     json_string(r.rule), json_string(r.severity.name()), json_string(&message))?;
+            writeln!(f, "       \"locations\": [{{\"physicalLocation\": {{\"artifactLocation\": {{\"uri\": {}}}, \"region\": {{\"startLine\": {}}}}}}}]}}{}",
+                     json_string(uri), line, delim)?;
+        }
+        writeln!(f, "    ]")?;
+        writeln!(f, "  }}]")?;
+        writeln!(f, "}}")?;
+        Ok(())
+    })
+}
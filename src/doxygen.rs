@@ -0,0 +1,88 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --run-doxygen. Runs doxygen on the header files (or Doxyfile) given on the
+// command-line to make the XML files in a temporary directory, so small projects
+// don't need to set up doxygen themselves.
+
+use std::fs;
+use std::io::{Error, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use doxygen2man::cli::Opt;
+use doxygen2man::project::read_index;
+
+use crate::tempdir::TempDir;
+
+// Make a Doxyfile in dir for the input files. A Doxyfile on the command-line is
+// included, with the output settings overridden. Anything else is a header file.
+// Any --tagfiles are added to its TAGFILES and --enabled-sections to its ENABLED_SECTIONS.
+fn write_doxyfile(dir: &Path, inputs: &[String], tagfiles: &[String], enabled_sections: &[String]) -> Result<PathBuf, Error>
+{
+    let doxyfile = dir.join("Doxyfile");
+    let mut f = fs::File::create(&doxyfile)?;
+
+    let mut headers = Vec::<&str>::new();
+    for i in inputs {
+        if Path::new(i).file_name().is_some_and(|n| n.to_string_lossy().starts_with("Doxyfile")) {
+            writeln!(f, "@INCLUDE = \"{i}\"")?;
+        } else {
+            headers.push(i);
+        }
+    }
+    if !headers.is_empty() {
+        write!(f, "INPUT =")?;
+        for h in headers {
+            write!(f, " \"{h}\"")?;
+        }
+        writeln!(f)?;
+        // Headers without a \file comment are otherwise ignored by doxygen
+        writeln!(f, "EXTRACT_ALL = YES")?;
+    }
+
+    // So that doxygen makes <ref>s to the things in them (the =section is ours, not doxygen's)
+    for t in tagfiles {
+        let file = t.split_once('=').map_or(t.as_str(), |(f, _)| f);
+        writeln!(f, "TAGFILES += \"{file}\"")?;
+    }
+    for s in enabled_sections.iter().filter(|s| !s.is_empty()) {
+        writeln!(f, "ENABLED_SECTIONS += {s}")?;
+    }
+
+    writeln!(f, "OUTPUT_DIRECTORY = \"{}\"", dir.display())?;
+    writeln!(f, "GENERATE_XML = YES")?;
+    writeln!(f, "XML_OUTPUT = xml")?;
+    writeln!(f, "XML_PROGRAMLISTING = NO")?;
+    writeln!(f, "GENERATE_HTML = NO")?;
+    writeln!(f, "GENERATE_LATEX = NO")?;
+    writeln!(f, "GENERATE_MAN = NO")?;
+    writeln!(f, "GENERATE_RTF = NO")?;
+    writeln!(f, "QUIET = YES")?;
+    Ok(doxyfile)
+}
+
+// Run doxygen and point opt.xml_dir and opt.xml_files at what it made.
+// The XML files are in the returned directory, which is removed when it's dropped.
+pub fn run_doxygen(opt: &mut Opt) -> Result<TempDir, Error>
+{
+    let output = TempDir::new("doxygen")?;
+
+    let doxyfile = write_doxyfile(output.path(), &opt.xml_files, &opt.tagfiles, &opt.enabled_sections)?;
+    let status = match Command::new("doxygen").arg(&doxyfile).status() {
+        Ok(s) => s,
+        Err(e) => return Err(Error::new(e.kind(), format!("Cannot run doxygen: {e}"))),
+    };
+    if !status.success() {
+        return Err(Error::other(format!("doxygen failed: {status}")));
+    }
+
+    opt.xml_dir = output.path().join("xml").to_string_lossy().to_string();
+    opt.xml_files = read_index(&opt.xml_dir)?;
+    Ok(output)
+}
@@ -0,0 +1,158 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Reading and writing the model as JSON
+
+use std::collections::BTreeMap;
+use std::fmt::Write as fmtwrite;
+use std::fs::File;
+use std::io::{BufReader, Error, Write};
+use std::process::{Command, Stdio};
+use serde::{Serialize, Deserialize};
+
+use doxygen2man::cli::Opt;
+use doxygen2man::model::*;
+use doxygen2man::render::write_atomically;
+
+// The intermediate representation is written out as JSON with this version
+// number, which is bumped whenever an incompatible change is made.
+// The format is described in README.md
+const JSON_IR_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct JsonModelOut<'a>
+{
+    version: u32,
+    headerfile: &'a str,
+    functions: &'a [FunctionInfo],
+    structures: &'a BTreeMap<String, StructureInfo>,
+}
+
+#[derive(Deserialize)]
+struct JsonModel
+{
+    version: u32,
+    headerfile: String,
+    functions: Vec<FunctionInfo>,
+    structures: BTreeMap<String, StructureInfo>,
+}
+
+// Pass the model through a filter command as JSON on stdin, and read the
+// (possibly changed) model back from its stdout in the same format.
+// The command is run by the shell so it can be a pipeline.
+pub fn filter_model(opt: &Opt,
+                    command: &str,
+                    functions: &[FunctionInfo],
+                    structures: &BTreeMap<String, StructureInfo>) -> Result<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>), Error>
+{
+    let json = model_to_json(opt, functions, structures)?;
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("DOXYGEN2MAN_HEADER", &opt.headerfile)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // Write from another thread so a filter that starts writing
+    // before it has read all of its input can't block us
+    let mut stdin = match child.stdin.take() {
+        Some(s) => s,
+        None => return Err(Error::other("Cannot get stdin for filter")),
+    };
+    let writer = std::thread::spawn(move || stdin.write_all(&json));
+
+    let output = child.wait_with_output()?;
+    match writer.join() {
+        Ok(r) => r?,
+        Err(_) => return Err(Error::other("Error writing to filter")),
+    }
+    if !output.status.success() {
+        return Err(Error::other(format!("filter '{command}' failed: {}", output.status)));
+    }
+
+    model_from_json(&output.stdout)
+}
+
+// The model as a JSON byte string, for filters
+pub fn model_to_json(opt: &Opt,
+                     functions: &[FunctionInfo],
+                     structures: &BTreeMap<String, StructureInfo>) -> Result<Vec<u8>, Error>
+{
+    let model = JsonModelOut {
+        version: JSON_IR_VERSION,
+        headerfile: &opt.headerfile,
+        functions,
+        structures,
+    };
+    Ok(serde_json::to_vec(&model)?)
+}
+
+// Read back a model returned by a filter
+pub fn model_from_json(json: &[u8]) -> Result<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>), Error>
+{
+    let model: JsonModel = serde_json::from_slice(json)?;
+    if model.version != JSON_IR_VERSION {
+        return Err(Error::other(format!("unsupported model version {} (expected {JSON_IR_VERSION})", model.version)));
+    }
+    Ok((model.functions, model.structures))
+}
+
+// Write the parsed model to <output-dir>/<headerfile>.json
+pub fn write_json(opt: &Opt,
+                  functions: &[FunctionInfo],
+                  structures: &BTreeMap<String, StructureInfo>) -> Result<(), std::io::Error>
+{
+    let model = JsonModelOut {
+        version: JSON_IR_VERSION,
+        headerfile: &opt.headerfile,
+        functions,
+        structures,
+    };
+
+    let mut json_file = String::new();
+    if let Err(e) = write!(json_file, "{}/{}.json", &opt.output_dir, &opt.headerfile) {
+        eprintln!("Error making JSON filename: {e:?}");
+        return Err(Error::other("Error making filename"));
+    }
+    write_atomically(&json_file, |f| {
+        serde_json::to_writer_pretty(&mut *f, &model)?;
+        writeln!(f)
+    })
+}
+
+// Read a model previously written by --write-json (or prepared by some other tool)
+pub fn read_json_input(opt: &mut Opt, in_file: &str) -> Option<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>)>
+{
+    let f = match File::open(in_file) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("Cannot open JSON file {in_file}: {e}");
+            return None;
+        }
+    };
+
+    match serde_json::from_reader::<_, JsonModel>(BufReader::new(f)) {
+        Ok(model) => {
+            if model.version != JSON_IR_VERSION {
+                eprintln!("Error reading JSON for {in_file}: unsupported version {} (expected {JSON_IR_VERSION})", model.version);
+                return None;
+            }
+            if opt.headerfile == "unknown.h" {
+                opt.headerfile = model.headerfile;
+            }
+            Some((model.functions, model.structures))
+        }
+        Err(e) => {
+            eprintln!("Error reading JSON for {in_file}: {e}");
+            None
+        }
+    }
+}
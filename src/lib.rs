@@ -0,0 +1,33 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+//! The doxygen XML parsing half of doxygen2man, for tools that want to use
+//! the same model of a C API that the man pages are generated from.
+//! See [`project::Project`] for the easy way in, or [`build::ManPages`]
+//! (with the `build` feature) to generate the man pages from a build script.
+
+
+#[cfg(feature = "build")]
+pub mod build;
+// The command-line options and the page writers are shared with the
+// doxygen2man binary, they are not a stable API
+#[doc(hidden)]
+pub mod cli;
+pub mod coverage;
+pub mod diagnostics;
+pub mod model;
+pub mod parser;
+pub mod po;
+pub mod project;
+#[doc(hidden)]
+pub mod render;
+#[doc(hidden)]
+pub mod state;
+#[doc(hidden)]
+pub mod timings;
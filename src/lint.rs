@@ -0,0 +1,88 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --lint. Checks the pages we wrote for mistakes that man doesn't complain
+// about but that break other tools.
+
+use std::fs;
+use doxygen2man::render::{ManPage, PageKind};
+
+// Check that the NAME section will be understood by lexgrog (and so by mandb,
+// apropos and whatis). It must be one logical line of the form
+//    name[, name...] \- description
+fn check_name_section(page: &str) -> Result<(), String>
+{
+    let mut in_name = false;
+    let mut name_line = String::new();
+    for l in page.lines() {
+        if let Some(section) = l.strip_prefix(".SH") {
+            if in_name {
+                break;
+            }
+            in_name = section.trim().trim_matches('"') == "NAME";
+            continue;
+        }
+        if !in_name {
+            continue;
+        }
+        // These break the line for lexgrog too, but are harmless at the start
+        if l == ".PP" || l == ".br" || l == ".sp" {
+            if !name_line.is_empty() {
+                return Err(format!("NAME section has '{l}' in it"));
+            }
+            continue;
+        }
+        if l.starts_with('.') || l.starts_with('\'') {
+            return Err(format!("NAME section has a line that starts with a control character: {l}"));
+        }
+        if !name_line.is_empty() {
+            name_line.push(' ');
+        }
+        name_line.push_str(l.trim());
+    }
+
+    if !in_name && name_line.is_empty() {
+        return Err("no NAME section".to_string());
+    }
+    let (names, description) = match name_line.split_once(" \\- ") {
+        Some(n) => n,
+        None => return Err(format!("NAME line has no ' \\- ' between the name and the description: {name_line}")),
+    };
+    if description.trim().is_empty() {
+        return Err("NAME line has an empty description".to_string());
+    }
+    for n in names.split(',') {
+        let n = n.trim();
+        if n.is_empty() || n.contains(char::is_whitespace) {
+            return Err(format!("NAME line has a bad name list: {names}"));
+        }
+        if n.contains('\\') {
+            return Err(format!("NAME line has an escape in the name: {n}"));
+        }
+    }
+    Ok(())
+}
+
+// Lint all the pages, returns the number of pages with problems
+pub fn lint_pages(pages: &[ManPage]) -> usize
+{
+    let mut errors = 0;
+    // .so pages for --rename-map don't have a NAME of their own
+    for p in pages.iter().filter(|p| p.kind != PageKind::Alias) {
+        let res = match fs::read_to_string(&p.file) {
+            Ok(page) => check_name_section(&page),
+            Err(e) => Err(format!("cannot read page: {e}")),
+        };
+        if let Err(e) = res {
+            eprintln!("Lint: {} ({}): {}", p.function, p.file, e);
+            errors += 1;
+        }
+    }
+    errors
+}
@@ -7,1585 +7,1076 @@
 // This software licensed under GPL-2.0+
 //
 
-extern crate xml;
 extern crate chrono;
 
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Write, ErrorKind, Error, BufRead};
-use std::fmt::Write as fmtwrite;
+#[cfg(feature = "browse")]
+mod browse;
+mod deps;
+mod doxygen;
+#[cfg(feature = "json")]
+mod json;
+mod lint;
+mod serve;
+mod spell;
+mod stream;
+#[cfg(feature = "tarball")]
+mod tarball;
+mod tempdir;
+mod verify;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
 use structopt::StructOpt;
-use xml::reader::{EventReader, XmlEvent, ParserConfig};
-use xml::name::OwnedName;
-use chrono::prelude::*;
-
-// This defines how long a parameter type can get before we
-// decide it's not worth lining everything up.
-// It's mainly to stop function pointer types (which can get VERY long because
-// of all *their* parameters) making everything else 'line-up' over separate lines
-
-const MAX_PRINT_PARAM_LEN: usize = 80;
-
-// Similar for structure member comments
-const MAX_STRUCT_COMMENT_LEN: usize = 50;
-
-
-#[derive(Debug, StructOpt)]
-#[structopt(name = "doxygen2man", about = "Convert doxygen files to man pages")]
-/// This is a tool to generate API manpages from a doxygen-annotated header file.
-/// First run doxygen on the file and then run this program against the main XML file
-/// it created and the directory containing the ancilliary files. It will then
-/// output a lot of *.3 man page files which you can then ship with your library.
-///
-/// Doxygen creates an .xml file for each .h file in your project, pass these .xml
-/// files into this program - they are usually called something like
-/// <include-file>_8h.xml, eg qbipcs_8h.xml, you can pass multiple XML files to
-/// doxygen2man on the command-line.
-///
-/// If you want HTML output then simply use nroff on the generated files as you
-/// would do with any other man page.
-///
-struct Opt {
-    #[structopt (short="a", long="print-ascii", help="Print ASCII dump of manpage data to stdout")]
-    print_ascii: bool,
-
-    #[structopt (short="m", long="print-man", help="Write man page files to <output-dir>")]
-    print_man: bool,
-
-    #[structopt (short="P", long="print-params", help="print PARAMS section")]
-    print_params: bool,
-
-    #[structopt (short="g", long="print-general", help="Print general man page for the whole header file")]
-    print_general: bool,
-
-    #[structopt (short="q", long="quiet", help="Run quietly, no progress info printed")]
-    _quiet: bool,
-
-    #[structopt (short="c", long="use-header-copyright", help="Use the Copyright date from the header file (if one can be found)")]
-    use_header_copyright: bool,
-
-    #[structopt (short="I", long="headerfile", default_value="unknown.h", help="Set include filename (default taken from XML)")]
-    headerfile: String,
-
-    #[structopt (short="i", long="header-prefix", default_value="", help="prefix for includefile. eg qb/")]
-    header_prefix: String,
-
-    #[structopt (short="s", long="section", default_value="3", help="write man pages into section <section>")]
-    man_section: u32,
-
-    #[structopt (short="S", long="start-year", default_value="2010", help="Start year to print at end of copyright line")]
-    start_year: u32,
-
-    #[structopt (short="d", long="xml-dir", default_value="./xml/", help="Directory for XML files")]
-    xml_dir: String,
-
-    #[structopt (short="D", long="manpage-date", default_value="2010", help="Date to print at top of man pages (format not checked)")]
-    manpage_date: String,
-
-    #[structopt (short="Y", long="manpage-year", default_value="2010", help="Year to print at end of copyright line")]
-    manpage_year: i32,
-
-    #[structopt (short="p", long="package-name", default_value="Package", help="Name of package for these man pages")]
-    package_name: String,
-
-    #[structopt (short="H", long="header-name", default_value="Programmer's Manual", help="Header text")]
-    header: String,
-
-    #[structopt (short="o", long="output_dir", default_value="./", help="Write all man pages to <dir>")]
-    output_dir: String,
-
-    #[structopt (short="O", long="header_src_dir", default_value="./", help="Directory for the original header files (often needed by -c above)")]
-    header_src_dir: String,
-
-    #[structopt (short="C", long="company", default_value="Red Hat Inc", help="Company name in copyright")]
-    company: String,
-
-    // Positional parameters
-    #[structopt (help="XML files to process", required = true)]
-    xml_files: Vec<String>,
-}
-
-// Function parameter - also used for structure members
-#[derive(Clone)]
-struct FnParam
+use doxygen2man::model::*;
+use doxygen2man::parser::{fill_structures, is_c_language, normalize_c, read_compound_language, read_main_xml, read_page_refids, read_page_xml};
+use doxygen2man::parser::read_header_kerneldoc;
+#[cfg(feature = "libclang")]
+use doxygen2man::parser::read_header_clang;
+
+use doxygen2man::cli::{expand_subcommand, Opt};
+use doxygen2man::state::RunState;
+use doxygen2man::coverage::{read_baseline, undocumented_symbols, write_baseline, write_coverage, CoverageTotals, FunctionCoverage, Undocumented};
+use doxygen2man::diagnostics;
+use doxygen2man::po::{read_po, translate_function, write_pot};
+use deps::{write_depfile, write_make_deps, write_manifest, write_output_list, write_packaging_lists};
+use doxygen::run_doxygen;
+use lint::lint_pages;
+use spell::spellcheck_functions;
+#[cfg(feature = "json")]
+use json::{filter_model, read_json_input, write_json};
+use doxygen2man::render::{get_date_and_copyright, parse_removed, print_removed_page, read_removed_file, read_rename_map, resolve_source_version, wants_page, print_ascii_pages, print_index_page, print_main_page, print_man_pages, print_page_page, render_man_page, ManPage};
+use doxygen2man::render::devhelp::{print_devhelp_pages, write_devhelp_book};
+#[cfg(feature = "docset")]
+use doxygen2man::render::docset::{print_docset_pages, write_docset_index};
+#[cfg(feature = "templates")]
+use doxygen2man::render::template::load_templates;
+use stream::stream_xml_input;
+#[cfg(feature = "tarball")]
+use tarball::write_tarball;
+use tempdir::TempDir;
+use verify::verify_pages;
+use serve::serve;
+#[cfg(feature = "browse")]
+use browse::browse;
+use doxygen2man::timings::{Phase, PhaseTimes};
+#[cfg(feature = "wasm")]
+use wasm::wasm_filter_model;
+
+// Read the main XML file for a header, and all the structure files it refers to
+fn read_xml_input(opt: &mut Opt,
+                  state: &mut RunState,
+                  in_file: &str,
+                  structure_cache: &mut BTreeMap<String, StructureInfo>) -> Option<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>)>
 {
-    par_name: String,
-    par_type: String,
-    par_refid: Option<String>,
-    par_args: String,
-    par_desc: String,
-    par_brief: String,
+    let xml_dir = &opt.xml_dir;
+    if opt.language != "any" {
+        match read_compound_language(xml_dir, in_file) {
+            Ok(l) if !is_c_language(&l) => {
+                eprintln!("Skipping {in_file}, it is for {l} not {}", opt.language);
+                return None;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                diagnostics::error("input-error", "", &e.to_string());
+                return None;
+            }
+        }
+    }
+    let headerfile = &mut opt.headerfile;
+    let (mut functions, structures) = match state.timer.time(Phase::Parse, || read_main_xml(xml_dir, in_file, headerfile)) {
+        Ok(r) => r,
+        Err(e) => {
+            diagnostics::error("input-error", "", &e.to_string());
+            return None;
+        }
+    };
+    let mut filled_structures = state.timer.time(Phase::Structures, || fill_structures(xml_dir, &functions, &structures, structure_cache));
+    if opt.language == "c" {
+        normalize_c(&mut functions, &mut filled_structures);
+    }
+    Some((functions, filled_structures))
 }
 
-#[derive(Clone)]
-struct ReturnVal
+// Read a C file with kernel-doc comments
+fn read_kerneldoc_input(opt: &mut Opt,
+                        in_file: &str) -> Option<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>)>
 {
-    ret_name: String,
-    ret_desc: String,
+    match read_header_kerneldoc(in_file, &mut opt.headerfile) {
+        Ok(r) => Some(r),
+        Err(e) => {
+            diagnostics::error("input-error", "", &e.to_string());
+            None
+        }
+    }
 }
 
-#[derive(Clone)]
-enum StructureType
+// Read a header file with libclang
+#[cfg(feature = "libclang")]
+fn read_clang_input(opt: &mut Opt,
+                    in_file: &str) -> Option<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>)>
 {
-    Unknown,
-    Enum,
-    Struct,
-}
-#[derive(Clone)]
-struct StructureInfo
-{
-    str_type: StructureType,
-    str_name: String,
-    str_brief: String,
-    str_description: String,
-    str_members: Vec<FnParam>,
-}
-
-impl StructureInfo {
-    pub fn new() -> StructureInfo {
-        StructureInfo {
-            str_type: StructureType::Unknown,
-            str_name: String::new(),
-            str_brief: String::new(),
-            str_description: String::new(),
-            str_members: Vec::<FnParam>::new(),
+    match read_header_clang(in_file, &opt.clang_args, &mut opt.headerfile) {
+        Ok(r) => Some(r),
+        Err(e) => {
+            diagnostics::error("input-error", "", &e.to_string());
+            None
         }
     }
 }
 
-// Collected #defines - printed on the General page.
-struct HashDefine
+// Read the model for one input file, in whichever format it's in
+fn read_model(opt: &mut Opt,
+              state: &mut RunState,
+              in_file: &str,
+              structure_cache: &mut BTreeMap<String, StructureInfo>) -> Option<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>)>
 {
-    hd_name: String,
-    hd_init: String,
-    hd_brief: String,
-    hd_desc: String,
+    #[cfg(feature = "json")]
+    if opt.from_json {
+        let start = Instant::now();
+        let model = read_json_input(opt, in_file);
+        state.timer.add(Phase::Parse, start.elapsed());
+        return model;
+    }
+    if opt.kernel_doc {
+        let start = Instant::now();
+        let model = read_kerneldoc_input(opt, in_file);
+        state.timer.add(Phase::Parse, start.elapsed());
+        return model;
+    }
+    #[cfg(feature = "libclang")]
+    if opt.clang {
+        let start = Instant::now();
+        let model = read_clang_input(opt, in_file);
+        state.timer.add(Phase::Parse, start.elapsed());
+        return model;
+    }
+    read_xml_input(opt, state, in_file, structure_cache)
 }
 
-
-// Information for a function.
-// Pretty much everything else is hung off this
-struct FunctionInfo
+// Why an input file might have nothing in it, for the empty-input diagnostic
+fn empty_input_causes(opt: &Opt, in_file: &str) -> String
 {
-    fn_type: String,
-    fn_name: String,
-    fn_def: String,
-    fn_argsstring: String,
-    fn_brief: String,
-    fn_detail: String,
-    fn_returnval: String,
-    fn_note: String,
-    fn_args: Vec<FnParam>,
-    fn_defines: Vec<HashDefine>,
-    fn_retvals: Vec<ReturnVal>,
-    fn_refids: Vec<String>, // refids for structs used in the function
+    #[cfg(feature = "json")]
+    if opt.from_json {
+        return "Was it written by --write-json from the right XML file?".to_string();
+    }
+    #[cfg(feature = "libclang")]
+    if opt.clang {
+        return "Are its declarations documented with /** */ comments, and does --clang-args have what it needs to parse?".to_string();
+    }
+    if opt.kernel_doc {
+        return "Does it have kernel-doc comments (/** with the function name on the first line) before its functions?".to_string();
+    }
+    let name = Path::new(in_file).file_name().and_then(|n| n.to_str()).unwrap_or(in_file);
+    if name == "index.xml" || name.starts_with("dir_") || name.starts_with("namespace") {
+        return format!("{name} is one of doxygen's index files, pass the XML file for the header instead (eg qbipcs_8h.xml for qbipcs.h).");
+    }
+    "Is it the XML file for a header (eg qbipcs_8h.xml for qbipcs.h)? Was doxygen run with GENERATE_XML = YES and the header in its INPUT, \
+     and with EXTRACT_ALL = YES if the header's comments don't have \\file in them?".to_string()
 }
 
-impl FunctionInfo {
-    pub fn new() -> FunctionInfo {
-        FunctionInfo {
-            fn_type: String::new(),
-            fn_name: String::new(),
-            fn_def: String::new(),
-            fn_argsstring: String::new(),
-            fn_brief: String::new(),
-            fn_detail: String::new(),
-            fn_returnval: String::new(),
-            fn_note: String::new(),
-            fn_args: Vec::<FnParam>::new(),
-            fn_defines: Vec::<HashDefine>::new(),
-            fn_retvals: Vec::<ReturnVal>::new(),
-            fn_refids: Vec::<String>::new(),
-        }
+// Say so if an input file has no functions, structures or #defines in it, which is
+// usually the wrong file or doxygen not being set up for us rather than an empty
+// header. Returns false if it's an error (--strict-empty).
+fn check_empty_input(opt: &Opt,
+                     state: &mut RunState,
+                     in_file: &str,
+                     functions: &[FunctionInfo],
+                     structures: &BTreeMap<String, StructureInfo>) -> bool
+{
+    // The general page is there even for an empty file
+    let empty = structures.is_empty() && functions.iter().all(|f| f.fn_name == opt.headerfile && f.fn_defines.is_empty());
+    if !empty {
+        return true;
+    }
+    let message = format!("No functions, structures or #defines were found in {}. {}",
+                          state.current_input, empty_input_causes(opt, in_file));
+    if opt.strict_empty {
+        diagnostics::error("empty-input", "", &message);
+        state.empty_inputs += 1;
+        return false;
     }
+    diagnostics::warning("empty-input", "", &message);
+    true
 }
 
-// Return the length of a string ignoring any formatting
-fn len_without_formatting(param: &str) -> usize
+// Read the model for one input file, without the functions that don't get pages
+// (eg callback typedefs without --callback-pages)
+fn read_input(opt: &mut Opt,
+              state: &mut RunState,
+              in_file: &str,
+              structure_cache: &mut BTreeMap<String, StructureInfo>) -> Option<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>)>
 {
-    let mut length = 0;
-    let mut last_was_escape = false;
-    for i in param.chars() {
-	if i == '\\' {
-	    last_was_escape = true;
-	} else if last_was_escape {
-	    last_was_escape = false;
-	} else {
-	    length += 1;
-	}
-    }
-    length
+    let (mut functions, structures) = read_model(opt, state, in_file, structure_cache)?;
+    if !check_empty_input(opt, state, in_file, &functions, &structures) {
+        return None;
+    }
+    functions.retain(|f| wants_page(opt, state, f));
+    Some((functions, structures))
 }
 
-// Does what it says on the tin
-fn get_attr(e: &XmlEvent, attrname: &str) -> String
+// Run the model through the --filter commands and WASM filters, in that order
+#[cfg(feature = "json")]
+fn apply_filters(opt: &Opt,
+                 in_file: &str,
+                 functions: Vec<FunctionInfo>,
+                 structures: BTreeMap<String, StructureInfo>) -> Option<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>)>
 {
-    if let XmlEvent::StartElement {attributes,.. } = e {
-        for a in attributes {
-            if a.name.to_string() == attrname {
-                return a.value.to_string();
+    let mut model = (functions, structures);
+    for command in &opt.filter {
+        match filter_model(opt, command, &model.0, &model.1) {
+            Ok(m) => model = m,
+            Err(e) => {
+                eprintln!("Error running filter for {in_file}: {e}");
+                return None;
             }
         }
     }
-    String::new()
-}
 
-
-// Do the easy/common tags here
-fn parse_standard_elements(parser: &mut EventReader<BufReader<File>>, name: &OwnedName, e: &XmlEvent) -> Result<String, xml::reader::Error>
-{
-    let mut text = String::new();
-
-    match name.to_string().as_str() {
-        "para" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "sp" => {
-            text += " ";
-        }
-        "emphasis" => {
-            text += "\\fB";
-            text += collect_text(parser, name)?.as_str();
-            text += "\\fR";
-        }
-        "highlight" => { // TBH I've only ever seen "normal" here
-            let h_type = get_attr(e, "class");
-            if h_type != "normal" {
-                text += "\\fB";
-            }
-            text += collect_text(parser, name)?.as_str();
-            if h_type != "normal" {
-                text += "\\fR";
+    #[cfg(feature = "wasm")]
+    for wasm_file in &opt.wasm_filter {
+        match wasm_filter_model(opt, wasm_file, &model.0, &model.1) {
+            Ok(m) => model = m,
+            Err(e) => {
+                eprintln!("Error running filter for {in_file}: {e}");
+                return None;
             }
         }
-        "computeroutput" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "codeline" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "programlisting" => {
-            text += "\n.nf\n";
-            text += collect_text(parser, name)?.as_str();
-            text += "\n.fi\n";
-        }
-        "itemizedlist" => {
-            text += "\n";
-            text += collect_text(parser, name)?.as_str();
-            text += "\n";
-        }
-        "listitem" => {
-            text += "\n* ";
-            text += collect_text(parser, name)?.as_str();
-        }
-        "parameternamelist" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "parameteritem" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "parameterlist" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "parameterdescription" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "parametername" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "note" => {
-            text += collect_text(parser, name)?.as_str();
-            text += "\n";
-        }
-        "ref" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "simplesect" => {
-            text += collect_text(parser, name)?.as_str();
-        }
-        "xreftitle" | "xrefdescription" | "xrefsect" => {
-            let _ignore = collect_text(parser, name)?;
-        }
-        _ => {
-        }
     }
-    Ok(text)
+    Some(model)
 }
 
-// This returns the string itself (formatted) and a refid for the object if appropriate.
-fn collect_text_and_refid(parser: &mut EventReader<BufReader<File>>) -> Result<(String, Option<String>), xml::reader::Error>
+// Run mandb (or whatever --mandb-command says) on the manpath we wrote the pages to.
+// If the pages went into a section directory (eg man/man3) then that's its parent.
+fn update_mandb(opt: &Opt)
 {
-    let mut text = String::new();
-    let mut refid = None;
+    let output_dir = Path::new(&opt.output_dir);
+    let manpath = match (output_dir.file_name(), output_dir.parent()) {
+        (Some(n), Some(p)) if n.to_string_lossy().starts_with("man") => {
+            if p.as_os_str().is_empty() { Path::new(".") } else { p }
+        }
+        _ => output_dir,
+    };
 
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "ref" => {
-                                refid = Some(get_attr(&e, "refid"));
-                                text += collect_text(parser, name)?.as_str();
-                            }
-                            _ => {
-                                text += parse_standard_elements(parser, name, &e)?.as_str();
-                            }
-                        }
-                    }
-                    XmlEvent::Characters(s) => {
-                        text += s;
-                    }
-                    XmlEvent::EndElement {..} => {
-                        return Ok((text.trim_end().to_string(), refid));
-                    }
-                    _ => {}
-                }
-            }
-            Err(e) => {
-                return Err(e);
-            }
+    match Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", opt.mandb_command))
+        .arg("sh")
+        .arg(manpath)
+        .status() {
+            Ok(s) if s.success() => {}
+            Ok(s) => eprintln!("{} failed: {}", opt.mandb_command, s),
+            Err(e) => eprintln!("Cannot run {}: {}", opt.mandb_command, e),
         }
-    }
 }
 
-// Collect a single ReturnVal
-fn collect_retval(parser: &mut EventReader<BufReader<File>>, elem_name: &OwnedName) -> Result<ReturnVal, xml::reader::Error>
+// Show a page with --preview-command (usually man -l)
+fn preview_page(opt: &Opt, page: &str)
 {
-    let mut ret_name = String::new();
-    let mut ret_desc = String::new();
+    match Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", opt.preview_command))
+        .arg("sh")
+        .arg(page)
+        .status() {
+            Ok(s) if s.success() => {}
+            Ok(s) => eprintln!("{} failed: {}", opt.preview_command, s),
+            Err(e) => eprintln!("Cannot run {}: {}", opt.preview_command, e),
+        }
+}
 
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "parameternamelist" => {
-                                ret_name = collect_text(parser, name)?.trim().to_string();
-                            }
-                            "parameterdescription" => {
-                                ret_desc = collect_text(parser, name)?.trim().to_string();
-                            }
-                            _ => {
-                                let _text = collect_text(parser, name)?;
-                            }
-                        }
-                    }
-                    XmlEvent::Characters(s) => {
-                        let _text = s;
-                    }
-                    XmlEvent::EndElement {name, ..} => {
-                        if name == elem_name {
-                            return Ok(ReturnVal{ret_name, ret_desc})
-                        };
-                    }
-                    _ => {}
-                }
-            }
+// Write the --main-page overview page, from indexpage.xml if doxygen made one
+fn write_main_page(opt: &Opt, state: &RunState, pages: &mut Vec<ManPage>)
+{
+    let main_page = if opt.kernel_doc {
+        None
+    } else {
+        match read_page_xml(&opt.xml_dir, "indexpage") {
+            Ok(p) => Some(p),
             Err(e) => {
-                return Err(e);
+                eprintln!("No \\mainpage, the overview page will only have the index: {e}");
+                None
             }
         }
+    };
+    let (date_to_print, copyright) = match get_date_and_copyright(opt) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Error making date and copyright: {e}");
+            return;
+        }
+    };
+    match print_main_page(opt, state, &date_to_print, main_page.as_ref(), pages, &copyright) {
+        Ok(Some(file)) => pages.push(ManPage::for_doc_page(opt, file, &opt.package_name, "indexpage", "")),
+        Ok(None) => {}
+        Err(e) => eprintln!("Error writing man page for {}: {}", opt.package_name, e),
     }
 }
 
-// Collect all retvals for a function
-fn collect_retvals(parser: &mut EventReader<BufReader<File>>, elem_name: &OwnedName) -> Result<Vec<ReturnVal>, xml::reader::Error>
+// Write the --index-page page, listing all the function pages written
+fn write_index_page(opt: &Opt, state: &RunState, pages: &mut Vec<ManPage>)
 {
-    let mut rvs = Vec::<ReturnVal>::new();
-
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "parameteritem" => {
-                                rvs.push(collect_retval(parser, name)?);
-                            }
-                            _ => {
-                                let _text = collect_text(parser, name)?;
-                            }
-                        }
-                    }
-                    XmlEvent::Characters(s) => {
-                        let _text = s;
-                    }
-                    XmlEvent::EndElement {name, ..} => {
-                        if name == elem_name {
-                            return Ok(rvs)
-                        };
-                    }
-                    _ => {}
-                }
-            }
-            Err(e) => {
-                return Err(e);
-            }
+    let (date_to_print, copyright) = match get_date_and_copyright(opt) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Error making date and copyright: {e}");
+            return;
         }
+    };
+    let index_name = format!("{}_index", opt.package_name);
+    match print_index_page(opt, state, &date_to_print, pages, &copyright) {
+        Ok(Some(file)) => pages.push(ManPage::for_index(file, &index_name)),
+        Ok(None) => {}
+        Err(e) => eprintln!("Error writing man page for {index_name}: {e}"),
     }
 }
 
-
-fn collect_parameter_item(parser: &mut EventReader<BufReader<File>>, elem_name: &OwnedName) -> Result<(String, String), xml::reader::Error>
+// Write the --removed pages, for the functions that aren't there any more
+fn write_removed_pages(opt: &Opt, state: &RunState, pages: &mut Vec<ManPage>)
 {
-    let mut par_name = String::new();
-    let mut par_desc = String::new();
-
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "parameternamelist" => {
-                                par_name = collect_text(parser, name)?.trim().to_string();
-                            }
-                            "parameterdescription" => {
-                                par_desc = collect_text(parser, name)?.trim().to_string();
-                            }
-                            _ => {
-                                let _text = collect_text(parser, name)?;
-                            }
-                        }
-                    }
-                    XmlEvent::Characters(s) => {
-                        let _text = s;
-                    }
-                    XmlEvent::EndElement {name, ..} => {
-                        if name == elem_name {
-                            return Ok((par_name, par_desc));
-                        };
-                    }
-                    _ => {}
-                }
-            }
-            Err(e) => {
-                return Err(e);
-            }
+    let (date_to_print, copyright) = match get_date_and_copyright(opt) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Error making date and copyright: {e}");
+            return;
+        }
+    };
+    for r in &state.removed_functions {
+        // It might have come back
+        if pages.iter().any(|p| p.function == r.name) {
+            eprintln!("Not writing a removed page for {}, it has a page", r.name);
+            continue;
+        }
+        match print_removed_page(opt, state, &date_to_print, r, &copyright) {
+            Ok(Some(file)) => pages.push(ManPage::for_removed(opt, file, r)),
+            Ok(None) => {}
+            Err(e) => eprintln!("Error writing man page for {}: {}", r.name, e),
         }
     }
 }
 
-fn collect_params(parser: &mut EventReader<BufReader<File>>, elem_name: &OwnedName,
-                  params: &mut Vec<FnParam>) -> Result<(), xml::reader::Error>
+// Write a section 7 page for each of doxygen's \page documents, for --doc-pages
+fn write_doc_pages(opt: &Opt, state: &RunState, pages: &mut Vec<ManPage>)
 {
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "parameteritem" => {
-                                let (name, desc) = collect_parameter_item(parser, name)?;
-                                // Add the desc to this param
-                                for p in &mut *params {
-                                    if p.par_name == name {
-                                        p.par_desc = desc.clone();
-                                    }
-                                }
-                            }
-                            _ => {
-                                let _text = collect_text(parser, name)?;
-                            }
-                        }
-                    }
-                    XmlEvent::Characters(s) => {
-                        let _text = s;
-                    }
-                    XmlEvent::EndElement {name, ..} => {
-                        if name == elem_name {
-                            return Ok(())
-                        };
-                    }
-                    _ => {}
-                }
-            }
+    let refids = match read_page_refids(&opt.xml_dir) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+    let (date_to_print, copyright) = match get_date_and_copyright(opt) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Error making date and copyright: {e}");
+            return;
+        }
+    };
+    for refid in refids {
+        let page = match read_page_xml(&opt.xml_dir, &refid) {
+            Ok(p) => p,
             Err(e) => {
-                return Err(e);
+                eprintln!("{e}");
+                continue;
             }
+        };
+        match print_page_page(opt, state, &date_to_print, &page, &copyright) {
+            Ok(Some(file)) => pages.push(ManPage::for_doc_page(opt, file, &page.pg_name, &refid, &page.pg_title)),
+            Ok(None) => {}
+            Err(e) => eprintln!("Error writing man page for {}: {}", page.pg_name, e),
         }
     }
+}
 
+// Use the --po translations, if there are any
+fn translate_functions(state: &RunState, functions: Vec<FunctionInfo>) -> Vec<FunctionInfo>
+{
+    if state.translations.is_empty() {
+        return functions;
+    }
+    functions.into_iter()
+        .map(|mut f| {
+            translate_function(&mut f, &state.translations);
+            f
+        })
+        .collect()
 }
-// Called from "detaileddescription", so only needs to process tags that are immediately below it
-// (everything below that is handled by collect_text()),
-// and returns the main text, return text, and notes
-fn collect_detail_bits(parser: &mut EventReader<BufReader<File>>,
-                       elem_name: &OwnedName,
-                       function: &mut FunctionInfo) -> Result<(), xml::reader::Error>
+
+// Print the page for --stdout if it's in this input file. Returns true if it was.
+fn print_stdout_page(opt: &mut Opt,
+                     state: &mut RunState,
+                     in_file: &str,
+                     structure_cache: &mut BTreeMap<String, StructureInfo>) -> bool
 {
-    let mut text = String::new();
-    let mut returns = String::new();
-    let mut notes = String::new();
-    let mut retvals = Vec::<ReturnVal>::new();
+    let (functions, structures) = match read_input(opt, state, in_file, structure_cache) {
+        Some(i) => i,
+        None => return false,
+    };
+    #[cfg(feature = "json")]
+    let (functions, structures) = match apply_filters(opt, in_file, functions, structures) {
+        Some(m) => m,
+        None => return false,
+    };
+    let functions = translate_functions(state, functions);
 
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "para" => {
-                                collect_detail_bits(parser, name, function)?;
-                                function.fn_detail += "\n";
-                            }
-                            "parameterlist" => {
-                                if get_attr(&e, "kind") == "retval" {
-                                    retvals = collect_retvals(parser, name)?;
-                                } else if get_attr(&e, "kind") == "param" {
-                                    collect_params(parser, name, &mut function.fn_args)?;
-                                } else {
-                                    text += collect_text(parser, name)?.as_str();
-                                }
-                            }
-                            "simplesect" => {
-                                if get_attr(&e, "kind") == "return" {
-                                    returns += collect_text(parser, name)?.as_str();
-                                } else if get_attr(&e, "kind") == "note" {
-                                    notes += collect_text(parser, name)?.as_str();
-                                } else  {
-                                    text += collect_text(parser, name)?.as_str();
-                                }
-                            }
-                            _ => {
-                                text += parse_standard_elements(parser, name, &e)?.as_str();
-                            }
-                        }
-                    }
-                    XmlEvent::Characters(s) => {
-                        text += s;
-                    }
-                    XmlEvent::EndElement {name, ..} => {
-                        // Only return if we are at the end of the element that called us
-                        if name == elem_name {
-                            function.fn_detail += text.trim_end().to_string().as_str();
-                            function.fn_returnval += returns.as_str();
-                            function.fn_note += notes.as_str();
-                            function.fn_retvals.append(&mut retvals);
-                            return Ok(());
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            Err(e) => {
-                return Err(e);
+    let function = match functions.iter().find(|f| f.fn_name == opt.stdout) {
+        Some(f) => f,
+        None => return false,
+    };
+    let (date_to_print, copyright) = match get_date_and_copyright(opt) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Error making date and copyright: {e}");
+            return true;
+        }
+    };
+    match render_man_page(opt, state, &date_to_print, function, &functions, &structures, &copyright) {
+        Ok(page) => {
+            if let Err(e) = std::io::stdout().write_all(&page) {
+                eprintln!("Error writing page for {}: {}", opt.stdout, e);
             }
         }
+        Err(e) => eprintln!("Error making page for {}: {}", opt.stdout, e),
     }
+    true
 }
 
-// This is the main text-collecting routine. It should parse as many XML options as possible.
-// It returns the string itself (formatted).
-// It is called recursively as we descend the XML structures
-fn collect_text(parser: &mut EventReader<BufReader<File>>, elem_name: &OwnedName) -> Result<String, xml::reader::Error>
+// Things in the functions that get pages that aren't documented.
+// With --baseline anything undocumented that isn't in it is an error (and the
+// rest are fine), otherwise it's only warnings about parameters.
+fn warn_undocumented(opt: &Opt, state: &mut RunState, functions: &[FunctionInfo])
 {
-    let mut text = String::new();
-
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        text += parse_standard_elements(parser, name, &e)?.as_str();
-                    }
-                    XmlEvent::Characters(s) => {
-                        text += s;
-                    }
-                    XmlEvent::EndElement {name, ..} => {
-                        // Only return if we are at the end of the element that called us
-                        if name == elem_name {
-                            return Ok(text.trim_end().to_string());
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            Err(e) => {
-                return Err(e);
+    let found: Vec<(&str, Undocumented)> = functions.iter()
+        .filter(|f| f.fn_name != opt.headerfile && wants_page(opt, state, f))
+        .flat_map(|f| undocumented_symbols(f).into_iter().map(move |u| (f.fn_name.as_str(), u)))
+        .collect();
+    for (function, u) in found {
+        if !opt.write_baseline.is_empty() {
+            state.undocumented.insert(u.symbol.clone());
+        }
+        if !opt.baseline.is_empty() {
+            if !state.baseline_symbols.contains(&u.symbol) {
+                diagnostics::error(u.rule, function, &format!("{} (and is not in {})", u.message, opt.baseline));
+                state.new_undocumented += 1;
             }
+        } else if u.rule == "undocumented-param" {
+            diagnostics::warning(u.rule, function, &u.message);
         }
     }
 }
 
-fn collect_function_param(parser: &mut EventReader<BufReader<File>>,
-                          structures: &mut HashMap<String, StructureInfo>) -> Result<FnParam, xml::reader::Error>
+// --min-coverage and --min-param-coverage. Returns false if the coverage is too low.
+fn check_coverage(opt: &Opt, state: &RunState) -> bool
 {
-    let mut par_name = String::new();
-    let mut par_type = String::new();
-    let mut par_refid = None;
-
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        let (tmp, refid) = collect_text_and_refid(parser)?;
-                        if let Some(r) = &refid {
-                            if structures.get(r).is_none() {
-                                let new_struct = StructureInfo {str_type: StructureType::Struct, str_name: tmp.clone(), str_brief: String::new(), str_description: String::new(), str_members: Vec::<FnParam>::new()};
-                                structures.insert(r.clone(), new_struct);
-                            }
-                        }
-
-                        if name.to_string() == "type" {
-                            par_type = tmp.clone();
-                            par_refid = refid.clone();
-                        }
-                        if name.to_string() == "declname" {
-                            par_name = tmp.clone();
-                        }
-                    }
+    if opt.min_coverage <= 0.0 && opt.min_param_coverage <= 0.0 {
+        return true;
+    }
+    let totals = CoverageTotals::new(&state.coverage_entries);
+    if totals.functions == 0 {
+        eprintln!("No functions were found to measure the documentation coverage of");
+        return false;
+    }
+    eprintln!("Documentation coverage: {:.1}% of functions ({}/{}), {:.1}% of parameters ({}/{})",
+              totals.function_percent(), totals.documented_functions, totals.functions,
+              totals.param_percent(), totals.documented_params, totals.params);
+    let mut ok = true;
+    if totals.function_percent() < opt.min_coverage {
+        eprintln!("Function coverage is below --min-coverage {}%", opt.min_coverage);
+        ok = false;
+    }
+    // Functions that all take no parameters don't have any to leave undocumented
+    if totals.params > 0 && totals.param_percent() < opt.min_param_coverage {
+        eprintln!("Parameter coverage is below --min-param-coverage {}%", opt.min_param_coverage);
+        ok = false;
+    }
+    ok
+}
 
-                    XmlEvent::EndElement {..} => {
-                        return Ok(FnParam{par_name, par_type, par_refid, par_args: String::new(), par_desc: String::new(), par_brief: String::new()});
-                    }
-                    _e => {
-                    }
-                }
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        }
+// The input file that the pages (and diagnostics) are made from
+fn set_current_input(opt: &Opt, state: &mut RunState, in_file: &str)
+{
+    state.current_input = format!("{}/{}", opt.xml_dir, in_file);
+    #[cfg(feature = "json")]
+    if opt.from_json {
+        state.current_input = in_file.to_string();
+    }
+    #[cfg(feature = "libclang")]
+    if opt.clang {
+        state.current_input = in_file.to_string();
+    }
+    if opt.kernel_doc {
+        state.current_input = in_file.to_string();
     }
+    diagnostics::set_input(&state.current_input);
 }
 
-fn collect_function_info(parser: &mut EventReader<BufReader<File>>,
-                         functions: &mut Vec<FunctionInfo>,
-                         structures: &mut HashMap<String, StructureInfo>) -> Result<(), xml::reader::Error>
+// Read one input file and write its pages. Returns false if the run should stop.
+fn process_input(opt: &mut Opt,
+                 state: &mut RunState,
+                 in_file: &str,
+                 structure_cache: &mut BTreeMap<String, StructureInfo>,
+                 pages: &mut Vec<ManPage>) -> bool
 {
-    let mut function = FunctionInfo::new();
+    set_current_input(opt, state, in_file);
 
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "type" => {
-                                function.fn_type = collect_text(parser, name)?;
-                            },
-                            "definition" =>  {
-                                function.fn_def = collect_text(parser, name)?;
-                            }
-                            "argsstring" => {
-                                function.fn_argsstring = collect_text(parser, name)?;
-                            }
-                            "name" | "compoundname" => {
-                                function.fn_name = collect_text(parser, name)?;
-                            }
-                            "param" => {
-                                let param = collect_function_param(parser, structures)?;
-                                // If the param has a refid then make a note of it so we
-                                // can expand structures in the manpage
-                                if let Some(r) = &param.par_refid {
-                                    function.fn_refids.push(r.clone());
-                                }
-                                function.fn_args.push(param);
-                            }
-                            "briefdescription" => {
-                                function.fn_brief = collect_text(parser, name)?;
-                            }
-                            "detaileddescription" => {
-                                collect_detail_bits(parser, name, &mut function)?;
-                            }
-                            _ => {
-                                // Not used,. but still need to consume it
-                                let _fntext = collect_text(parser, name)?;
-                            }
-                        }
-                    }
-                    XmlEvent::Characters(_s) => {
+    if opt.streaming {
+        match stream_xml_input(opt, state, in_file, structure_cache) {
+            Ok(p) => pages.extend(p),
+            Err(e) => diagnostics::error("input-error", "", &e.to_string()),
+        }
+        return true;
+    }
 
-                    }
-                    XmlEvent::EndElement {name, ..} => {
-                        if name.to_string().as_str() == "memberdef" {
-                            // Remove all duplicate refids for functions
-                            // where a structure appears as multiple arguments
-                            // (not common, but no need to print it twice)
-                            function.fn_refids.sort_unstable();
-                            function.fn_refids.dedup();
+    let input = read_input(opt, state, in_file, structure_cache);
+    let (functions, filled_structures) = match input {
+        Some(i) => i,
+        None => return true,
+    };
 
-                            functions.push(function);
-                            return Ok(());
-                        }
-                    }
-                    _ => {}
-                }
-            }
+    #[cfg(feature = "json")]
+    let (functions, filled_structures) = match apply_filters(opt, in_file, functions, filled_structures) {
+        Some(m) => m,
+        None => return true,
+    };
+
+    warn_undocumented(opt, state, &functions);
+    diagnostics::locate(&functions);
+    if !opt.coverage.is_empty() || opt.min_coverage > 0.0 || opt.min_param_coverage > 0.0 {
+        let coverage: Vec<FunctionCoverage> = functions.iter()
+            .filter(|f| f.fn_name != opt.headerfile && wants_page(opt, state, f))
+            .map(|f| FunctionCoverage::new(&opt.headerfile, f))
+            .collect();
+        state.coverage_entries.extend(coverage);
+    }
+    if opt.spellcheck {
+        match spellcheck_functions(opt, state, &functions) {
+            Ok(n) => state.spelling_errors += n,
             Err(e) => {
-                return Err(e);
+                eprintln!("Error checking spelling in {in_file}: {e}");
+                return false;
             }
         }
     }
-}
-
-fn collect_define(parser: &mut EventReader<BufReader<File>>) -> Result<HashDefine, xml::reader::Error>
-{
-    let mut hd_name = String::new();
-    let mut hd_init = String::new();
-    let mut hd_brief = String::new();
-    let mut hd_desc = String::new();
+    if !opt.write_pot.is_empty() {
+        let wanted: Vec<&FunctionInfo> = functions.iter().filter(|f| wants_page(opt, state, f)).collect();
+        for f in wanted {
+            state.pot.add_function(f);
+        }
+    }
+    let functions = translate_functions(state, functions);
 
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "name" => {
-                                hd_name = collect_text(parser, name)?;
-                            }
-                            "initializer" => {
-                                hd_init = collect_text(parser, name)?;
-                            }
-                            "briefdescription" => {
-                                hd_brief = collect_text(parser, name)?;
-                            }
-                            "detaileddescription" => {
-                                hd_desc = collect_text(parser, name)?;
-                            }
-                            _ => {}
-                        }
-                    },
-                    XmlEvent::EndElement {name, ..} => {
-                        if name.to_string().as_str() == "memberdef" {
-                            return Ok(HashDefine{hd_name, hd_init, hd_brief, hd_desc});
-                        }
-                    },
-                    XmlEvent::Characters(_s) => {
-                    },
-                    XmlEvent::EndDocument => return Ok(HashDefine{hd_name, hd_init, hd_brief, hd_desc}),
-                    _ => {}
-                }
+    // Then print those man pages!
+    if opt.print_ascii {
+        print_ascii_pages(opt, &functions, &filled_structures);
+    }
+    #[cfg(feature = "json")]
+    if opt.write_json {
+        if let Err(e) = state.timer.time(Phase::Write, || write_json(opt, &functions, &filled_structures)) {
+            eprintln!("Error writing JSON for {in_file}: {e}");
+        }
+    }
+    if opt.print_man {
+        match print_man_pages(opt, state, &functions, &filled_structures) {
+            Ok((p, failed)) => {
+                pages.extend(p);
+                state.page_errors += failed;
             }
             Err(e) => {
-                return Err(e);
+                eprintln!("Error in print_man_pages: {e:?}");
+                return false;
             }
         }
     }
+    #[cfg(feature = "docset")]
+    if !opt.docset.is_empty() {
+        match print_docset_pages(opt, state, &functions, &filled_structures) {
+            Ok(e) => state.docset_entries.extend(e),
+            Err(e) => eprintln!("Error writing docset pages for {in_file}: {e}"),
+        }
+    }
+    if !opt.devhelp.is_empty() {
+        match print_devhelp_pages(opt, state, &functions, &filled_structures) {
+            Ok(c) => state.devhelp_chapters.push(c),
+            Err(e) => eprintln!("Error writing devhelp pages for {in_file}: {e}"),
+        }
+    }
+    true
 }
 
+// Exit status for a bad command line, eg options that can't be used together or
+// files named by the options that can't be read
+const USAGE_ERROR: i32 = 2;
 
-fn read_file(parser: &mut EventReader<BufReader<File>>,
-             opt: &mut Opt,
-             functions: &mut Vec<FunctionInfo>,
-             structures: &mut HashMap<String, StructureInfo>) -> Result<(), xml::reader::Error>
+// The whole run, returning the exit status. exit() doesn't run destructors, so
+// this returns to main() first to remove the private directories of pages.
+fn run() -> i32
 {
-    let mut defines = Vec::<HashDefine>::new();
-    let mut general = FunctionInfo::new();
-
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "memberdef" => {
-                                if get_attr(&e, "kind") == "function" {
+    let run_start = Instant::now();
 
-                                    // Do function stuff
-                                    // go down the tree collecting info until we read EndElement
-                                    collect_function_info(parser,
-                                                          functions,
-                                                          structures)?;
-                                }
-                                // Collect #defines
-                                if get_attr(&e, "kind") == "define" {
-                                    let new_hd = collect_define(parser)?;
-                                    defines.push(new_hd);
-                                }
-                                // enums are in the main file, structs have their own
-                                if get_attr(&e, "kind") == "enum" {
-                                    let refid = get_attr(&e, "id");
-                                    if let Ok(si) = collect_enum(parser, StructureType::Enum) {
-                                        structures.insert(refid, si);
-                                    }
-				}
-                                // Ignore typedefs for the moment
-                                if get_attr(&e, "kind") == "typedef" {
-                                    let _ignore = collect_text(parser, name)?;
-                                }
-                            }
-                            "compoundname" => {
-                                // This is the header filename (and the reason &opt is mutable & cloned)
-				if opt.headerfile == "unknown.h" {
-                                    opt.headerfile = collect_text(parser, name)?;
-				}
-                            }
+    // Get command-line options
+    let mut opt = Opt::from_iter(expand_subcommand(std::env::args().collect()));
+    let mut state = RunState::default();
 
-                            // These are at the file (eg qblog.h) level
-                            "briefdescription" => {
-                                general.fn_brief += collect_text(parser, name)?.as_str();
-                            }
-                            "detaileddescription" => {
-                                collect_detail_bits(parser, name, &mut general)?;
-                            }
-                            _ => {
-                                let _tother = parse_standard_elements(parser, name, &e)?;
-                            }
-                        }
-                    },
-                    XmlEvent::EndElement {..} => {
-                    },
-                    XmlEvent::Characters(_s) => {
-                    },
-                    XmlEvent::EndDocument => {
-                        general.fn_name = opt.headerfile.clone();
-                        general.fn_defines = defines;
-                        functions.push(general);
-                        return Ok(());
-                    }
-                    _ => {}
-                }
-            }
+    #[cfg(feature = "templates")]
+    {
+        state.templates = load_templates(&opt);
+    }
+    opt.source_version = resolve_source_version(&opt);
+    if let Err(e) = diagnostics::set_output(&opt.diagnostics, &opt.diagnostics_file) {
+        eprintln!("Cannot open diagnostics file {}: {}", opt.diagnostics_file, e);
+        return USAGE_ERROR;
+    }
+    if !opt.sarif.is_empty() {
+        diagnostics::keep_records();
+    }
+    if !opt.baseline.is_empty() {
+        match read_baseline(&opt.baseline) {
+            Ok(b) => state.baseline_symbols = b,
             Err(e) => {
-                return Err(e);
+                eprintln!("Cannot read baseline file {}: {}", opt.baseline, e);
+                return USAGE_ERROR;
             }
         }
     }
-}
-
-// Read a single structure member from a structure file
-fn read_structure_member(parser: &mut EventReader<BufReader<File>>) -> Result<FnParam, xml::reader::Error>
-{
-    let mut par_name = String::new();
-    let mut par_type = String::new();
-    let mut par_desc = String::new();
-    let mut par_brief = String::new();
-    let mut par_args = String::new();
-
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "name" => {
-                                par_name = collect_text(parser, name)?;
-                            }
-                            "type" => {
-                                par_type = collect_text(parser, name)?;
-                            }
-                            "argsstring" => {
-                                par_args = collect_text(parser, name)?;
-                            }
-                            "detaileddescription" => {
-                                par_desc = collect_text(parser, name)?.trim().to_string();
-                            }
-                            "briefdescription" => {
-                                par_brief = collect_text(parser, name)?.trim().to_string();
-                            }
-                            _ => {
-                                // Not used but still needs to be collected
-                                let _fntext = collect_text(parser, name)?;
-                            }
-                        }
-                    }
-                    XmlEvent::EndElement {..} => {
-                        return Ok(FnParam {par_name, par_type, par_desc, par_args, par_brief, par_refid: None});
-                    },
-                    XmlEvent::Characters(_s) => {
-                    },
-                    _ => {}
-                }
-            }
+    if !opt.po.is_empty() {
+        match read_po(&opt.po) {
+            Ok(t) => state.translations = t,
             Err(e) => {
-                return Err(e);
+                eprintln!("Cannot read translations {}: {}", opt.po, e);
+                return USAGE_ERROR;
             }
         }
     }
-}
 
-fn collect_enum(parser: &mut EventReader<BufReader<File>>,
-                str_type: StructureType) -> Result<StructureInfo, xml::reader::Error>
-{
-    let mut sinfo = StructureInfo::new();
-    sinfo.str_type = str_type;
+    #[cfg(feature = "json")]
+    if opt.streaming && (opt.write_json || opt.from_json || !opt.filter.is_empty()) {
+        eprintln!("--streaming cannot be used with --write-json, --from-json or --filter");
+        return USAGE_ERROR;
+    }
+    #[cfg(feature = "wasm")]
+    if opt.streaming && !opt.wasm_filter.is_empty() {
+        eprintln!("--streaming cannot be used with --wasm-filter");
+        return USAGE_ERROR;
+    }
+    if opt.streaming && !opt.devhelp.is_empty() {
+        eprintln!("--streaming cannot be used with --devhelp");
+        return USAGE_ERROR;
+    }
+    if opt.streaming && opt.spellcheck {
+        eprintln!("--streaming cannot be used with --spellcheck");
+        return USAGE_ERROR;
+    }
+    if opt.streaming && !opt.verify.is_empty() {
+        eprintln!("--streaming cannot be used with --verify");
+        return USAGE_ERROR;
+    }
+    if opt.streaming && (!opt.coverage.is_empty() || opt.min_coverage > 0.0 || opt.min_param_coverage > 0.0) {
+        eprintln!("--streaming cannot be used with --coverage, --min-coverage or --min-param-coverage");
+        return USAGE_ERROR;
+    }
+    if opt.streaming && (!opt.baseline.is_empty() || !opt.write_baseline.is_empty()) {
+        eprintln!("--streaming cannot be used with --baseline or --write-baseline");
+        return USAGE_ERROR;
+    }
+    if opt.streaming && (!opt.po.is_empty() || !opt.write_pot.is_empty()) {
+        eprintln!("--streaming cannot be used with --po or --write-pot");
+        return USAGE_ERROR;
+    }
+    if opt.streaming && opt.strict_empty {
+        eprintln!("--streaming cannot be used with --strict-empty");
+        return USAGE_ERROR;
+    }
+    if opt.include_static && opt.exclude_static {
+        eprintln!("--include-static cannot be used with --exclude-static");
+        return USAGE_ERROR;
+    }
+    if opt.streaming && opt.language != "any" {
+        eprintln!("--streaming cannot be used with --language");
+        return USAGE_ERROR;
+    }
+    if opt.streaming && (opt.type_pages || opt.enum_pages) {
+        eprintln!("--streaming cannot be used with --type-pages or --enum-pages");
+        return USAGE_ERROR;
+    }
+    #[cfg(feature = "docset")]
+    if opt.streaming && !opt.docset.is_empty() {
+        eprintln!("--streaming cannot be used with --docset");
+        return USAGE_ERROR;
+    }
+    #[cfg(feature = "json")]
+    if opt.run_doxygen && opt.from_json {
+        eprintln!("--run-doxygen cannot be used with --from-json");
+        return USAGE_ERROR;
+    }
+    if opt.kernel_doc && (opt.streaming || opt.run_doxygen) {
+        eprintln!("--kernel-doc cannot be used with --streaming or --run-doxygen");
+        return USAGE_ERROR;
+    }
+    #[cfg(feature = "json")]
+    if opt.kernel_doc && opt.from_json {
+        eprintln!("--kernel-doc cannot be used with --from-json");
+        return USAGE_ERROR;
+    }
+    #[cfg(feature = "libclang")]
+    if opt.kernel_doc && opt.clang {
+        eprintln!("--kernel-doc cannot be used with --clang");
+        return USAGE_ERROR;
+    }
+    #[cfg(feature = "libclang")]
+    if opt.clang && (opt.streaming || opt.run_doxygen) {
+        eprintln!("--clang cannot be used with --streaming or --run-doxygen");
+        return USAGE_ERROR;
+    }
+    #[cfg(all(feature = "libclang", feature = "json"))]
+    if opt.clang && opt.from_json {
+        eprintln!("--clang cannot be used with --from-json");
+        return USAGE_ERROR;
+    }
 
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "name" => {
-                                sinfo.str_name = collect_text(parser, name)?;
-                            }
-                            "enumvalue" => {
-                                match read_structure_member(parser) {
-                                    Ok(s) => sinfo.str_members.push(s),
-                                    Err(e) => return Err(e),
-                                }
-                            }
-                            "briefdescription" => {
-                                sinfo.str_brief = collect_text(parser, name)?;
-                            }
-                            "detaileddescription" => {
-                                sinfo.str_description = collect_text(parser, name)?;
-                            }
-                            _ => {
-                                let _ = collect_text(parser, name)?;
-                            }
-                        }
-                    }
-                    XmlEvent::EndElement {..} => {
-                        return Ok(sinfo);
-                    },
-                    XmlEvent::Characters(_s) => {
-                    },
-                    XmlEvent::EndDocument => return Ok(sinfo),
-                    _ => {}
-                }
+    if !opt.copyright_regex.is_empty() {
+        if let Err(e) = regex::Regex::new(&opt.copyright_regex) {
+            eprintln!("Invalid --copyright-regex: {e}");
+            return USAGE_ERROR;
+        }
+    }
+
+    if !opt.preamble.is_empty() {
+        match std::fs::read_to_string(&opt.preamble) {
+            Ok(p) => state.preamble_text = p,
+            Err(e) => {
+                eprintln!("Error reading preamble {}: {}", opt.preamble, e);
+                return USAGE_ERROR;
             }
+        }
+    }
+    if !opt.rename_map.is_empty() {
+        match read_rename_map(&opt.rename_map) {
+            Ok(r) => state.renames = r,
             Err(e) => {
-                return Err(e);
+                eprintln!("Error reading rename map {}: {}", opt.rename_map, e);
+                return USAGE_ERROR;
             }
         }
     }
-}
-
-
-// Found the point in the struct file where the definition is. Read it in
-fn read_structure(parser: &mut EventReader<BufReader<File>>,
-                  str_type: StructureType) -> Result<StructureInfo, xml::reader::Error>
-{
-    let mut sinfo = StructureInfo::new();
-
-    sinfo.str_type = str_type;
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "compoundname" => {
-                                sinfo.str_name = collect_text(parser, name)?;
-                            }
-                            "briefdescription" => {
-                                sinfo.str_brief = collect_text(parser, name)?;
-                            }
-                            "includes" => {
-                                let _ignore = collect_text(parser, name)?;
-                            }
-                            "detaileddescription" => {
-                                sinfo.str_description = collect_text(parser, name)?;
-                            }
-                            "memberdef" => {
-                                match read_structure_member(parser) {
-                                    Ok(s) => sinfo.str_members.push(s),
-                                    Err(e) => return Err(e),
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                    XmlEvent::EndElement {name, ..} => {
-                        if name.to_string() == "compounddef" {
-                            return Ok(sinfo);
-                        }
-                    },
-                    XmlEvent::Characters(_s) => {
-                    },
-                    XmlEvent::EndDocument => {},
-                    _ => {}
-                }
+    for spec in &opt.removed {
+        match parse_removed(spec) {
+            Some(r) => state.removed_functions.push(r),
+            None => {
+                eprintln!("--removed should be <name>=<version>[:<replacement>]: {spec}");
+                return USAGE_ERROR;
             }
+        }
+    }
+    if !opt.removed_file.is_empty() {
+        match read_removed_file(&opt.removed_file) {
+            Ok(r) => state.removed_functions.extend(r),
             Err(e) => {
-                return Err(e);
+                eprintln!("Error reading removed functions {}: {}", opt.removed_file, e);
+                return USAGE_ERROR;
             }
         }
     }
-}
-
-// Read a single structure from its XML file
-fn read_structure_file(parser: &mut EventReader<BufReader<File>>,
-                       str_type: StructureType) -> Result<(String, StructureInfo), xml::reader::Error>
-{
-    let mut sinfo = StructureInfo::new();
-    let mut refid = String::new();
+    for map_file in &opt.ref_map {
+        if let Err(e) = state.external_refs.read_ref_map(map_file) {
+            eprintln!("Error reading ref map {map_file}: {e}");
+            return USAGE_ERROR;
+        }
+    }
+    for tagfile in &opt.tagfiles {
+        let (file, section) = tagfile.split_once('=').unwrap_or((tagfile, "3"));
+        if let Err(e) = state.external_refs.read_tagfile(file, section) {
+            eprintln!("Error reading tag file {file}: {e}");
+            return USAGE_ERROR;
+        }
+    }
 
-    sinfo.str_type = str_type;
-    loop {
-        let er = parser.next();
-        match er {
-            Ok(e) => {
-                match &e {
-                    XmlEvent::StartElement {name, ..} => {
-                        match name.to_string().as_str() {
-                            "compounddef" => {
-                                if let Ok(s) = read_structure(parser, StructureType::Struct) {
-                                    sinfo = s;
-                                    refid = get_attr(&e, "id");
-                                }
-                            }
-                            "briefdescription" => {
-                                sinfo.str_brief = collect_text(parser, name)?;
-                            }
-                            "detaileddescription" => {
-                                sinfo.str_description = collect_text(parser, name)?;
-                            }
-                            _ => {}
-                        }
-                    }
-                    XmlEvent::EndElement {..} => {
-                    },
-                    XmlEvent::Characters(_s) => {
-                    },
-                    XmlEvent::EndDocument => return Ok((refid, sinfo)),
-                    _ => {}
+    // Keep doxygen's output until we have finished with it
+    let _doxygen_output = if opt.run_doxygen {
+        let start = Instant::now();
+        match run_doxygen(&mut opt) {
+            Ok(o) => {
+                if opt.timings {
+                    eprintln!("Timings for doxygen: {:.2?}", start.elapsed());
                 }
+                Some(o)
             }
             Err(e) => {
-                return Err(e);
+                eprintln!("{e}");
+                return 1;
             }
         }
-    }
-}
-
+    } else {
+        None
+    };
 
-// Read all the structure files we need for our functions
-fn read_structures_files(opt: &Opt,
-                         structures: &HashMap<String, StructureInfo>,
-                         filled_structures: &mut HashMap<String, StructureInfo>)
-{
-    for (refid, s) in structures {
-        match s.str_type {
-            StructureType::Enum => {
-                filled_structures.insert(refid.to_string(), (*s).clone());
+    // --dry-run and --list write the pages somewhere private that is removed at the end.
+    // --list prints them as they would be in the real output directory.
+    let output_dir = opt.output_dir.clone();
+    let _dry_run_dir = if opt.dry_run || opt.list {
+        match TempDir::new("dry-run") {
+            Ok(d) => {
+                opt.output_dir = d.path().to_string_lossy().to_string();
+                opt.print_man = true;
+                opt.force = true;
+                opt.no_clobber = false;
+                opt.update_mandb = false;
+                Some(d)
             }
-            StructureType::Unknown => {} // Throw it away
-            StructureType::Struct => {
-                let mut xml_file = String::new();
-                if let Err(e) = write!(xml_file, "{}/{}.xml", &opt.xml_dir, &refid) {
-                    println!("Error making structure XML file name for {refid}: {e}");
-                    return;
-                }
-
-                if let Ok(f) = File::open(&xml_file) {
-                        let mut parser = ParserConfig::new()
-                            .whitespace_to_characters(true)
-                            .ignore_comments(true)
-                            .create_reader(BufReader::new(f));
-
-                    if let Ok((refid, new_s)) = read_structure_file(&mut parser, StructureType::Struct) {
-                        // Add to the new map
-                        filled_structures.insert(refid, new_s);
-                    }
-		}
+            Err(e) => {
+                eprintln!("Cannot make directory for dry run: {e}");
+                return 1;
             }
         }
-    }
-}
-
-fn read_header_copyright(opt: &Opt) -> Result<String, std::io::Error>
-{
-    let mut h_file = String::new();
-    if let Err(_e) = write!(h_file, "{}/{}", &opt.header_src_dir, &opt.headerfile) {
-        println!("Error making header file name for {}: {}", opt.header_src_dir, opt.headerfile);
-        return Err(Error::new(ErrorKind::Other, "Error making filename"));
-    }
+    } else {
+        None
+    };
 
-    let f = File::open(&h_file)?;
-    let r = BufReader::new(f);
-    for l in r.lines() {
-        match l {
-            Ok(line) => {
-                if line.starts_with(" * Copyright") {
-                    // unwrap is safe here because of the above line.
-                    return Ok(line.get(3..).unwrap().to_string());
-                }
+    // --preview writes the pages somewhere private, and then shows just the one asked for
+    let _preview_dir = if !opt.preview.is_empty() {
+        match TempDir::new("preview") {
+            Ok(d) => {
+                opt.output_dir = d.path().to_string_lossy().to_string();
+                opt.print_man = true;
+                opt.force = true;
+                opt.no_clobber = false;
+                opt.update_mandb = false;
+                Some(d)
+            }
+            Err(e) => {
+                eprintln!("Cannot make directory for preview: {e}");
+                return 1;
             }
-            Err(e) => return Err(e)
         }
-    }
-    Err(Error::new(ErrorKind::Other, "Not found"))
-}
-
+    } else {
+        None
+    };
 
-// Mainly for debugging
-fn print_text_function(f: &FunctionInfo,
-                       structures: &HashMap<String, StructureInfo>)
-{
-    println!("FUNCTION {} {} {}", f.fn_type, f.fn_name, f.fn_argsstring);
-    for i in &f.fn_args {
-        match &i.par_refid {
-            Some(r) =>
-                println!("  PARAM: {} {}{} (refid={})", i.par_type, i.par_name, i.par_args, r),
-            None =>
-                println!("  PARAM: {} {}{}", i.par_type, i.par_name, i.par_args),
+    // --output-tar also writes the pages somewhere private first
+    #[cfg(feature = "tarball")]
+    let _tar_dir = if !opt.output_tar.is_empty() {
+        if !opt.preview.is_empty() {
+            eprintln!("--output-tar cannot be used with --preview");
+            return USAGE_ERROR;
         }
-        if !i.par_brief.is_empty() {
-            println!("  PARAM brief: {}", i.par_brief);
-        }
-        if !i.par_desc.is_empty() {
-            println!("  PARAM desc: {}", i.par_desc);
-        }
-    }
-    println!("BRIEF: {}", f.fn_brief);
-    println!("DETAIL: {}", f.fn_detail);
-
-
-    for fs in &f.fn_refids {
-        if let Some(s) = structures.get(fs) {
-            println!("STRUCTURE: {}", s.str_name);
-            if !s.str_brief.is_empty() {
-                println!("           {}", s.str_brief);
-            }
-            if !s.str_description.is_empty() {
-                println!("           {}", s.str_description);
+        match TempDir::new("tar") {
+            Ok(d) => {
+                opt.output_dir = d.path().to_string_lossy().to_string();
+                opt.print_man = true;
+                opt.force = true;
+                opt.no_clobber = false;
+                opt.update_mandb = false;
+                Some(d)
             }
-            for m in &s.str_members {
-                println!("   MEMB: {} {}{}", m.par_type, m.par_name, m.par_args);
+            Err(e) => {
+                eprintln!("Cannot make directory for tar file: {e}");
+                return 1;
             }
         }
-    }
-
-    println!("----------------------");
-}
-
-// Format a long description string
-fn print_long_string(f: &mut BufWriter<File>, s: &str) -> Result<(), std::io::Error>
-{
-    let mut in_nf = false;
+    } else {
+        None
+    };
 
-    // Check for .nf / .fi and don't format those!
-    for l in s.lines() {
-        if l.starts_with(".nf") {
-            writeln!(f)?;
-            in_nf = true;
+    // --verify writes the pages somewhere private too, to compare them with the expected ones
+    let _verify_dir = if !opt.verify.is_empty() {
+        if !opt.preview.is_empty() {
+            eprintln!("--verify cannot be used with --preview");
+            return USAGE_ERROR;
         }
-
-        writeln!(f,"{l}")?;
-
-        if !in_nf {
-            writeln!(f,".PP")?;
+        #[cfg(feature = "tarball")]
+        if !opt.output_tar.is_empty() {
+            eprintln!("--verify cannot be used with --output-tar");
+            return USAGE_ERROR;
         }
+        match TempDir::new("verify") {
+            Ok(d) => {
+                opt.output_dir = d.path().to_string_lossy().to_string();
+                opt.print_man = true;
+                opt.force = true;
+                opt.no_clobber = false;
+                opt.update_mandb = false;
+                Some(d)
+            }
+            Err(e) => {
+                eprintln!("Cannot make directory for verify: {e}");
+                return 1;
+            }
+        }
+    } else {
+        None
+    };
 
-        if l.starts_with(".fi") {
-            writeln!(f)?;
-            in_nf = false;
+    if opt.serve {
+        let port = opt.port;
+        if let Err(e) = serve(&mut opt, &mut state, port) {
+            eprintln!("Cannot serve pages: {e}");
+            return 1;
         }
+        return 0;
     }
-    Ok(())
-}
 
-// Just for testing really
-fn print_ascii_pages(_opt: &Opt,
-                     functions: &[FunctionInfo],
-                     structures: &HashMap<String, StructureInfo>)
-{
-    for f in functions {
-        print_text_function(f, structures);
+    #[cfg(feature = "browse")]
+    if opt.browse {
+        if let Err(e) = browse(&mut opt, &mut state) {
+            eprintln!("Cannot browse pages: {e}");
+            return 1;
+        }
+        return 0;
     }
-}
 
+    // Structures shared between header files are only read once
+    let mut structure_cache = BTreeMap::<String, StructureInfo>::new();
 
-fn print_long_structure_comment(f: &mut BufWriter<File>, comment: &str) -> Result<(), std::io::Error>
-{
-    writeln!(f, "    \\fP/*")?;
-    write!(f, "     *")?;
+    // Every page written, for --depfile, --write-deps, --output-list, --lint and --verify
+    let mut pages = Vec::new();
 
-    let mut column = 7;
-    for word in comment.split_whitespace() {
-	column += word.len();
-	if column > 80 {
-	    write!(f, "\n     *")?;
-	    column = 7;
-	}
-	write!(f, " {word}")?;
-    }
-    writeln!(f, "\n     */")?;
-    Ok(())
-}
-
-// Prints a structure member or a function param given
-// a field width. Also reformats pointers to look nicer (IMHO)
-fn print_param(f: &mut BufWriter<File>, pi: &FnParam, type_field_width: usize,
-	       name_field_width: usize, bold: bool, delimeter: String) -> Result<(), std::io::Error>
-{
-    let mut asterisks = "  ".to_string();
-    let mut formatted_type = pi.par_type.clone();
-    let typelen: usize = formatted_type.len();
-
-    // Reformat pointer params so they look nicer
-    // these unwrap()s are safe because we check the length before doing the get()
-    if !formatted_type.is_empty() && formatted_type.get(typelen-1..typelen).unwrap() == "*" {
-        asterisks = " *".to_string();
-        formatted_type = pi.par_type.get(..typelen-1).unwrap().to_string();
-
-        // Cope with double pointers
-        if typelen > 1 && formatted_type.get(typelen-2..typelen-1).unwrap() == "*" {
-            asterisks = "**".to_string();
-            formatted_type = pi.par_type.get(..typelen-2).unwrap().to_string();
+    let mut total_times = PhaseTimes::default();
+    let mut stdout_found = false;
+    for in_file in &opt.xml_files.clone() {
+        let start = Instant::now();
+        // --stdout only needs to read files until it finds its function
+        let carry_on = if opt.stdout.is_empty() {
+            process_input(&mut opt, &mut state, in_file, &mut structure_cache, &mut pages)
         } else {
-            // Tidy function pointers
-            if typelen > 1 && formatted_type.get(typelen-2..typelen-1).unwrap() == "(" {
-                asterisks = "(*".to_string();
-                formatted_type = pi.par_type.get(..typelen-2).unwrap().to_string();
-            }
-	}
+            stdout_found = print_stdout_page(&mut opt, &mut state, in_file, &mut structure_cache);
+            !stdout_found
+        };
+        if opt.timings {
+            let times = state.timer.take();
+            eprintln!("Timings for {}: {}, total {:.2?}", in_file, times, start.elapsed());
+            total_times.add(&times);
+        }
+        if !carry_on {
+            break;
+        }
     }
-
-    // Put long comments on their own line for clarity
-    let comment_len = len_without_formatting(&pi.par_desc);
-    if comment_len > MAX_STRUCT_COMMENT_LEN {
-	print_long_structure_comment(f, &pi.par_desc)?;
+    // Before the --main-page, so that they are in its index
+    if opt.doc_pages && opt.print_man && opt.stdout.is_empty() {
+        write_doc_pages(&opt, &state, &mut pages);
     }
-
-    if bold {
-        write!(f, "    \\fB")?;
-    } else {
-        write!(f, "    \\fR")?;
+    if !state.removed_functions.is_empty() && opt.print_man && opt.stdout.is_empty() {
+        write_removed_pages(&opt, &state, &mut pages);
     }
-    write!(f, "{:<width$}{}\\fI{}\\fB{}\\fR{}",
-           formatted_type, asterisks,
-           pi.par_name, pi.par_args, delimeter, width=type_field_width)?;
-
-    // Field description */
-    if comment_len > 0 && comment_len <= MAX_STRUCT_COMMENT_LEN && name_field_width > 0 {
-	let pad_width = 1 + (name_field_width - pi.par_name.len() - pi.par_args.len()) - delimeter.len();
-	write!(f, "\\fP {:>width$} /* {} */", "", pi.par_desc, width=pad_width)?;
+    if opt.index_page && opt.print_man && opt.stdout.is_empty() {
+        write_index_page(&opt, &state, &mut pages);
     }
-    writeln!(f)?;
-    Ok(())
-}
-
-// Print a structure or enum
-fn print_structure(f: &mut BufWriter<File>, si: &StructureInfo) -> Result<(), std::io::Error>
-{
-    if !si.str_brief.is_empty() {
-        writeln!(f, "{}", si.str_brief)?;
+    if opt.main_page && opt.print_man && opt.stdout.is_empty() {
+        write_main_page(&opt, &state, &mut pages);
     }
-    if !si.str_description.is_empty() {
-        writeln!(f, "{}", si.str_description)?;
+    #[cfg(feature = "docset")]
+    if !opt.docset.is_empty() && opt.stdout.is_empty() {
+        if let Err(e) = write_docset_index(&opt, &state.docset_entries) {
+            eprintln!("Error writing docset {}: {}", opt.docset, e);
+        }
     }
-
-    let mut max_param_type_length = 0;
-    let mut max_param_name_length = 0;
-    for p in &si.str_members {
-        if p.par_type.len() > max_param_type_length {
-            max_param_type_length = p.par_type.len();
-	}
-        if p.par_name.len() + p.par_args.len() > max_param_name_length {
-            max_param_name_length = p.par_name.len() + p.par_args.len();
+    if !opt.devhelp.is_empty() && opt.stdout.is_empty() {
+        if let Err(e) = write_devhelp_book(&opt, &state.devhelp_chapters) {
+            eprintln!("Error writing devhelp book {}: {}", opt.devhelp, e);
         }
     }
-
-    writeln!(f,)?;
-    writeln!(f, ".nf")?;
-    writeln!(f, "\\fB")?;
-    match si.str_type {
-        StructureType::Enum =>  writeln!(f, "enum {} {{", si.str_name)?,
-        StructureType::Struct => writeln!(f, "struct {} {{", si.str_name)?,
-        StructureType::Unknown => writeln!(f, "??? {} {{", si.str_name)?,
-    };
-
-    let mut i=0;
-    for p in &si.str_members {
-        i += 1;
-        if i == si.str_members.len() {
-            print_param(f, p, max_param_type_length, max_param_name_length, false, "".to_string())?;
-        } else {
-            print_param(f, p, max_param_type_length, max_param_name_length, false, ";".to_string())?;
+    #[cfg(feature = "tarball")]
+    if !opt.output_tar.is_empty() {
+        if let Err(e) = write_tarball(&opt, &opt.output_tar, &pages) {
+            eprintln!("Error writing tar file {}: {}", opt.output_tar, e);
         }
     }
-
-    writeln!(f, "}};\\fP")?;
-    writeln!(f, ".PP")?;
-    writeln!(f, ".fi")?;
-
-    Ok(())
-}
-
-// Print a single man page
-fn print_man_page(opt: &Opt,
-                  man_date: &str,
-                  function: &FunctionInfo,
-                  functions: &[FunctionInfo],
-                  structures: &HashMap<String, StructureInfo>,
-                  copyright: &str) -> Result<(), std::io::Error>
-{
-    if function.fn_name == opt.headerfile && !opt.print_general {
-        return Ok(());
+    if !opt.depfile.is_empty() {
+        if let Err(e) = write_depfile(&opt.depfile, &pages) {
+            eprintln!("Error writing depfile {}: {}", opt.depfile, e);
+        }
     }
-
-    // DO IT!
-    let mut man_file = String::new();
-    if let Err(e) = write!(man_file, "{}/{}.{}", &opt.output_dir, function.fn_name, opt.man_section) {
-        eprintln!("Error making manpage filename: {e:?}");
-        return Err(Error::new(ErrorKind::Other, "Error making filename"));
+    if !opt.write_deps.is_empty() {
+        if let Err(e) = write_make_deps(&opt.write_deps, &pages) {
+            eprintln!("Error writing dependencies {}: {}", opt.write_deps, e);
+        }
     }
-
-    let dateptr = man_date;
-
-    match File::create(&man_file) {
-        Err(e) => {
-            println!("Cannot create man file {}: {}", &man_file, e);
-            return Err(e);
+    if !opt.output_list.is_empty() {
+        if let Err(e) = write_output_list(&opt.output_list, &pages) {
+            eprintln!("Error writing output list {}: {}", opt.output_list, e);
         }
-        Ok(fl) => {
-            let mut f = BufWriter::new(fl);
-
-            // Work out the length of the parameters, so we can line them up
-            let mut max_param_type_len: usize = 0;
-            let mut max_param_name_len: usize = 0;
-            let mut num_param_descs: usize = 0;
-            let mut param_count: usize = 0;
-
-            for p in &function.fn_args {
-                if (p.par_type.len() < MAX_PRINT_PARAM_LEN) &&
-                    (p.par_type.len() > max_param_type_len) {
-                        max_param_type_len = p.par_type.len();
-                    }
-                if p.par_name.len() > max_param_name_len {
-                    max_param_name_len = p.par_name.len();
-                }
-                if !p.par_desc.is_empty() && !p.par_type.is_empty() {
-                    num_param_descs += 1;
-                }
-                param_count += 1;
-            }
-
-            writeln!(f, ".\\\"  Automatically generated man page, do not edit")?;
-            writeln!(f, ".TH {} {} {} \"{}\" \"{}\"",
-                     function.fn_name.to_ascii_uppercase(), opt.man_section, dateptr, opt.package_name, opt.header)?;
-
-            writeln!(f, ".SH NAME")?;
-            writeln!(f, ".PP")?;
-            if !function.fn_brief.is_empty()  {
-                writeln!(f, "{} \\- {}", function.fn_name, function.fn_brief)?;
-            } else {
-                writeln!(f, "{}", function.fn_name)?;
-            }
-
-            writeln!(f, ".SH SYNOPSIS")?;
-            writeln!(f, ".PP")?;
-	    writeln!(f, ".nf")?;
-	    writeln!(f, ".B #include <{}{}>", opt.header_prefix, opt.headerfile)?;
-            if !function.fn_def.is_empty() {
-                writeln!(f, ".sp")?;
-                writeln!(f, "\\fB{}\\fP(", function.fn_def)?;
-
-                let mut i=0;
-                for p in &function.fn_args {
-                    i += 1;
-                    if i == param_count {
-                        print_param(&mut f, p, max_param_type_len, 0, true, "".to_string())?;
-                    } else {
-                        print_param(&mut f, p, max_param_type_len, 0, true, ",".to_string())?;
-                    }
-                }
-
-                writeln!(f, ");")?;
-                writeln!(f, ".fi")?;
-            }
-
-            if opt.print_params && num_param_descs > 0 {
-	        writeln!(f, ".SH PARAMETERS")?;
-                writeln!(f, ".PP")?;
-                for p in &function.fn_args {
-                    writeln!(f, ".TP")?;
-                    writeln!(f, "\\fB{}\\fP {}",
-                             p.par_name, p.par_desc)?;
-                }
-            }
-            if !function.fn_detail.is_empty() {
-	        writeln!(f, ".SH DESCRIPTION")?;
-                writeln!(f, ".PP")?;
-                print_long_string(&mut f, &function.fn_detail)?;
-            }
-
-            if !function.fn_refids.is_empty() {
-                let mut first = true; // In case we can't find the refids, don't print the header
-
-                for fs in &function.fn_refids {
-                    if let Some(s) = structures.get(fs) {
-                        if first {
-                            writeln!(f, ".SH STRUCTURES")?;
-                            writeln!(f, ".PP")?;
-                            first = false;
-                        }
-                        print_structure(&mut f, s)?;
-                    }
-                }
-            }
-            if !function.fn_returnval.is_empty() {
-	        writeln!(f, ".SH RETURN VALUE")?;
-                writeln!(f, ".PP")?;
-                writeln!(f, "{}", function.fn_returnval)?;
-                writeln!(f, ".br")?;
-                for rv in &function.fn_retvals {
-                    writeln!(f, ".TP")?;
-                    writeln!(f, "\\fB{}\\fR {}", rv.ret_name, rv.ret_desc)?;
-                }
-                writeln!(f, ".PP")?;
-            }
-
-            // #defines - only exists on the General manpage
-            if !function.fn_defines.is_empty() {
-                writeln!(f, ".SH DEFINES")?;
-                writeln!(f, ".PP")?;
-                for d in &function.fn_defines {
-                    // Only print ALLCAPS defines, for neatness
-                    if d.hd_name == d.hd_name.to_ascii_uppercase() {
-                        if !d.hd_brief.is_empty() {
-                            writeln!(f, ".PP")?;
-                            writeln!(f, "{}", d.hd_brief)?;
-                            writeln!(f, ".br")?;
-                        }
-                        if !d.hd_desc.is_empty() {
-                            writeln!(f, ".br")?;
-                            writeln!(f, "{}", d.hd_desc)?;
-                            writeln!(f, ".br")?;
-                        }
-
-                        writeln!(f, "#define {} {}", d.hd_name, d.hd_init)?;
-                        writeln!(f, ".br")?;
-                    }
-                }
-            }
-
-            if !function.fn_note.is_empty() {
-	        writeln!(f, ".SH NOTE")?;
-                writeln!(f, ".PP")?;
-                print_long_string(&mut f, &function.fn_note)?;
-            }
-
-            // Print list of related functions
-	    writeln!(f, ".SH SEE ALSO")?;
-	    writeln!(f, ".PP")?;
-	    writeln!(f, ".nh")?;
-	    writeln!(f, ".ad l")?;
-            let mut num_func = 0;
-            for func in functions {
-                num_func += 1;
-                if func.fn_name != function.fn_name {
-                    let delim =
-                        if num_func == functions.len() {
-                            ""
-                        } else {
-                            ", "
-                        };
-	            writeln!(f, "\\fI{}\\fP({}){}", func.fn_name, opt.man_section, delim)?;
-                };
+    }
+    if !opt.manifest.is_empty() {
+        if let Err(e) = write_manifest(&opt.manifest, &pages) {
+            eprintln!("Error writing manifest {}: {}", opt.manifest, e);
+        }
+    }
+    if opt.packaging_lists {
+        if let Err(e) = write_packaging_lists(&opt, &pages) {
+            eprintln!("Error writing packaging lists: {e}");
+        }
+    }
+    if opt.update_mandb && opt.print_man {
+        update_mandb(&opt);
+    }
+    if !opt.preview.is_empty() {
+        match pages.iter().find(|p| p.function == opt.preview) {
+            Some(p) => preview_page(&opt, &p.file),
+            None => eprintln!("No page for {} to preview", opt.preview),
+        }
+    }
+    if opt.print_man {
+        for name in &opt.only {
+            if !pages.iter().any(|p| &p.function == name) {
+                eprintln!("Warning: no page written for --only {name}");
             }
-
-            if !copyright.is_empty() {
-                writeln!(f, ".SH COPYRIGHT")?;
-                writeln!(f, ".PP")?;
-                writeln!(f,"{copyright}")?;
+        }
+    }
+    if !opt.stdout.is_empty() && !stdout_found {
+        eprintln!("No page for {} to print", opt.stdout);
+        return 1;
+    }
+    if opt.timings {
+        eprintln!("Timings for all files: {}, total {:.2?}", total_times, run_start.elapsed());
+    }
+    if opt.list {
+        for p in &pages {
+            if let Some(name) = Path::new(&p.file).file_name() {
+                println!("{}", Path::new(&output_dir).join(name).display());
             }
-
-            //END OF PRINTING
         }
     }
-    Ok(())
-}
-
-
-// Print all man pages
-fn print_man_pages(opt: &Opt,
-                   functions: &[FunctionInfo],
-                   structures: &HashMap<String, StructureInfo>) -> Result<(), std::fmt::Error>
-{
-    let mut date_to_print = String::new();
-    let mut header_copyright = String::new();
-    let mut manpage_year: i32 = opt.manpage_year;
-
-    // Get current date
-    let today: DateTime<Local> = Local::now();
-
-    if !opt.manpage_date.is_empty() {
-        date_to_print = opt.manpage_date.clone();
-    } else {
-        write!(date_to_print, "{}-{}-{}", today.year(), today.month(), today.day())?;
+    // The reports that CI might be relying on, so not writing them is a failure
+    let mut report_errors = 0;
+    if !opt.coverage.is_empty() {
+        if let Err(e) = write_coverage(&opt.coverage, &state.coverage_entries) {
+            eprintln!("Error writing coverage report {}: {}", opt.coverage, e);
+            report_errors += 1;
+        }
     }
-
-    if manpage_year == 0 {
-        manpage_year = today.year();
+    if !opt.write_baseline.is_empty() {
+        if let Err(e) = write_baseline(&opt.write_baseline, &state.undocumented) {
+            eprintln!("Error writing baseline file {}: {}", opt.write_baseline, e);
+            report_errors += 1;
+        }
     }
-
-    if opt.use_header_copyright {
-        if let Ok(s) = read_header_copyright(opt) {
-            header_copyright = s;
+    if !opt.write_pot.is_empty() {
+        if let Err(e) = write_pot(&opt.write_pot, &opt.package_name, &state.pot) {
+            eprintln!("Error writing POT file {}: {}", opt.write_pot, e);
+            report_errors += 1;
         }
-    } else {
-        write!(header_copyright, "Copyright (C) {}-{} {}, All rights reserved",
-               opt.start_year, manpage_year, opt.company)?;
     }
-
-    for f in functions {
-        print_man_page(opt, &date_to_print, f, functions, structures, &header_copyright).unwrap();
+    if !opt.sarif.is_empty() {
+        if let Err(e) = diagnostics::write_sarif(&opt.sarif) {
+            eprintln!("Error writing SARIF file {}: {}", opt.sarif, e);
+            report_errors += 1;
+        }
     }
-    Ok(())
+    let coverage_ok = check_coverage(&opt, &state);
+    let lint_errors = if opt.lint { lint_pages(&pages) } else { 0 };
+    let differences = if opt.verify.is_empty() { 0 } else { verify_pages(&opt.verify, &pages) };
+    // Writing a new baseline accepts what isn't documented now
+    let new_undocumented = if opt.write_baseline.is_empty() { state.new_undocumented } else { 0 };
+    if lint_errors > 0 || differences > 0 || state.spelling_errors > 0 || !coverage_ok || new_undocumented > 0 || state.empty_inputs > 0 ||
+        state.page_errors > 0 || report_errors > 0 {
+        return 1;
+    }
+    0
 }
 
-
 fn main() {
-
-    // Get command-line options
-    let mut opt = Opt::from_args();
-
-    for in_file in &opt.xml_files.clone() {
-        let mut main_xml_file = String::new();
-        if let Err(e) = write!(main_xml_file, "{}/{}", &opt.xml_dir, &in_file) {
-            eprintln!("Error making main XML file name for {in_file}: {e}");
-            return;
-        }
-
-        match File::open(&main_xml_file) {
-            Ok(f) => {
-                let mut parser = ParserConfig::new()
-                    .whitespace_to_characters(true)
-                    .ignore_comments(true)
-                    .create_reader(BufReader::new(f));
-
-                let mut functions = Vec::<FunctionInfo>::new();
-                let mut structures = HashMap::<String, StructureInfo>::new();
-
-                // Read it all into structures
-                if let Err(e) = read_file(&mut parser, &mut opt, &mut functions, &mut structures) {
-                    eprintln!("Error reading XML for {main_xml_file}: {e:?}");
-                    continue;
-                }
-
-                // Go through the structures map and read those files in to get the full structure info
-                let mut filled_structures = HashMap::<String, StructureInfo>::new();
-                read_structures_files(&opt, &structures,
-                                      &mut filled_structures);
-
-                // Then print those man pages!
-                if opt.print_ascii {
-                    print_ascii_pages(&opt, &functions, &filled_structures);
-                }
-                if opt.print_man {
-                    if let Err(e) = print_man_pages(&opt, &functions, &filled_structures) {
-                        eprintln!("Error in print_man_pages: {e:?}");
-                        break;
-                    }
-                }
-            }
-            Err(e) => {
-                println!("Cannot open XML file {}: {}", &main_xml_file, e);
-            }
-        }
-    }
+    std::process::exit(run());
 }
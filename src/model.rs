@@ -0,0 +1,226 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+// Function parameter - also used for structure members
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FnParam
+{
+    pub par_name: String,
+    pub par_type: String,
+    pub par_refid: Option<String>,
+    pub par_args: String,
+    pub par_desc: String,
+    pub par_brief: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub par_defval: String, // C++ default value
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub par_direction: String, // in, out or inout from @param[in] etc
+}
+
+impl FnParam {
+    // The ... of a variadic function, it has no name
+    pub fn is_variadic(&self) -> bool {
+        self.par_type == "..." && self.par_name.is_empty()
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReturnVal
+{
+    pub ret_name: String,
+    pub ret_desc: String,
+}
+
+// An xrefsect, these are usually created by custom ALIASES in the Doxyfile
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct XrefSect
+{
+    pub xr_title: String,
+    pub xr_desc: String,
+}
+
+// \attention, \remark or \invariant, these get sections of their own like notes.
+// \copyright is kept here too, for the COPYRIGHT section.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SimpleSect
+{
+    pub ss_kind: String, // attention, remark, invariant or copyright
+    pub ss_text: String,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StructureType
+{
+    Unknown,
+    Enum,
+    Struct,
+}
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StructureInfo
+{
+    pub str_type: StructureType,
+    pub str_name: String,
+    pub str_brief: String,
+    pub str_description: String,
+    pub str_members: Vec<FnParam>,
+}
+
+impl StructureInfo {
+    pub fn new() -> StructureInfo {
+        StructureInfo {
+            str_type: StructureType::Unknown,
+            str_name: String::new(),
+            str_brief: String::new(),
+            str_description: String::new(),
+            str_members: Vec::<FnParam>::new(),
+        }
+    }
+}
+
+impl Default for StructureInfo {
+    fn default() -> StructureInfo {
+        StructureInfo::new()
+    }
+}
+
+// A <ref> in a function's documentation (or its types), eg to another function
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DocRef
+{
+    pub ref_id: String,
+    pub ref_name: String,
+}
+
+// Collected #defines - printed on the General page.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HashDefine
+{
+    pub hd_name: String,
+    pub hd_init: String,
+    pub hd_brief: String,
+    pub hd_desc: String,
+}
+
+
+// Information for a function.
+// Pretty much everything else is hung off this
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FunctionInfo
+{
+    pub fn_type: String,
+    pub fn_name: String,
+    pub fn_def: String,
+    pub fn_argsstring: String,
+    pub fn_brief: String,
+    pub fn_detail: String,
+    pub fn_returnval: String,
+    pub fn_note: String,
+    pub fn_args: Vec<FnParam>,
+    pub fn_defines: Vec<HashDefine>,
+    pub fn_retvals: Vec<ReturnVal>,
+    pub fn_xrefsects: Vec<XrefSect>,
+    pub fn_refids: Vec<String>, // refids for structs used in the function
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_typedef: bool, // A callback (function pointer) typedef rather than a function
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_refs: Vec<DocRef>, // <ref>s in the documentation, for --ref-map
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_static: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_inline: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_internal_detail: String, // \internal sections of the description
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_file: String,            // Where it is declared, if known
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_line: u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_attributes: Vec<String>, // Compiler attributes, eg deprecated or nonnull(1)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_id: String,              // doxygen's id, that <ref>s to it use
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fn_simplesects: Vec<SimpleSect>,
+}
+
+impl FunctionInfo {
+    pub fn new() -> FunctionInfo {
+        FunctionInfo {
+            fn_type: String::new(),
+            fn_name: String::new(),
+            fn_def: String::new(),
+            fn_argsstring: String::new(),
+            fn_brief: String::new(),
+            fn_detail: String::new(),
+            fn_returnval: String::new(),
+            fn_note: String::new(),
+            fn_args: Vec::<FnParam>::new(),
+            fn_defines: Vec::<HashDefine>::new(),
+            fn_retvals: Vec::<ReturnVal>::new(),
+            fn_xrefsects: Vec::<XrefSect>::new(),
+            fn_refids: Vec::<String>::new(),
+            fn_typedef: false,
+            fn_refs: Vec::<DocRef>::new(),
+            fn_static: false,
+            fn_inline: false,
+            fn_internal_detail: String::new(),
+            fn_file: String::new(),
+            fn_line: 0,
+            fn_attributes: Vec::new(),
+            fn_id: String::new(),
+            fn_simplesects: Vec::new(),
+        }
+    }
+}
+
+impl Default for FunctionInfo {
+    fn default() -> FunctionInfo {
+        FunctionInfo::new()
+    }
+}
+
+// A doxygen \mainpage or \page. The sections in pg_detail have already been
+// turned into .SH/.SS lines.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DocPage
+{
+    pub pg_name: String,
+    pub pg_title: String,
+    pub pg_brief: String,
+    pub pg_detail: String,
+}
+
+impl DocPage {
+    pub fn new() -> DocPage {
+        DocPage {
+            pg_name: String::new(),
+            pg_title: String::new(),
+            pg_brief: String::new(),
+            pg_detail: String::new(),
+        }
+    }
+}
+
+impl Default for DocPage {
+    fn default() -> DocPage {
+        DocPage::new()
+    }
+}
@@ -0,0 +1,199 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Compiler attributes in declarations, eg __attribute__((deprecated)),
+// [[nodiscard]] or glibc's __wur and __nonnull((1)). These are taken out of
+// the types and argsstrings, so they don't clutter the SYNOPSIS, and kept in
+// fn_attributes as eg "deprecated" or "nonnull(1, 2)" for the pages to describe.
+
+use crate::model::*;
+
+fn is_ident(c: char) -> bool
+{
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Macros for attributes (glibc's and the kernel's), and whether they take arguments
+fn macro_attribute(word: &str) -> Option<(&'static str, bool)>
+{
+    match word {
+        "__attribute_deprecated__" | "__deprecated" => Some(("deprecated", false)),
+        "__attribute_deprecated_msg__" => Some(("deprecated", true)),
+        "__nonnull" => Some(("nonnull", true)),
+        "__wur" | "__attribute_warn_unused_result__" | "__must_check" => Some(("warn_unused_result", false)),
+        "__attribute_pure__" | "__pure" => Some(("pure", false)),
+        "__attribute_const__" => Some(("const", false)),
+        "__attribute_malloc__" | "__malloc" => Some(("malloc", false)),
+        "__printf" => Some(("format", true)),
+        "_Noreturn" | "noreturn" | "__noreturn" | "__attribute_noreturn__" => Some(("noreturn", false)),
+        "__THROW" | "__THROWNL" => Some(("nothrow", false)),
+        _ => None,
+    }
+}
+
+// If s starts with a (bracketed list), returns what is inside the brackets and the rest of s
+fn bracketed(s: &str) -> Option<(&str, &str)>
+{
+    let s = s.trim_start();
+    if !s.starts_with('(') {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&s[1..i], &s[i + 1..]));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Add the attributes in eg "deprecated("use bar"), nonnull (1, 2)"
+fn add_attribute_list(list: &str, attributes: &mut Vec<String>)
+{
+    let mut depth = 0;
+    let mut start = 0;
+    let mut items = Vec::new();
+    for (i, c) in list.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&list[start..]);
+
+    for item in items {
+        let item = item.trim();
+        let (name, args) = match item.find('(') {
+            Some(p) => (item[..p].trim(), item[p..].trim()),
+            None => (item, ""),
+        };
+        let name = name.trim_start_matches("gnu::").trim_start_matches("clang::").trim_matches('_');
+        if !name.is_empty() {
+            attributes.push(format!("{name}{args}"));
+        }
+    }
+}
+
+// Take the attributes out of some C, adding them to attributes.
+// The text is only changed if there are any.
+pub fn strip_attributes(text: &str, attributes: &mut Vec<String>) -> String
+{
+    let mut out = String::new();
+    let mut rest = text;
+    let mut found = false;
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("[[") {
+            if let Some(end) = rest.find("]]") {
+                add_attribute_list(&rest[2..end], attributes);
+                rest = &rest[end + 2..];
+                found = true;
+                continue;
+            }
+        }
+        if !is_ident(c) {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let len = rest.find(|c| !is_ident(c)).unwrap_or(rest.len());
+        let (word, after) = rest.split_at(len);
+        if word == "__attribute__" || word == "__attribute" {
+            if let Some((args, remain)) = bracketed(after) {
+                add_attribute_list(bracketed(args).map_or(args, |(a, _)| a), attributes);
+                rest = remain;
+                found = true;
+                continue;
+            }
+        }
+        if let Some((name, takes_args)) = macro_attribute(word) {
+            match bracketed(after).filter(|_| takes_args) {
+                Some((args, remain)) => {
+                    // __nonnull((1, 2)) has its list in another set of brackets
+                    let args = bracketed(args).map_or(args, |(a, _)| a);
+                    attributes.push(format!("{}({})", name, args.trim()));
+                    rest = remain;
+                }
+                None => {
+                    attributes.push(name.to_string());
+                    rest = after;
+                }
+            }
+            found = true;
+            continue;
+        }
+        out.push_str(word);
+        rest = after;
+    }
+
+    if !found {
+        return text.to_string();
+    }
+    // Tidy up the gaps they leave
+    let mut tidy = out.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if text.starts_with(' ') {
+        tidy.insert(0, ' ');
+    }
+    tidy.replace("( ", "(").replace(" )", ")").replace(" ,", ",")
+}
+
+// Move the attributes in a function's declaration into fn_attributes.
+// Clang's _Nonnull on a parameter becomes nonnull(<its position>).
+pub fn extract_attributes(function: &mut FunctionInfo)
+{
+    let mut attributes = std::mem::take(&mut function.fn_attributes);
+    function.fn_type = strip_attributes(&function.fn_type, &mut attributes);
+    function.fn_def = strip_attributes(&function.fn_def, &mut attributes);
+    function.fn_argsstring = strip_attributes(&function.fn_argsstring, &mut attributes);
+
+    for (i, p) in function.fn_args.iter_mut().enumerate() {
+        let mut words = Vec::new();
+        let mut changed = false;
+        for w in p.par_type.split_whitespace() {
+            // They can be stuck to the *, eg "char *_Nonnull"
+            let bare = w.trim_start_matches('*');
+            let stars = &w[..w.len() - bare.len()];
+            match bare {
+                "_Nonnull" => attributes.push(format!("nonnull({})", i + 1)),
+                "_Nullable" | "_Null_unspecified" => {}
+                _ => {
+                    words.push(w);
+                    continue;
+                }
+            }
+            changed = true;
+            if !stars.is_empty() {
+                words.push(stars);
+            }
+        }
+        if changed {
+            p.par_type = words.join(" ");
+        }
+        let mut ignored = Vec::new();
+        p.par_type = strip_attributes(&p.par_type, &mut ignored);
+    }
+
+    for a in attributes {
+        if !function.fn_attributes.contains(&a) {
+            function.fn_attributes.push(a);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// \cond blocks for --enabled-sections. doxygen doesn't say in the XML which
+// things were in a \cond block, so they are found in the header files and
+// matched against the line numbers in the <location>s.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use crate::diagnostics;
+
+// The lines from a \cond to its \endcond. An empty label is never enabled.
+#[derive(Debug, Clone)]
+pub struct CondBlock {
+    pub first_line: u32,
+    pub last_line: u32,
+    pub label: String,
+}
+
+// Find the command (eg "cond") in a line, as \cond or @cond, and not as
+// the start of a longer word. Returns where the text after it starts.
+fn find_command(line: &str, command: &str) -> Option<(usize, usize)>
+{
+    let mut from = 0;
+    while let Some(pos) = line[from..].find(command) {
+        let start = from + pos;
+        let end = start + command.len();
+        let before = line[..start].chars().next_back();
+        let after = line[end..].chars().next();
+        if (before == Some('\\') || before == Some('@')) &&
+            !after.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Some((start - 1, end));
+        }
+        from = end;
+    }
+    None
+}
+
+// The section label after a \cond, a word or a (bracketed expression)
+fn read_label(rest: &str) -> String
+{
+    let rest = rest.trim_start();
+    if rest.starts_with('(') {
+        let mut depth = 0;
+        for (i, c) in rest.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return rest[..=i].to_string();
+                    }
+                }
+                _ => {}
+            }
+        }
+        return rest.to_string();
+    }
+    rest.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '_').collect()
+}
+
+// Read the \cond blocks in a C file, nested ones are returned separately
+pub fn read_cond_blocks(file: &str) -> Result<Vec<CondBlock>, std::io::Error>
+{
+    let text = fs::read_to_string(file)?;
+    let mut blocks = Vec::new();
+    let mut open = Vec::<(u32, String)>::new();
+    let mut line_no = 0;
+
+    for line in text.lines() {
+        line_no += 1;
+        let mut rest = line;
+        loop {
+            let cond = find_command(rest, "cond");
+            let endcond = find_command(rest, "endcond");
+            match (cond, endcond) {
+                (Some((c, c_end)), e) if e.is_none_or(|(e, _)| c < e) => {
+                    open.push((line_no, read_label(&rest[c_end..])));
+                    rest = &rest[c_end..];
+                }
+                (_, Some((_, e_end))) => {
+                    if let Some((first_line, label)) = open.pop() {
+                        blocks.push(CondBlock {first_line, last_line: line_no, label});
+                    }
+                    rest = &rest[e_end..];
+                }
+                _ => break,
+            }
+        }
+    }
+    // An unterminated \cond goes to the end of the file
+    for (first_line, label) in open {
+        blocks.push(CondBlock {first_line, last_line: line_no, label});
+    }
+    Ok(blocks)
+}
+
+// Split a label into words and operators
+fn label_tokens(label: &str) -> Vec<String>
+{
+    let mut tokens = Vec::new();
+    let mut chars = label.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ')' | '!' => tokens.push(c.to_string()),
+            '&' | '|' => {
+                if chars.peek() == Some(&c) {
+                    chars.next();
+                }
+                tokens.push(format!("{c}{c}"));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&n) = chars.peek() {
+                    if n.is_whitespace() || "()!&|".contains(n) {
+                        break;
+                    }
+                    word.push(n);
+                    chars.next();
+                }
+                tokens.push(word);
+            }
+        }
+    }
+    tokens
+}
+
+// label := and ('||' and)*, and := not ('&&' not)*, not := '!' not | '(' label ')' | word
+fn eval_or(tokens: &[String], pos: &mut usize, enabled: &[String]) -> bool
+{
+    let mut value = eval_and(tokens, pos, enabled);
+    while tokens.get(*pos).is_some_and(|t| t == "||") {
+        *pos += 1;
+        value |= eval_and(tokens, pos, enabled);
+    }
+    value
+}
+
+fn eval_and(tokens: &[String], pos: &mut usize, enabled: &[String]) -> bool
+{
+    let mut value = eval_not(tokens, pos, enabled);
+    while tokens.get(*pos).is_some_and(|t| t == "&&") {
+        *pos += 1;
+        value &= eval_not(tokens, pos, enabled);
+    }
+    value
+}
+
+fn eval_not(tokens: &[String], pos: &mut usize, enabled: &[String]) -> bool
+{
+    let token = match tokens.get(*pos) {
+        Some(t) => t.as_str(),
+        None => return false,
+    };
+    *pos += 1;
+    match token {
+        "!" => !eval_not(tokens, pos, enabled),
+        "(" => {
+            let value = eval_or(tokens, pos, enabled);
+            if tokens.get(*pos).is_some_and(|t| t == ")") {
+                *pos += 1;
+            }
+            value
+        }
+        word => enabled.iter().any(|s| s == word),
+    }
+}
+
+// Whether a \cond label (eg ADVANCED or (LINUX && !OLD_API)) is enabled
+pub fn cond_enabled(label: &str, enabled: &[String]) -> bool
+{
+    let tokens = label_tokens(label);
+    if tokens.is_empty() {
+        return false;
+    }
+    eval_or(&tokens, &mut 0, enabled)
+}
+
+// The \cond blocks of the files seen so far, so each is only read once
+#[derive(Debug, Default)]
+pub struct CondBlocks {
+    files: RefCell<BTreeMap<String, Vec<CondBlock>>>,
+}
+
+impl CondBlocks {
+    // Whether a line of a file is in a \cond block that isn't enabled. Files
+    // that can't be read have no blocks.
+    pub fn hides(&self, file: &str, line: u32, enabled: &[String]) -> bool
+    {
+        let mut files = self.files.borrow_mut();
+        let blocks = files.entry(file.to_string()).or_insert_with(|| {
+            match read_cond_blocks(file) {
+                Ok(b) => b,
+                Err(e) => {
+                    diagnostics::warning("unreadable-file", "", &format!("cannot read {file} for --enabled-sections: {e}"));
+                    Vec::new()
+                }
+            }
+        });
+        blocks.iter().any(|b| b.first_line <= line && line <= b.last_line && !cond_enabled(&b.label, enabled))
+    }
+
+    // The files that have been read
+    pub fn files(&self) -> Vec<String>
+    {
+        self.files.borrow().keys().cloned().collect()
+    }
+}
@@ -0,0 +1,824 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use super::reader::{XmlReader, XmlEvent, XmlName};
+
+use crate::diagnostics;
+use crate::model::*;
+use super::attributes::extract_attributes;
+use super::get_attr;
+use super::text::{collect_text, collect_text_and_refid, collect_text_into, parse_standard_elements, CODE_FONT};
+
+// Collect a single ReturnVal
+fn collect_retval<R: BufRead>(parser: &mut XmlReader<R>, elem_name: &XmlName) -> Result<ReturnVal, super::reader::Error>
+{
+    let mut ret_name = String::new();
+    let mut ret_desc = String::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "parameternamelist" => {
+                                ret_name = collect_text(parser, name)?.trim().to_string();
+                            }
+                            "parameterdescription" => {
+                                ret_desc = collect_text(parser, name)?.trim().to_string();
+                            }
+                            _ => {
+                                let _text = collect_text(parser, name)?;
+                            }
+                        }
+                    }
+                    XmlEvent::Characters(s) => {
+                        let _text = s;
+                    }
+                    XmlEvent::EndElement {name, ..} if name == elem_name => {
+                        return Ok(ReturnVal{ret_name, ret_desc})
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+// Collect all retvals for a function
+fn collect_retvals<R: BufRead>(parser: &mut XmlReader<R>, elem_name: &XmlName) -> Result<Vec<ReturnVal>, super::reader::Error>
+{
+    let mut rvs = Vec::<ReturnVal>::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "parameteritem" => {
+                                rvs.push(collect_retval(parser, name)?);
+                            }
+                            _ => {
+                                let _text = collect_text(parser, name)?;
+                            }
+                        }
+                    }
+                    XmlEvent::Characters(s) => {
+                        let _text = s;
+                    }
+                    XmlEvent::EndElement {name, ..} if name == elem_name => {
+                        return Ok(rvs)
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+
+// Collect the title and text of an xrefsect
+fn collect_xrefsect<R: BufRead>(parser: &mut XmlReader<R>, elem_name: &XmlName) -> Result<XrefSect, super::reader::Error>
+{
+    let mut xr_title = String::new();
+    let mut xr_desc = String::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "xreftitle" => {
+                                xr_title = collect_text(parser, name)?.trim().to_string();
+                            }
+                            "xrefdescription" => {
+                                xr_desc = collect_text(parser, name)?.trim().to_string();
+                            }
+                            _ => {
+                                let _text = collect_text(parser, name)?;
+                            }
+                        }
+                    }
+                    XmlEvent::EndElement {name, ..} if name == elem_name => {
+                        return Ok(XrefSect{xr_title, xr_desc});
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+// Returns the name, direction (from @param[in] etc) and description of a parameter
+fn collect_parameter_item<R: BufRead>(parser: &mut XmlReader<R>, elem_name: &XmlName) -> Result<(String, String, String), super::reader::Error>
+{
+    let mut par_name = String::new();
+    let mut par_direction = String::new();
+    let mut par_desc = String::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            // The names are read from inside it
+                            "parameternamelist" => {}
+                            "parametername" => {
+                                par_direction = get_attr(&e, "direction");
+                                par_name += collect_text(parser, name)?.trim();
+                            }
+                            "parameterdescription" => {
+                                par_desc = collect_text(parser, name)?.trim().to_string();
+                            }
+                            _ => {
+                                let _text = collect_text(parser, name)?;
+                            }
+                        }
+                    }
+                    XmlEvent::Characters(s) => {
+                        let _text = s;
+                    }
+                    XmlEvent::EndElement {name, ..} if name == elem_name => {
+                        return Ok((par_name, par_direction, par_desc));
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+// A documented @param doesn't match any of the function's arguments,
+// its description will be lost so tell the user
+pub fn warn_unknown_param(fn_name: &str, doc_name: &str, params: &[FnParam])
+{
+    match params.iter().find(|p| p.par_name.eq_ignore_ascii_case(doc_name)) {
+        Some(p) => diagnostics::warning("unknown-param", fn_name, &format!("documented parameter '{doc_name}' does not match any argument (did you mean '{}'?)", p.par_name)),
+        None => diagnostics::warning("unknown-param", fn_name, &format!("documented parameter '{doc_name}' does not match any argument")),
+    }
+}
+
+// Put the <computeroutput>s that name a parameter in italics. doxygen gives \p
+// and \c the same markup, so these are taken to be the \p ones. Italic words
+// that aren't parameters (kernel-doc's @name, libclang's \p) and code words that
+// only differ in case from one are probably mistakes, so are returned.
+fn mark_text_param_refs(text: &str, names: &[&str], unknown: &mut Vec<String>) -> String
+{
+    let mut out = String::new();
+    let mut rest = text;
+    loop {
+        let font = [CODE_FONT, "\\fI"].iter()
+            .filter_map(|f| rest.find(f).map(|pos| (pos, *f)))
+            .min();
+        let (pos, font) = match font {
+            Some(f) => f,
+            None => break,
+        };
+        let after = &rest[pos + font.len()..];
+        let end = match after.find("\\fR") {
+            Some(e) => e,
+            None => break,
+        };
+        let word = &after[..end];
+        out.push_str(&rest[..pos]);
+        let is_ident = !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if is_ident && names.contains(&word) {
+            out.push_str(&format!("\\fI{word}\\fR"));
+        } else {
+            if is_ident && (font == "\\fI" || names.iter().any(|n| n.eq_ignore_ascii_case(word))) &&
+                !unknown.iter().any(|u| u == word) {
+                unknown.push(word.to_string());
+            }
+            out.push_str(&rest[pos..pos + font.len() + end + 3]);
+        }
+        rest = &after[end + 3..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// Parameter references in a function's documentation, see mark_text_param_refs
+pub fn mark_param_refs(function: &mut FunctionInfo)
+{
+    let arg_names: Vec<String> = function.fn_args.iter().map(|p| p.par_name.clone()).filter(|n| !n.is_empty()).collect();
+    let names: Vec<&str> = arg_names.iter().map(|n| n.as_str()).collect();
+    let mut unknown = Vec::new();
+
+    let mut texts = vec![&mut function.fn_brief, &mut function.fn_detail, &mut function.fn_internal_detail,
+                         &mut function.fn_returnval, &mut function.fn_note];
+    texts.extend(function.fn_args.iter_mut().map(|p| &mut p.par_desc));
+    texts.extend(function.fn_retvals.iter_mut().map(|r| &mut r.ret_desc));
+    texts.extend(function.fn_simplesects.iter_mut().map(|s| &mut s.ss_text));
+    for t in texts {
+        if t.contains('\\') {
+            *t = mark_text_param_refs(t, &names, &mut unknown);
+        }
+    }
+
+    for u in unknown {
+        match names.iter().find(|n| n.eq_ignore_ascii_case(&u)) {
+            Some(n) => diagnostics::warning("unknown-param-ref", &function.fn_name, &format!("parameter reference '{u}' does not match any argument (did you mean '{n}'?)")),
+            None => diagnostics::warning("unknown-param-ref", &function.fn_name, &format!("parameter reference '{u}' does not match any argument")),
+        }
+    }
+}
+
+fn collect_params<R: BufRead>(parser: &mut XmlReader<R>, elem_name: &XmlName,
+                           fn_name: &str, params: &mut Vec<FnParam>) -> Result<(), super::reader::Error>
+{
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "parameteritem" => {
+                                let (name, direction, desc) = collect_parameter_item(parser, name)?;
+                                // Add the desc to this param
+                                let mut found = false;
+                                for p in &mut *params {
+                                    // @param ... documents the variadic arguments
+                                    if p.par_name == name || (name == "..." && p.is_variadic()) {
+                                        p.par_desc = desc.clone();
+                                        p.par_direction = direction.clone();
+                                        found = true;
+                                    }
+                                }
+                                if !found {
+                                    warn_unknown_param(fn_name, &name, params);
+                                }
+                            }
+                            _ => {
+                                let _text = collect_text(parser, name)?;
+                            }
+                        }
+                    }
+                    XmlEvent::Characters(s) => {
+                        let _text = s;
+                    }
+                    XmlEvent::EndElement {name, ..} if name == elem_name => {
+                        return Ok(())
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+
+}
+
+// Called from "detaileddescription", so only needs to process tags that are immediately below it
+// (everything below that is handled by collect_text()),
+// and returns the main text, return text, and notes
+pub fn collect_detail_bits<R: BufRead>(parser: &mut XmlReader<R>,
+                                    elem_name: &XmlName,
+                                    function: &mut FunctionInfo) -> Result<(), super::reader::Error>
+{
+    let mut text = String::new();
+    let mut returns = String::new();
+    let mut notes = String::new();
+    let mut retvals = Vec::<ReturnVal>::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "para" => {
+                                // Text before a nested para (eg a table's caption) goes first
+                                if !text.trim().is_empty() {
+                                    function.fn_detail += text.trim_end();
+                                    function.fn_detail.push('\n');
+                                    text.clear();
+                                }
+                                let detail_len = function.fn_detail.len();
+                                let internal_len = function.fn_internal_detail.len();
+                                collect_detail_bits(parser, name, function)?;
+                                // Paras that only hold an \internal section don't leave a gap
+                                if function.fn_detail.len() != detail_len || function.fn_internal_detail.len() == internal_len {
+                                    function.fn_detail += "\n";
+                                }
+                            }
+                            "parameterlist" => {
+                                if get_attr(&e, "kind") == "retval" {
+                                    retvals = collect_retvals(parser, name)?;
+                                } else if get_attr(&e, "kind") == "param" {
+                                    collect_params(parser, name, &function.fn_name, &mut function.fn_args)?;
+                                } else {
+                                    collect_text_into(parser, name, &mut text)?;
+                                }
+                            }
+                            "xrefsect" => {
+                                function.fn_xrefsects.push(collect_xrefsect(parser, name)?);
+                            }
+                            // Markdown > quotes are indented
+                            "blockquote" => {
+                                if !text.trim().is_empty() {
+                                    function.fn_detail += text.trim_end();
+                                    text.clear();
+                                }
+                                if !function.fn_detail.is_empty() && !function.fn_detail.ends_with('\n') {
+                                    function.fn_detail.push('\n');
+                                }
+                                function.fn_detail += ".RS\n";
+                                collect_detail_bits(parser, name, function)?;
+                                if !function.fn_detail.ends_with('\n') {
+                                    function.fn_detail.push('\n');
+                                }
+                                function.fn_detail += ".RE\n";
+                            }
+                            // @section and @subsection, the titles are subsection headings
+                            // and bold paragraphs in the DESCRIPTION
+                            "sect1" | "sect2" | "sect3" | "sect4" => {
+                                collect_detail_bits(parser, name, function)?;
+                            }
+                            "title" if elem_name.local_name.starts_with("sect") => {
+                                let title = collect_text(parser, name)?;
+                                if !function.fn_detail.is_empty() && !function.fn_detail.ends_with('\n') {
+                                    function.fn_detail.push('\n');
+                                }
+                                if elem_name.local_name == "sect1" {
+                                    function.fn_detail += &format!(".SS {}\n", title.trim());
+                                } else {
+                                    function.fn_detail += &format!("\\fB{}\\fR\n", title.trim());
+                                }
+                            }
+                            // \internal sections (only there if doxygen has INTERNAL_DOCS = YES)
+                            // are kept apart, so they can be left out of the pages
+                            "internal" => {
+                                let mut internal = FunctionInfo::new();
+                                internal.fn_name = function.fn_name.clone();
+                                internal.fn_args = std::mem::take(&mut function.fn_args);
+                                collect_detail_bits(parser, name, &mut internal)?;
+                                function.fn_args = internal.fn_args;
+                                if !function.fn_internal_detail.is_empty() {
+                                    function.fn_internal_detail.push('\n');
+                                }
+                                function.fn_internal_detail += internal.fn_detail.trim();
+                                function.fn_internal_detail += internal.fn_internal_detail.as_str();
+                            }
+                            "simplesect" => {
+                                if get_attr(&e, "kind") == "return" {
+                                    collect_text_into(parser, name, &mut returns)?;
+                                } else if get_attr(&e, "kind") == "note" {
+                                    collect_text_into(parser, name, &mut notes)?;
+                                } else if ["attention", "remark", "invariant", "copyright"].contains(&get_attr(&e, "kind").as_str()) {
+                                    let ss_text = collect_text(parser, name)?.trim().to_string();
+                                    function.fn_simplesects.push(SimpleSect {ss_kind: get_attr(&e, "kind"), ss_text});
+                                } else  {
+                                    collect_text_into(parser, name, &mut text)?;
+                                }
+                            }
+                            _ => {
+                                parse_standard_elements(parser, name, &e, &mut text)?;
+                            }
+                        }
+                    }
+                    XmlEvent::Characters(s) => {
+                        text += s;
+                    }
+                    XmlEvent::EndElement {name, ..} if name == elem_name => {
+                        // Only return if we are at the end of the element that called us
+                        function.fn_detail += text.trim_end().to_string().as_str();
+                        function.fn_returnval += returns.as_str();
+                        function.fn_note += notes.as_str();
+                        function.fn_retvals.append(&mut retvals);
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn collect_function_param<R: BufRead>(parser: &mut XmlReader<R>,
+                                   structures: &mut BTreeMap<String, StructureInfo>) -> Result<FnParam, super::reader::Error>
+{
+    let mut par_name = String::new();
+    let mut par_type = String::new();
+    let mut par_refid = None;
+    let mut par_defval = String::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        let (tmp, refid, compound) = collect_text_and_refid(parser)?;
+                        // Structures have files of their own to read them from. Enums are
+                        // members, they are read from the header's file when it gets to them.
+                        if let Some(r) = refid.as_ref().filter(|_| compound) {
+                            if structures.get(r).is_none() {
+                                let new_struct = StructureInfo {str_type: StructureType::Struct, str_name: tmp.clone(), str_brief: String::new(), str_description: String::new(), str_members: Vec::<FnParam>::new()};
+                                structures.insert(r.clone(), new_struct);
+                            }
+                        }
+
+                        if name.local_name == "type" {
+                            par_type = tmp.clone();
+                            par_refid = refid.clone();
+                        }
+                        if name.local_name == "declname" {
+                            par_name = tmp.clone();
+                        }
+                        // C++ default values
+                        if name.local_name == "defval" {
+                            par_defval = tmp.trim().to_string();
+                        }
+                    }
+
+                    XmlEvent::EndElement {..} => {
+                        return Ok(FnParam{par_name, par_type, par_refid, par_args: String::new(), par_desc: String::new(), par_brief: String::new(), par_defval, par_direction: String::new()});
+                    }
+                    _e => {
+                    }
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+// Split one argument from a prototype (eg "const char *name", or "int flags = 0"
+// in C++) into a parameter
+pub fn parse_arg(arg: &str) -> FnParam
+{
+    let (arg, par_defval) = match arg.split_once('=') {
+        Some((a, d)) => (a.trim_end(), d.trim().to_string()),
+        None => (arg, String::new()),
+    };
+    let mut param = FnParam {par_name: String::new(), par_type: arg.to_string(), par_refid: None,
+                             par_args: String::new(), par_desc: String::new(), par_brief: String::new(), par_defval,
+                             par_direction: String::new()};
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    // The name is the last word, before any array size, or in (*name) for function pointers
+    let name_end = match arg.find("(*") {
+        Some(p) => match arg[p..].find(')') {
+            Some(e) => p + e,
+            None => return param,
+        },
+        None => arg.find('[').unwrap_or(arg.len()),
+    };
+    let name_start = arg[..name_end].trim_end().rfind(|c| !is_ident(c)).map_or(0, |p| p + 1);
+    let name = arg[name_start..name_end].trim_end();
+    if name_start == 0 || name.is_empty() || !name.chars().all(is_ident) {
+        // No name, just a type
+        return param;
+    }
+    param.par_name = name.to_string();
+    param.par_type = arg[..name_start].trim_end().to_string();
+    param.par_args = arg[name_end..].to_string();
+    param
+}
+
+// Make the parameters from an argument list, eg "(int a, char *b)" or the
+// argsstring of a callback typedef, eg ")(void *data, int event)"
+pub fn parse_args_string(argsstring: &str) -> Vec<FnParam>
+{
+    let args = argsstring.trim_start_matches(')').trim();
+    let args = args.strip_prefix('(').unwrap_or(args);
+    let args = args.strip_suffix(')').unwrap_or(args);
+
+    let mut params = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(parse_arg(args[start..i].trim()));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = args[start..].trim();
+    if !last.is_empty() && last != "void" {
+        params.push(parse_arg(last));
+    }
+    params
+}
+
+pub fn collect_function_info<R: BufRead>(parser: &mut XmlReader<R>,
+                                      functions: &mut Vec<FunctionInfo>,
+                                      structures: &mut BTreeMap<String, StructureInfo>) -> Result<(), super::reader::Error>
+{
+    let mut function = FunctionInfo::new();
+    parser.refs.clear();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "type" => {
+                                function.fn_type = collect_text(parser, name)?;
+                            },
+                            "definition" =>  {
+                                function.fn_def = collect_text(parser, name)?;
+                            }
+                            "argsstring" => {
+                                function.fn_argsstring = collect_text(parser, name)?;
+                            }
+                            "name" | "compoundname" => {
+                                function.fn_name = collect_text(parser, name)?;
+                            }
+                            "param" => {
+                                let param = collect_function_param(parser, structures)?;
+                                // If the param has a refid then make a note of it so we
+                                // can expand structures in the manpage.
+                                // Skip duplicates for functions where a structure appears
+                                // as multiple arguments (not common, but no need to print it twice)
+                                // and keep them in the order they are used in the prototype.
+                                if let Some(r) = &param.par_refid {
+                                    if !function.fn_refids.contains(r) {
+                                        function.fn_refids.push(r.clone());
+                                    }
+                                }
+                                function.fn_args.push(param);
+                            }
+                            "briefdescription" => {
+                                function.fn_brief = collect_text(parser, name)?;
+                            }
+                            "location" => {
+                                function.fn_file = get_attr(&e, "file");
+                                function.fn_line = get_attr(&e, "line").parse().unwrap_or(0);
+                            }
+                            "detaileddescription" => {
+                                // Function pointer typedefs have no <param>s, the arguments
+                                // are only in the argsstring
+                                if function.fn_args.is_empty() && function.fn_argsstring.starts_with(")(") {
+                                    function.fn_args = parse_args_string(&function.fn_argsstring);
+                                }
+                                collect_detail_bits(parser, name, &mut function)?;
+                            }
+                            _ => {
+                                // Not used,. but still need to consume it
+                                let _fntext = collect_text(parser, name)?;
+                            }
+                        }
+                    }
+                    XmlEvent::Characters(_s) => {
+
+                    }
+                    XmlEvent::EndElement {name, ..} if name.local_name == "memberdef" => {
+                        add_doc_refs(parser, &mut function);
+                        extract_attributes(&mut function);
+                        mark_param_refs(&mut function);
+                        functions.push(function);
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+// Move the <ref>s the parser has seen into the function, so that --ref-map can
+// find the ones that point at other libraries
+pub fn add_doc_refs<R: BufRead>(parser: &mut XmlReader<R>, function: &mut FunctionInfo)
+{
+    for (ref_id, ref_name) in parser.refs.drain(..) {
+        if !function.fn_refs.iter().any(|r| r.ref_id == ref_id && r.ref_name == ref_name) {
+            function.fn_refs.push(DocRef {ref_id, ref_name});
+        }
+    }
+}
+
+pub fn collect_define<R: BufRead>(parser: &mut XmlReader<R>) -> Result<HashDefine, super::reader::Error>
+{
+    let mut hd_name = String::new();
+    let mut hd_init = String::new();
+    let mut hd_brief = String::new();
+    let mut hd_desc = String::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "name" => {
+                                hd_name = collect_text(parser, name)?;
+                            }
+                            "initializer" => {
+                                hd_init = collect_text(parser, name)?;
+                            }
+                            "briefdescription" => {
+                                hd_brief = collect_text(parser, name)?;
+                            }
+                            "detaileddescription" => {
+                                hd_desc = collect_text(parser, name)?;
+                            }
+                            _ => {}
+                        }
+                    },
+                    XmlEvent::EndElement {name, ..} if name.local_name == "memberdef" => {
+                        return Ok(HashDefine{hd_name, hd_init, hd_brief, hd_desc});
+                    },
+                    XmlEvent::Characters(_s) => {
+                    },
+                    XmlEvent::EndDocument => return Ok(HashDefine{hd_name, hd_init, hd_brief, hd_desc}),
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Read a <memberdef> the way read_file does, after its start element
+    fn read_memberdef(xml: &[u8]) -> (Vec<FunctionInfo>, BTreeMap<String, StructureInfo>)
+    {
+        let mut parser = XmlReader::new(xml);
+        let mut functions = Vec::new();
+        let mut structures = BTreeMap::new();
+        parser.next_event().unwrap();
+        collect_function_info(&mut parser, &mut functions, &mut structures).unwrap();
+        (functions, structures)
+    }
+
+    #[test]
+    fn function_with_struct_param()
+    {
+        let (functions, structures) = read_memberdef(br#"<memberdef kind="function" id="test_8h_1a1">
+            <type>int</type>
+            <definition>int test_open</definition>
+            <argsstring>(struct test_info *info, int flags)</argsstring>
+            <name>test_open</name>
+            <param><type>struct <ref refid="structtest__info" kindref="compound">test_info</ref> *</type><declname>info</declname></param>
+            <param><type>int</type><declname>flags</declname></param>
+            <briefdescription><para>Open a test</para></briefdescription>
+            <detaileddescription>
+              <para>Opens it.</para>
+              <para><parameterlist kind="param">
+                <parameteritem><parameternamelist><parametername>flags</parametername></parameternamelist>
+                  <parameterdescription><para>How to open it</para></parameterdescription></parameteritem>
+              </parameterlist><simplesect kind="return"><para>0 on success</para></simplesect></para>
+            </detaileddescription>
+            <location file="test.h" line="42"/>
+          </memberdef>"#);
+
+        assert_eq!(functions.len(), 1);
+        let f = &functions[0];
+        assert_eq!(f.fn_name, "test_open");
+        assert_eq!(f.fn_type, "int");
+        assert_eq!(f.fn_argsstring, "(struct test_info *info, int flags)");
+        assert_eq!(f.fn_brief.trim(), "Open a test");
+        assert!(f.fn_detail.contains("Opens it."));
+        assert!(f.fn_returnval.contains("0 on success"));
+        assert_eq!(f.fn_file, "test.h");
+        assert_eq!(f.fn_line, 42);
+
+        assert_eq!(f.fn_args.len(), 2);
+        assert_eq!(f.fn_args[0].par_name, "info");
+        assert_eq!(f.fn_args[0].par_refid.as_deref(), Some("structtest__info"));
+        assert_eq!(f.fn_args[1].par_name, "flags");
+        assert_eq!(f.fn_args[1].par_type, "int");
+        assert!(f.fn_args[1].par_desc.contains("How to open it"));
+
+        assert_eq!(f.fn_refids, vec!["structtest__info".to_string()]);
+        // Only a placeholder until the structure's own file is read
+        assert_eq!(structures.len(), 1);
+        assert!(matches!(structures["structtest__info"].str_type, StructureType::Struct));
+        assert!(structures["structtest__info"].str_members.is_empty());
+    }
+
+    #[test]
+    fn member_ref_param_has_no_placeholder()
+    {
+        let (functions, structures) = read_memberdef(br#"<memberdef kind="function" id="test_8h_1a7">
+            <type>int</type>
+            <name>test_watch</name>
+            <param><type><ref refid="test_8h_1a2" kindref="member">test_cb_t</ref></type><declname>cb</declname></param>
+            <briefdescription></briefdescription><detaileddescription></detaileddescription>
+          </memberdef>"#);
+
+        assert_eq!(functions[0].fn_args[0].par_refid.as_deref(), Some("test_8h_1a2"));
+        assert!(structures.is_empty());
+    }
+
+    #[test]
+    fn callback_typedef_args()
+    {
+        let (functions, _) = read_memberdef(br#"<memberdef kind="typedef" id="test_8h_1a2">
+            <type>void(*</type>
+            <definition>typedef void(* test_cb_t) (int fd, void *data)</definition>
+            <argsstring>)(int fd, void *data)</argsstring>
+            <name>test_cb_t</name>
+            <briefdescription><para>Called when it is ready</para></briefdescription>
+            <detaileddescription></detaileddescription>
+          </memberdef>"#);
+
+        let f = &functions[0];
+        assert_eq!(f.fn_name, "test_cb_t");
+        let names: Vec<&str> = f.fn_args.iter().map(|p| p.par_name.as_str()).collect();
+        assert_eq!(names, ["fd", "data"]);
+    }
+
+    #[test]
+    fn define()
+    {
+        let mut parser = XmlReader::new(&br#"<memberdef kind="define" id="test_8h_1a3">
+            <name>TEST_MAX</name>
+            <initializer>16</initializer>
+            <briefdescription><para>Most tests at once</para></briefdescription>
+            <detaileddescription></detaileddescription>
+          </memberdef>"#[..]);
+        parser.next_event().unwrap();
+        let define = collect_define(&mut parser).unwrap();
+        assert_eq!(define.hd_name, "TEST_MAX");
+        assert_eq!(define.hd_init, "16");
+        assert_eq!(define.hd_brief.trim(), "Most tests at once");
+    }
+
+    #[test]
+    fn text_param_refs()
+    {
+        let mut unknown = Vec::new();
+        let text = format!("Close {CODE_FONT}fd\\fR with \\fIflags\\fR, not {CODE_FONT}FD\\fR, \\fIbogus\\fR or {CODE_FONT}NULL\\fR.");
+        let marked = mark_text_param_refs(&text, &["fd", "flags"], &mut unknown);
+        assert_eq!(marked, format!("Close \\fIfd\\fR with \\fIflags\\fR, not {CODE_FONT}FD\\fR, \\fIbogus\\fR or {CODE_FONT}NULL\\fR."));
+        assert_eq!(unknown, ["FD", "bogus"]);
+    }
+
+    #[test]
+    fn args()
+    {
+        let p = parse_arg("const char *name");
+        assert_eq!((p.par_type.as_str(), p.par_name.as_str()), ("const char *", "name"));
+        let p = parse_arg("int flags = 0");
+        assert_eq!((p.par_type.as_str(), p.par_name.as_str(), p.par_defval.as_str()), ("int", "flags", "0"));
+        let p = parse_arg("char buf[64]");
+        assert_eq!((p.par_name.as_str(), p.par_args.as_str()), ("buf", "[64]"));
+        let p = parse_arg("void (*cb)(int)");
+        assert_eq!((p.par_name.as_str(), p.par_args.as_str()), ("cb", ")(int)"));
+        // Just a type
+        let p = parse_arg("int");
+        assert_eq!((p.par_type.as_str(), p.par_name.as_str()), ("int", ""));
+    }
+
+    #[test]
+    fn args_string()
+    {
+        let names = |s: &str| parse_args_string(s).into_iter().map(|p| p.par_name).collect::<Vec<String>>();
+        assert_eq!(names("(int a, void (*cb)(int, char), char *b)"), ["a", "cb", "b"]);
+        assert_eq!(names(")(void *data, int event)"), ["data", "event"]);
+        assert!(names("(void)").is_empty());
+    }
+}
@@ -0,0 +1,469 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Reads C files with kernel-doc comments (the format the Linux kernel uses,
+// see Documentation/doc-guide/kernel-doc.rst there) instead of doxygen's XML.
+// Functions, structures, unions, enums and DOC: sections are read into the
+// same model as the XML front-end makes.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+use crate::model::*;
+use super::attributes::extract_attributes;
+use super::function::{mark_param_refs, parse_arg, parse_args_string, warn_unknown_param};
+
+// One /** ... */ comment, split into its parts
+#[derive(Default)]
+struct KernelDocComment {
+    // The first line, eg "foo() - Do a thing" or "struct foo - A thing"
+    title: String,
+    brief: String,
+    // @name: descriptions, in order
+    params: Vec<(String, String)>,
+    description: String,
+    returns: String,
+    notes: String,
+}
+
+// doxygen-style refid for a structure or enum, so the names match the XML front-end
+fn structure_refid(kind: &str, name: &str) -> String
+{
+    format!("{}{}", kind, name.replace('_', "__"))
+}
+
+// Turn kernel-doc inline markup into troff: %CONST, @param, &struct foo and func()
+fn format_text(text: &str) -> String
+{
+    let mut formatted = Vec::new();
+    for word in text.split(' ') {
+        let (word, punct) = match word.find(|c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '%' && c != '@' && c != '&') {
+            Some(p) if p > 0 => word.split_at(p),
+            _ => (word, ""),
+        };
+        let w = if let Some(c) = word.strip_prefix('%') {
+            format!("\\fB{c}\\fR")
+        } else if let Some(p) = word.strip_prefix('@') {
+            format!("\\fI{p}\\fR")
+        } else if let Some(s) = word.strip_prefix('&') {
+            s.to_string()
+        } else {
+            word.to_string()
+        };
+        formatted.push(w + punct);
+    }
+    formatted.join(" ")
+}
+
+// Add a line of text to a section. Blank lines separate paragraphs, which are
+// kept on separate lines as the XML front-end does.
+fn add_line(section: &mut String, line: &str)
+{
+    if line.is_empty() {
+        if !section.is_empty() && !section.ends_with('\n') {
+            section.push('\n');
+        }
+        return;
+    }
+    if !section.is_empty() && !section.ends_with('\n') {
+        section.push(' ');
+    }
+    section.push_str(&format_text(line));
+}
+
+// Split up the text of a comment (without the /** and */)
+fn parse_comment(text: &str) -> KernelDocComment
+{
+    #[derive(PartialEq)]
+    enum Section { Description, Param, Returns, Notes }
+
+    let mut comment = KernelDocComment::default();
+    let mut section = Section::Description;
+    let mut lines = text.lines().map(|l| {
+        let l = l.trim_start();
+        let l = l.strip_prefix('*').unwrap_or(l);
+        l.strip_prefix(' ').unwrap_or(l).trim_end()
+    }).skip_while(|l| l.is_empty());
+
+    if let Some(title) = lines.next() {
+        match title.split_once(" - ") {
+            Some((t, b)) => {
+                comment.title = t.trim().to_string();
+                comment.brief = format_text(b.trim());
+            }
+            None => comment.title = title.trim().to_string(),
+        }
+    }
+
+    for line in lines {
+        if let Some(p) = line.strip_prefix('@') {
+            if let Some((name, desc)) = p.split_once(':') {
+                comment.params.push((name.trim().to_string(), format_text(desc.trim())));
+                section = Section::Param;
+                continue;
+            }
+        }
+        if let Some((heading, rest)) = line.split_once(':') {
+            let new_section = match heading {
+                "Return" | "Returns" => Some(Section::Returns),
+                "Note" | "Notes" => Some(Section::Notes),
+                "Description" | "Context" => Some(Section::Description),
+                _ => None,
+            };
+            if let Some(s) = new_section {
+                section = s;
+                // Context is kept as a paragraph of the description
+                let rest = if heading == "Context" { line } else { rest.trim() };
+                match section {
+                    Section::Returns => add_line(&mut comment.returns, rest),
+                    Section::Notes => add_line(&mut comment.notes, rest),
+                    _ => add_line(&mut comment.description, rest),
+                }
+                continue;
+            }
+        }
+
+        match section {
+            Section::Param => {
+                // Parameter descriptions carry on until a blank line
+                if line.is_empty() {
+                    section = Section::Description;
+                } else if let Some((_, desc)) = comment.params.last_mut() {
+                    add_line(desc, line.trim());
+                }
+            }
+            Section::Returns => add_line(&mut comment.returns, line),
+            Section::Notes => add_line(&mut comment.notes, line),
+            Section::Description => add_line(&mut comment.description, line),
+        }
+    }
+    comment.description = comment.description.trim().to_string();
+    comment.returns = comment.returns.trim().to_string();
+    comment.notes = comment.notes.trim().to_string();
+    comment
+}
+
+// The declaration after a comment: up to the ; (or { of a body) for a function,
+// up to the end of the line (and any continuations) for a #define, or the whole
+// body for a structure, union or enum. Comments are left out (including any
+// for the members) and whitespace is squashed to single spaces.
+fn declaration_after(text: &str, with_body: bool) -> String
+{
+    let text = text.trim_start();
+    let is_define = text.starts_with("#define");
+    let mut decl = String::new();
+    let mut depth = 0;
+    let mut prev = ' ';
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            let mut last = ' ';
+            for c in chars.by_ref() {
+                if last == '*' && c == '/' {
+                    break;
+                }
+                last = c;
+            }
+            decl.push(' ');
+            prev = ' ';
+            continue;
+        }
+        if c == '/' && chars.peek() == Some(&'/') {
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            if is_define {
+                break;
+            }
+            decl.push(' ');
+            prev = ' ';
+            continue;
+        }
+
+        if is_define {
+            if c == '\n' && prev != '\\' {
+                break;
+            }
+        } else {
+            match c {
+                '{' if !with_body && depth == 0 => break,
+                '(' | '{' => depth += 1,
+                ')' | '}' => depth -= 1,
+                ';' if depth == 0 => break,
+                _ => {}
+            }
+        }
+        decl.push(c);
+        prev = c;
+    }
+    decl.replace("\\\n", " ").split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// The text between the outermost { and }
+fn body_of(decl: &str) -> &str
+{
+    match (decl.find('{'), decl.rfind('}')) {
+        (Some(s), Some(e)) if s < e => &decl[s+1..e],
+        _ => "",
+    }
+}
+
+// Split a structure body into members, or an enum body into values
+fn split_body(body: &str, separator: char) -> Vec<&str>
+{
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in body.char_indices() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                items.push(body[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(body[start..].trim());
+    items.retain(|i| !i.is_empty());
+    items
+}
+
+fn make_function(comment: &KernelDocComment, decl: &str) -> Option<FunctionInfo>
+{
+    let mut function = FunctionInfo::new();
+    function.fn_brief = comment.brief.clone();
+    function.fn_detail = comment.description.clone();
+    function.fn_returnval = comment.returns.clone();
+    function.fn_note = comment.notes.clone();
+
+    let open = decl.find('(')?;
+    let mut depth = 0;
+    let close = open + decl[open..].find(|c| {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        depth == 0
+    })?;
+    let before = decl[..open].trim_end();
+    let is_macro = before.starts_with("#define");
+    let before = before.strip_prefix("#define").unwrap_or(before).trim();
+    let name_start = before.rfind(|c: char| !c.is_ascii_alphanumeric() && c != '_').map_or(0, |p| p + 1);
+    function.fn_name = before[name_start..].to_string();
+    function.fn_type = before[..name_start].trim().to_string();
+    function.fn_def = before.to_string();
+    function.fn_static = before.split_whitespace().any(|w| w == "static");
+    function.fn_inline = before.split_whitespace().any(|w| w == "inline" || w == "__always_inline");
+    function.fn_argsstring = decl[open..=close].to_string();
+    function.fn_args = parse_args_string(&function.fn_argsstring);
+    // Macro arguments are just names
+    if is_macro {
+        for p in &mut function.fn_args {
+            if p.par_name.is_empty() && p.par_type != "..." {
+                p.par_name = std::mem::take(&mut p.par_type);
+            }
+        }
+    }
+
+    for (name, desc) in &comment.params {
+        // kernel-doc uses @...: for variadic arguments
+        let name = if name == "..." || name.ends_with("...") { "..." } else { name.as_str() };
+        match function.fn_args.iter_mut().find(|p| p.par_name == name || (name == "..." && p.par_type == "...")) {
+            Some(p) => p.par_desc = desc.clone(),
+            None => warn_unknown_param(&function.fn_name, name, &function.fn_args),
+        }
+    }
+    extract_attributes(&mut function);
+    mark_param_refs(&mut function);
+    Some(function)
+}
+
+fn make_structure(comment: &KernelDocComment, str_type: StructureType, name: &str, decl: &str) -> StructureInfo
+{
+    let mut sinfo = StructureInfo::new();
+    sinfo.str_name = name.to_string();
+    sinfo.str_brief = comment.brief.clone();
+    sinfo.str_description = comment.description.clone();
+
+    let body = body_of(decl);
+    let members = match str_type {
+        StructureType::Enum => split_body(body, ','),
+        _ => split_body(body, ';'),
+    };
+    for m in members {
+        let mut param = match str_type {
+            StructureType::Enum => {
+                let name = m.split('=').next().unwrap_or(m).trim();
+                FnParam {par_name: name.to_string(), par_type: String::new(), par_refid: None,
+                         par_args: String::new(), par_desc: String::new(), par_brief: String::new(),
+                         par_defval: String::new(), par_direction: String::new()}
+            }
+            // Nested structures and unions are shown as just their type and name
+            _ if m.contains('}') => {
+                let (inner, after) = m.split_at(m.rfind('}').unwrap_or(0) + 1);
+                let mut p = parse_arg(&format!("{} {}", inner.split('{').next().unwrap_or("").trim(), after.trim()));
+                p.par_type = p.par_type.trim().to_string();
+                p
+            }
+            _ => parse_arg(m),
+        };
+        if let Some((_, desc)) = comment.params.iter().find(|(n, _)| *n == param.par_name) {
+            param.par_desc = desc.clone();
+        }
+        sinfo.str_members.push(param);
+    }
+    sinfo.str_type = str_type;
+    sinfo
+}
+
+// Read a C file with kernel-doc comments. If headerfile is "unknown.h" it is
+// filled in from the file name. As with the XML files the last 'function' is
+// the general page for the header, which gets the DOC: sections.
+pub fn read_header_kerneldoc(header: &str,
+                             headerfile: &mut String) -> Result<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>), Error>
+{
+    let text = match fs::read_to_string(header) {
+        Ok(t) => t,
+        Err(e) => return Err(Error::new(e.kind(), format!("Cannot read {header}: {e}"))),
+    };
+
+    if *headerfile == "unknown.h" {
+        if let Some(n) = Path::new(header).file_name() {
+            *headerfile = n.to_string_lossy().to_string();
+        }
+    }
+
+    let mut functions = Vec::<FunctionInfo>::new();
+    let mut structures = BTreeMap::<String, StructureInfo>::new();
+    let mut general = FunctionInfo::new();
+    general.fn_name = headerfile.clone();
+
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("/**") {
+        let after_start = &rest[start+3..];
+        let end = match after_start.find("*/") {
+            Some(e) => e,
+            None => break,
+        };
+        let comment = parse_comment(&after_start[..end]);
+        rest = &after_start[end+2..];
+
+        let title_words: Vec<&str> = comment.title.split_whitespace().collect();
+        match title_words.as_slice() {
+            ["DOC:", ..] => {
+                general.fn_detail.push_str(&comment.description);
+                general.fn_detail.push('\n');
+            }
+            [kind @ ("struct" | "union" | "enum"), name] => {
+                let decl = declaration_after(rest, true);
+                let (refid_kind, str_type) = match *kind {
+                    "enum" => ("enum", StructureType::Enum),
+                    _ => ("struct", StructureType::Struct),
+                };
+                let sinfo = make_structure(&comment, str_type, name, &decl);
+                structures.insert(structure_refid(refid_kind, name), sinfo);
+            }
+            ["define", name] => {
+                let decl = declaration_after(rest, false);
+                let hd_init = decl.strip_prefix("#define").unwrap_or(&decl).trim()
+                    .strip_prefix(name).unwrap_or("").trim().to_string();
+                general.fn_defines.push(HashDefine {hd_name: name.to_string(), hd_init,
+                                                    hd_brief: comment.brief.clone(), hd_desc: comment.description.clone()});
+            }
+            ["typedef", ..] => {} // Not printed, like the XML front-end
+            [_] => {
+                let decl = declaration_after(rest, false);
+                if let Some(f) = make_function(&comment, &decl) {
+                    functions.push(f);
+                }
+            }
+            _ => {}
+        }
+    }
+    general.fn_detail = general.fn_detail.trim().to_string();
+
+    // Link the functions to the structures they use, now they have all been read
+    for f in &mut functions {
+        for p in &mut f.fn_args {
+            let words: Vec<&str> = p.par_type.split(|c: char| !c.is_ascii_alphanumeric() && c != '_').filter(|w| !w.is_empty()).collect();
+            for pair in words.windows(2) {
+                let kind = if pair[0] == "union" { "struct" } else { pair[0] };
+                let refid = structure_refid(kind, pair[1]);
+                if structures.contains_key(&refid) {
+                    if !f.fn_refids.contains(&refid) {
+                        f.fn_refids.push(refid.clone());
+                    }
+                    p.par_refid = Some(refid);
+                }
+            }
+        }
+    }
+
+    functions.push(general);
+    Ok((functions, structures))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comment()
+    {
+        let comment = parse_comment("
+ * foo_open() - Open a %FOO
+ * @name: the name
+ *   to open
+ * @flags: how
+ *
+ * Opens @name.
+ *
+ * Context: Any context.
+ * Return: 0 or -errno
+ ");
+        assert_eq!(comment.title, "foo_open()");
+        assert_eq!(comment.brief, "Open a \\fBFOO\\fR");
+        assert_eq!(comment.params, [("name".to_string(), "the name to open".to_string()),
+                                    ("flags".to_string(), "how".to_string())]);
+        assert_eq!(comment.description, "Opens \\fIname\\fR.\nContext: Any context.");
+        assert_eq!(comment.returns, "0 or -errno");
+    }
+
+    #[test]
+    fn declarations()
+    {
+        assert_eq!(declaration_after("\nint foo_open(const char *name, /* the name */\n    int flags)\n{\n}\n", false),
+                   "int foo_open(const char *name, int flags)");
+        assert_eq!(declaration_after("\n#define FOO_MAX(a) \\\n    ((a) + 1)\nint x;\n", false),
+                   "#define FOO_MAX(a) ((a) + 1)");
+        let decl = declaration_after("\nstruct foo {\n    int fd; // the fd\n    char *name;\n};\n", true);
+        assert_eq!(split_body(body_of(&decl), ';'), ["int fd", "char *name"]);
+    }
+
+    #[test]
+    fn function()
+    {
+        let comment = parse_comment(" * foo_close() - Close it\n * @fd: what to close\n * Return: 0\n");
+        let f = make_function(&comment, "static inline int foo_close(int fd)").unwrap();
+        assert_eq!(f.fn_name, "foo_close");
+        assert_eq!(f.fn_type, "static inline int");
+        assert!(f.fn_static && f.fn_inline);
+        assert_eq!(f.fn_args[0].par_name, "fd");
+        assert_eq!(f.fn_args[0].par_desc, "what to close");
+        assert_eq!(f.fn_returnval, "0");
+    }
+}
@@ -0,0 +1,180 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --language. doxygen has no C of its own, it reads C headers as C++ so their
+// compounds say language="C++" too. Compounds in other languages (eg the
+// Python bindings documented into the same XML directory) are skipped. With c
+// the C++ things that doxygen finds in headers that are also C++ includes are
+// taken out: members of namespaces and classes, overloaded operators, templates
+// and references, and extern "C", the global :: and default arguments are
+// taken off the rest.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use crate::model::*;
+use super::{get_attr, new_parser, XmlEvent};
+
+// Read the language of the compound in a header's XML file, eg "C++"
+pub fn read_compound_language(xml_dir: &str, in_file: &str) -> Result<String, Error>
+{
+    let main_xml_file = format!("{xml_dir}/{in_file}");
+    let f = match File::open(&main_xml_file) {
+        Ok(f) => f,
+        Err(e) => return Err(Error::new(e.kind(), format!("Cannot open XML file {main_xml_file}: {e}"))),
+    };
+    for er in new_parser(f) {
+        match er {
+            Ok(e) => {
+                if let XmlEvent::StartElement {name, ..} = &e {
+                    if name.local_name == "compounddef" {
+                        return Ok(get_attr(&e, "language"));
+                    }
+                }
+            }
+            Err(e) => return Err(Error::new(ErrorKind::InvalidData, format!("Error reading XML for {main_xml_file}: {e}"))),
+        }
+    }
+    Ok(String::new())
+}
+
+// Whether a compound's language is C or C++, which doxygen both calls C++.
+// Old XML doesn't say.
+pub fn is_c_language(language: &str) -> bool
+{
+    language.is_empty() || language == "C++"
+}
+
+fn is_ident(c: char) -> bool
+{
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// A name or type without extern "C" and the global :: (but not other qualifiers)
+fn c_name(s: &str) -> String
+{
+    let s = s.replace("extern \"C\"", "");
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s.as_str();
+    while let Some(p) = rest.find("::") {
+        out.push_str(&rest[..p]);
+        if out.ends_with(is_ident) {
+            out.push_str("::");
+        }
+        rest = &rest[p + 2..];
+    }
+    out.push_str(rest);
+    out.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Qualified names, eg std::string or ns::function
+fn is_qualified(s: &str) -> bool
+{
+    c_name(s).contains("::")
+}
+
+// Whether a function can only be C++, from its name, prototype and parameters
+fn is_cplusplus_function(f: &FunctionInfo) -> bool
+{
+    if is_qualified(&f.fn_name) || is_qualified(&f.fn_def) || f.fn_name.starts_with("operator") || f.fn_name.contains('<') {
+        return true;
+    }
+    if f.fn_type.split_whitespace().any(|w| w == "template" || w == "virtual" || w == "constexpr") {
+        return true;
+    }
+    // Methods' qualifiers after the parameters
+    let after = f.fn_argsstring.rsplit(')').next().unwrap_or("").trim();
+    if ["const", "noexcept", "override", "final", "=", "&"].iter().any(|q| after.starts_with(q)) {
+        return true;
+    }
+    f.fn_args.iter().any(|p| is_qualified(&p.par_type) || p.par_type.trim_end().ends_with('&'))
+}
+
+// C has no default arguments, headers for both only give them to C++
+fn normalize_param(p: &mut FnParam)
+{
+    p.par_type = c_name(&p.par_type);
+    p.par_defval.clear();
+}
+
+// Take the C++ out of the model for --language c
+pub fn normalize_c(functions: &mut Vec<FunctionInfo>, structures: &mut BTreeMap<String, StructureInfo>)
+{
+    functions.retain(|f| !is_cplusplus_function(f));
+    for f in functions.iter_mut() {
+        f.fn_name = c_name(&f.fn_name);
+        f.fn_def = c_name(&f.fn_def);
+        f.fn_type = c_name(&f.fn_type);
+        f.fn_args.iter_mut().for_each(normalize_param);
+        // The general page's, eg #define EXTERN_C extern "C"
+        f.fn_defines.retain(|d| !d.hd_init.contains("extern \"C\"") && d.hd_name != "__cplusplus");
+    }
+
+    structures.retain(|_, s| !is_qualified(&s.str_name));
+    for s in structures.values_mut() {
+        s.str_name = c_name(&s.str_name);
+        s.str_members.iter_mut().for_each(normalize_param);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::function::parse_args_string;
+
+    fn function(name: &str, def: &str, argsstring: &str) -> FunctionInfo
+    {
+        let mut f = FunctionInfo::new();
+        f.fn_name = name.to_string();
+        f.fn_def = def.to_string();
+        f.fn_type = def.rsplit_once(' ').map_or("", |(t, _)| t).to_string();
+        f.fn_argsstring = argsstring.to_string();
+        f.fn_args = parse_args_string(argsstring);
+        f
+    }
+
+    #[test]
+    fn cplusplus_taken_out()
+    {
+        let mut general = FunctionInfo::new();
+        general.fn_name = "foo.h".to_string();
+        for (name, init) in [("EXTERN_C", "extern \"C\""), ("__cplusplus", ""), ("FOO_MAX", "16")] {
+            general.fn_defines.push(HashDefine {hd_name: name.to_string(), hd_init: init.to_string(),
+                                                hd_brief: String::new(), hd_desc: String::new()});
+        }
+        let mut functions = vec![
+            function("foo_open", "extern \"C\" int ::foo_open", "(::size_t len, int flags = 0)"),
+            function("ns::foo_open", "int ns::foo_open", "(int flags)"),
+            function("operator==", "bool operator==", "(const foo &a, const foo &b)"),
+            function("size", "int size", "() const"),
+            function("foo_name", "int foo_name", "(std::string name)"),
+            general,
+        ];
+        let mut structures = BTreeMap::new();
+        for name in ["foo_info", "ns::bar"] {
+            let mut s = StructureInfo::new();
+            s.str_name = name.to_string();
+            structures.insert(format!("struct{name}"), s);
+        }
+
+        normalize_c(&mut functions, &mut structures);
+
+        let names: Vec<&str> = functions.iter().map(|f| f.fn_name.as_str()).collect();
+        assert_eq!(names, ["foo_open", "foo.h"]);
+        let f = &functions[0];
+        assert_eq!(f.fn_def, "int foo_open");
+        assert_eq!(f.fn_type, "int");
+        assert_eq!(f.fn_args[0].par_type, "size_t");
+        assert!(f.fn_args[1].par_defval.is_empty());
+        let defines: Vec<&str> = functions[1].fn_defines.iter().map(|d| d.hd_name.as_str()).collect();
+        assert_eq!(defines, ["FOO_MAX"]);
+        let structs: Vec<&str> = structures.values().map(|s| s.str_name.as_str()).collect();
+        assert_eq!(structs, ["foo_info"]);
+    }
+}
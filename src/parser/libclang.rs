@@ -0,0 +1,299 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Reads a C header file directly with libclang instead of doxygen's XML.
+// Only the things doxygen2man prints are collected: function prototypes and
+// their doc comments, the structures & enums they use, and #defines.
+
+use std::collections::BTreeMap;
+use std::io::Error;
+use std::path::Path;
+use clang::{Clang, Entity, EntityKind, Index, StorageClass, Type, TypeKind};
+use clang::documentation::{CommentChild, ParameterDirection};
+
+use crate::model::*;
+use super::function::mark_param_refs;
+use super::text::CODE_FONT;
+
+// Flatten the text of a doc comment, paragraphs are separated by newlines and
+// verbatim blocks (eg \code) are kept as they are, like the XML parser does
+fn comment_text(children: &[CommentChild]) -> String
+{
+    let mut text = String::new();
+    for c in children {
+        match c {
+            CommentChild::Text(s) => text.push_str(s),
+            CommentChild::InlineCommand(ic) => {
+                for a in &ic.arguments {
+                    text.push(' ');
+                    match ic.command.as_str() {
+                        "p" => text.push_str(&format!("\\fI{a}\\fR")),
+                        "c" => text.push_str(&format!("{CODE_FONT}{a}\\fR")),
+                        _ => text.push_str(a),
+                    }
+                }
+            }
+            CommentChild::Paragraph(p) => {
+                let para = comment_text(p);
+                if !para.trim().is_empty() {
+                    text.push_str(para.trim());
+                    text.push('\n');
+                }
+            }
+            CommentChild::VerbatimCommand(lines) => {
+                text.push_str("\n.nf\n");
+                for l in lines {
+                    text.push_str(l);
+                    text.push('\n');
+                }
+                text.push_str(".fi\n");
+            }
+            CommentChild::VerbatimLineCommand(l) => text.push_str(l),
+            _ => {}
+        }
+    }
+    text
+}
+
+// Brief and detailed description from a comment's paragraphs. libclang works out
+// the brief (the \brief paragraph or the first one), the rest is the detail
+fn entity_descriptions(entity: &Entity) -> (String, String)
+{
+    let brief = entity.get_comment_brief().unwrap_or_default();
+    let mut detail = String::new();
+
+    if let Some(comment) = entity.get_parsed_comment() {
+        let mut seen_brief = false;
+        for c in comment.get_children() {
+            if let CommentChild::Paragraph(p) = &c {
+                let text = comment_text(p);
+                if !seen_brief && text.split_whitespace().eq(brief.split_whitespace()) {
+                    seen_brief = true;
+                    continue;
+                }
+                detail.push_str(text.trim());
+                if !text.trim().is_empty() {
+                    detail.push('\n');
+                }
+            }
+            if let CommentChild::VerbatimCommand(_) = &c {
+                detail.push_str(&comment_text(&[c]));
+            }
+        }
+    }
+    (brief.trim().to_string(), detail.trim().to_string())
+}
+
+// doxygen-style refid for a structure or enum, so the names match the XML front-end
+fn structure_refid(kind: &str, name: &str) -> String
+{
+    format!("{}{}", kind, name.replace('_', "__"))
+}
+
+// If a type is (a pointer to) a struct or enum declared in the header, read it in
+// and return its refid
+fn collect_type_structure(param_type: Type, structures: &mut BTreeMap<String, StructureInfo>) -> Option<String>
+{
+    let mut t = param_type.get_canonical_type();
+    while t.get_kind() == TypeKind::Pointer {
+        t = t.get_pointee_type()?.get_canonical_type();
+    }
+    let decl = t.get_declaration()?;
+    let decl = decl.get_definition().unwrap_or(decl);
+    if !decl.is_in_main_file() {
+        return None;
+    }
+    let name = decl.get_name()?;
+
+    let (kind, str_type, member_kind) = match decl.get_kind() {
+        EntityKind::StructDecl => ("struct", StructureType::Struct, EntityKind::FieldDecl),
+        EntityKind::EnumDecl => ("enum", StructureType::Enum, EntityKind::EnumConstantDecl),
+        _ => return None,
+    };
+    let refid = structure_refid(kind, &name);
+    if structures.contains_key(&refid) {
+        return Some(refid);
+    }
+
+    let (str_brief, str_description) = entity_descriptions(&decl);
+    let mut sinfo = StructureInfo {str_type, str_name: name, str_brief, str_description, str_members: Vec::<FnParam>::new()};
+
+    for m in decl.get_children().iter().filter(|m| m.get_kind() == member_kind) {
+        let mut par_type = match m.get_kind() {
+            EntityKind::FieldDecl => m.get_type().map(|t| t.get_display_name()).unwrap_or_default(),
+            _ => String::new(),
+        };
+        // Arrays are printed the way doxygen has them, with the size after the name
+        let mut par_args = String::new();
+        if let Some(i) = par_type.find(" [") {
+            par_args = par_type[i+1..].to_string();
+            par_type.truncate(i);
+        }
+        let (par_brief, par_desc) = entity_descriptions(m);
+        sinfo.str_members.push(FnParam {par_name: m.get_name().unwrap_or_default(), par_type, par_refid: None,
+                                        par_args, par_desc, par_brief, par_defval: String::new(), par_direction: String::new()});
+    }
+    structures.insert(refid.clone(), sinfo);
+    Some(refid)
+}
+
+fn collect_function(entity: &Entity, structures: &mut BTreeMap<String, StructureInfo>) -> FunctionInfo
+{
+    let mut function = FunctionInfo::new();
+
+    function.fn_name = entity.get_name().unwrap_or_default();
+    function.fn_type = entity.get_result_type().map(|t| t.get_display_name()).unwrap_or_default();
+    function.fn_def = format!("{} {}", function.fn_type, function.fn_name);
+    function.fn_static = entity.get_storage_class() == Some(StorageClass::Static);
+    function.fn_inline = entity.is_inline_function();
+    if let Some(loc) = entity.get_location().map(|l| l.get_file_location()) {
+        function.fn_file = loc.file.map(|f| f.get_path().to_string_lossy().to_string()).unwrap_or_default();
+        function.fn_line = loc.line;
+    }
+
+    let mut args = Vec::<String>::new();
+    for a in entity.get_arguments().unwrap_or_default() {
+        let par_type = a.get_type().map(|t| t.get_display_name()).unwrap_or_default();
+        let par_name = a.get_name().unwrap_or_default();
+        let par_refid = a.get_type().and_then(|t| collect_type_structure(t, structures));
+        if let Some(r) = &par_refid {
+            if !function.fn_refids.contains(r) {
+                function.fn_refids.push(r.clone());
+            }
+        }
+        args.push(format!("{par_type} {par_name}"));
+        function.fn_args.push(FnParam {par_name, par_type, par_refid, par_args: String::new(),
+                                       par_desc: String::new(), par_brief: String::new(), par_defval: String::new(), par_direction: String::new()});
+    }
+    if entity.is_variadic() {
+        args.push("...".to_string());
+        function.fn_args.push(FnParam {par_name: String::new(), par_type: "...".to_string(), par_refid: None,
+                                       par_args: String::new(), par_desc: String::new(), par_brief: String::new(),
+                                       par_defval: String::new(), par_direction: String::new()});
+    }
+    function.fn_argsstring = format!("({})", args.join(", "));
+
+    let (brief, detail) = entity_descriptions(entity);
+    function.fn_brief = brief;
+    function.fn_detail = detail;
+
+    if let Some(comment) = entity.get_parsed_comment() {
+        for c in comment.get_children() {
+            match c {
+                CommentChild::ParamCommand(pc) => {
+                    let desc = comment_text(&pc.children).trim().to_string();
+                    let arg = match pc.index {
+                        Some(i) => function.fn_args.get_mut(i),
+                        None => function.fn_args.iter_mut().find(|a| a.par_name == pc.parameter),
+                    };
+                    if let Some(a) = arg {
+                        a.par_desc = desc;
+                        a.par_direction = match pc.direction {
+                            Some(ParameterDirection::In) => "in",
+                            Some(ParameterDirection::Out) => "out",
+                            Some(ParameterDirection::InOut) => "inout",
+                            None => "",
+                        }.to_string();
+                    }
+                }
+                CommentChild::BlockCommand(bc) => {
+                    let text = comment_text(&bc.children).trim().to_string();
+                    match bc.command.as_str() {
+                        "return" | "returns" | "result" => function.fn_returnval = text,
+                        "retval" => {
+                            let (ret_name, ret_desc) = match bc.arguments.first() {
+                                Some(a) => (a.clone(), text),
+                                None => {
+                                    let mut words = text.splitn(2, char::is_whitespace);
+                                    (words.next().unwrap_or_default().to_string(),
+                                     words.next().unwrap_or_default().trim().to_string())
+                                }
+                            };
+                            function.fn_retvals.push(ReturnVal {ret_name, ret_desc});
+                        }
+                        "note" => function.fn_note = text,
+                        "attention" | "remark" | "remarks" | "invariant" | "copyright" => {
+                            let ss_kind = bc.command.trim_end_matches('s').to_string();
+                            function.fn_simplesects.push(SimpleSect {ss_kind, ss_text: text});
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    mark_param_refs(&mut function);
+    function
+}
+
+// A #define, leaving out include guards
+fn collect_macro(entity: &Entity) -> Option<HashDefine>
+{
+    let hd_name = entity.get_name()?;
+    let tokens = entity.get_range()?.tokenize();
+    let hd_init = tokens.iter().skip(1).map(|t| t.get_spelling()).collect::<Vec<String>>().join(" ");
+    if hd_init.is_empty() && (hd_name.ends_with("_H") || hd_name.ends_with("_H_")) {
+        return None;
+    }
+    Some(HashDefine {hd_name, hd_init, hd_brief: String::new(), hd_desc: String::new()})
+}
+
+// Read a header file with libclang. clang_args are passed to the compiler (eg -I
+// options). If headerfile is "unknown.h" it is filled in from the file name.
+// As with the XML files the last 'function' is the general page for the header.
+pub fn read_header_clang(header: &str,
+                         clang_args: &[String],
+                         headerfile: &mut String) -> Result<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>), Error>
+{
+    let clang = Clang::new().map_err(|e| Error::other(format!("Cannot load libclang: {e}")))?;
+    let index = Index::new(&clang, false, true);
+    let tu = match index.parser(header)
+        .arguments(clang_args)
+        .detailed_preprocessing_record(true)
+        .skip_function_bodies(true)
+        .parse() {
+            Ok(tu) => tu,
+            Err(e) => return Err(Error::other(format!("Error parsing {header}: {e}"))),
+        };
+
+    if *headerfile == "unknown.h" {
+        if let Some(n) = Path::new(header).file_name() {
+            *headerfile = n.to_string_lossy().to_string();
+        }
+    }
+
+    let mut functions = Vec::<FunctionInfo>::new();
+    let mut structures = BTreeMap::<String, StructureInfo>::new();
+    let mut general = FunctionInfo::new();
+    general.fn_name = headerfile.clone();
+
+    for e in tu.get_entity().get_children() {
+        if !e.is_in_main_file() {
+            continue;
+        }
+        match e.get_kind() {
+            EntityKind::FunctionDecl => {
+                // Only the first (usually the only) declaration of a function
+                let name = e.get_name().unwrap_or_default();
+                if !functions.iter().any(|f| f.fn_name == name) {
+                    functions.push(collect_function(&e, &mut structures));
+                }
+            }
+            EntityKind::MacroDefinition => {
+                if let Some(d) = collect_macro(&e) {
+                    general.fn_defines.push(d);
+                }
+            }
+            _ => {}
+        }
+    }
+    functions.push(general);
+    Ok((functions, structures))
+}
@@ -0,0 +1,404 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Reads the doxygen XML files into the model
+
+mod attributes;
+mod cond;
+mod function;
+mod kerneldoc;
+mod language;
+#[cfg(feature = "libclang")]
+mod libclang;
+mod page;
+mod reader;
+mod structure;
+mod text;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::fmt::Write as fmtwrite;
+use std::io::{BufReader, BufRead, Error, ErrorKind, Read};
+
+use crate::model::*;
+use function::{add_doc_refs, collect_define, collect_function_info, collect_detail_bits};
+use structure::{collect_enum, read_structures_files};
+use text::{collect_text, collect_text_into, parse_standard_elements};
+pub use attributes::{extract_attributes, strip_attributes};
+pub use cond::{cond_enabled, read_cond_blocks, CondBlock, CondBlocks};
+pub use kerneldoc::read_header_kerneldoc;
+pub use language::{is_c_language, normalize_c, read_compound_language};
+#[cfg(feature = "libclang")]
+pub use libclang::read_header_clang;
+pub use page::{read_page_refids, read_page_xml};
+pub use reader::{XmlReader, XmlEvent, XmlName, XmlAttribute};
+pub use text::{split_highlight, strip_highlights, CODE_FONT, HIGHLIGHT_START};
+
+// Called for each function as soon as it has been read, in streaming mode.
+// Also gets the (unfilled) structures and enums seen so far.
+pub type FunctionCallback<'a> = &'a mut dyn FnMut(FunctionInfo, &BTreeMap<String, StructureInfo>);
+
+// doxygen's XML files can be big, read them in larger chunks than BufReader's default
+const XML_BUFFER_SIZE: usize = 64 * 1024;
+
+// All XML files are read with the same settings.
+// CDATA (which doxygen sometimes puts in programlistings & verbatim blocks)
+// is passed on as normal Characters so the collectors don't lose it.
+pub fn new_parser<R: Read>(f: R) -> XmlReader<BufReader<R>>
+{
+    XmlReader::new(BufReader::with_capacity(XML_BUFFER_SIZE, f))
+}
+
+// Does what it says on the tin
+pub fn get_attr(e: &XmlEvent, attrname: &str) -> String
+{
+    if let XmlEvent::StartElement {attributes,.. } = e {
+        for a in attributes {
+            if a.name.local_name == attrname {
+                return a.value.to_string();
+            }
+        }
+    }
+    String::new()
+}
+
+
+fn read_file<R: BufRead>(parser: &mut XmlReader<R>,
+                      headerfile: &mut String,
+                      functions: &mut Vec<FunctionInfo>,
+                      structures: &mut BTreeMap<String, StructureInfo>,
+                      mut on_function: Option<FunctionCallback>) -> Result<(), reader::Error>
+{
+    let mut defines = Vec::<HashDefine>::new();
+    let mut general = FunctionInfo::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "memberdef" => {
+                                if get_attr(&e, "kind") == "function" {
+
+                                    // Do function stuff
+                                    // go down the tree collecting info until we read EndElement
+                                    collect_function_info(parser,
+                                                          functions,
+                                                          structures)?;
+                                    if let Some(f) = functions.last_mut() {
+                                        f.fn_id = get_attr(&e, "id");
+                                        f.fn_static = get_attr(&e, "static") == "yes";
+                                        f.fn_inline = get_attr(&e, "inline") == "yes";
+                                    }
+
+                                    // Streaming - hand it straight over rather than keeping it
+                                    if let Some(cb) = on_function.as_mut() {
+                                        if let Some(f) = functions.pop() {
+                                            cb(f, structures);
+                                        }
+                                    }
+                                }
+                                // Collect #defines
+                                if get_attr(&e, "kind") == "define" {
+                                    let new_hd = collect_define(parser)?;
+                                    defines.push(new_hd);
+                                }
+                                // enums are in the main file, structs have their own
+                                if get_attr(&e, "kind") == "enum" {
+                                    let refid = get_attr(&e, "id");
+                                    if let Ok(si) = collect_enum(parser, StructureType::Enum) {
+                                        structures.insert(refid, si);
+                                    }
+				}
+                                // Documented callback (function pointer) typedefs are read like
+                                // functions, other typedefs are ignored for the moment
+                                if get_attr(&e, "kind") == "typedef" {
+                                    let mut typedefs = Vec::new();
+                                    collect_function_info(parser, &mut typedefs, structures)?;
+                                    if let Some(mut t) = typedefs.pop() {
+                                        if t.fn_argsstring.starts_with(")(") && !t.fn_brief.is_empty() {
+                                            let return_type = t.fn_type.trim_end_matches("(*").trim().to_string();
+                                            t.fn_def = format!("typedef {} (*{})", return_type, t.fn_name);
+                                            t.fn_type = return_type;
+                                            t.fn_typedef = true;
+                                            t.fn_id = get_attr(&e, "id");
+                                            functions.push(t);
+                                        }
+                                    }
+                                }
+                            }
+                            "compoundname" => {
+                                // This is the header filename (and the reason it is mutable)
+				if headerfile == "unknown.h" {
+                                    *headerfile = collect_text(parser, name)?;
+				}
+                            }
+
+                            // These are at the file (eg qblog.h) level
+                            "briefdescription" => {
+                                parser.refs.clear();
+                                collect_text_into(parser, name, &mut general.fn_brief)?;
+                                add_doc_refs(parser, &mut general);
+                            }
+                            "detaileddescription" => {
+                                parser.refs.clear();
+                                collect_detail_bits(parser, name, &mut general)?;
+                                add_doc_refs(parser, &mut general);
+                            }
+                            _ => {
+                                let mut _tother = String::new();
+                                parse_standard_elements(parser, name, &e, &mut _tother)?;
+                            }
+                        }
+                    },
+                    XmlEvent::EndElement {..} => {
+                    },
+                    XmlEvent::Characters(_s) => {
+                    },
+                    XmlEvent::EndDocument => {
+                        general.fn_name = headerfile.clone();
+                        general.fn_defines = defines;
+                        functions.push(general);
+                        return Ok(());
+                    }
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+// Take the comment markers off the start and end of a line from a header file,
+// eg " * ", "\t// " or "/* ... */"
+fn strip_comment_markers(line: &str) -> &str
+{
+    let mut l = line.trim();
+    loop {
+        let stripped = l.trim_start_matches("/*").trim_start_matches("//").trim_start_matches('*').trim_start();
+        if stripped.len() == l.len() {
+            break;
+        }
+        l = stripped;
+    }
+    l.trim_end_matches("*/").trim_end()
+}
+
+// Find the copyright lines in a header file's comments. If there are several
+// they are all returned, one per line.
+// copyright_regex (--copyright-regex) replaces the usual matching of lines that
+// start with "Copyright": the first group in it is used if it has one, otherwise
+// all of the match.
+pub fn read_header_copyright(header_src_dir: &str, headerfile: &str, copyright_regex: &str) -> Result<String, std::io::Error>
+{
+    let re = if copyright_regex.is_empty() {
+        None
+    } else {
+        Some(regex::Regex::new(copyright_regex).map_err(Error::other)?)
+    };
+
+    let mut h_file = String::new();
+    if let Err(_e) = write!(h_file, "{}/{}", header_src_dir, headerfile) {
+        println!("Error making header file name for {}: {}", header_src_dir, headerfile);
+        return Err(Error::other("Error making filename"));
+    }
+
+    let f = File::open(&h_file)?;
+    let r = BufReader::new(f);
+    let mut copyrights = Vec::<String>::new();
+    for l in r.lines() {
+        let line = l?;
+        let found = match &re {
+            Some(re) => re.captures(&line)
+                .and_then(|c| c.get(1).or_else(|| c.get(0)))
+                .map(|m| m.as_str().trim()),
+            None => Some(strip_comment_markers(&line)).filter(|l| l.starts_with("Copyright")),
+        };
+        if let Some(c) = found {
+            if !c.is_empty() && !copyrights.iter().any(|x| x == c) {
+                copyrights.push(c.to_string());
+            }
+        }
+    }
+    if copyrights.is_empty() {
+        return Err(Error::other("Not found"));
+    }
+    Ok(copyrights.join("\n"))
+}
+
+
+// Read the main XML file for a header, and all the structure files it refers to.
+// If headerfile is "unknown.h" it is filled in from the XML.
+// Structure files are only read if they are not already in structure_cache, so
+// types shared between headers are only parsed once per run.
+pub fn read_header_xml(xml_dir: &str,
+                       in_file: &str,
+                       headerfile: &mut String,
+                       structure_cache: &mut BTreeMap<String, StructureInfo>) -> Result<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>), Error>
+{
+    let (functions, structures) = read_main_xml(xml_dir, in_file, headerfile)?;
+    let structures = fill_structures(xml_dir, &functions, &structures, structure_cache);
+    Ok((functions, structures))
+}
+
+// Read just the main XML file for a header. The structures only have what
+// was in that file, fill_structures() reads the rest of them.
+pub fn read_main_xml(xml_dir: &str,
+                     in_file: &str,
+                     headerfile: &mut String) -> Result<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>), Error>
+{
+    read_header_xml_internal(xml_dir, in_file, headerfile, None)
+}
+
+// Get the full information for the structures (that functions use), reading
+// the XML files for any that are not already in the cache
+pub fn fill_structures(xml_dir: &str,
+                       functions: &[FunctionInfo],
+                       structures: &BTreeMap<String, StructureInfo>,
+                       structure_cache: &mut BTreeMap<String, StructureInfo>) -> BTreeMap<String, StructureInfo>
+{
+    let mut new_structures = BTreeMap::<String, StructureInfo>::new();
+    for (refid, s) in structures {
+        if !structure_cache.contains_key(refid) {
+            new_structures.insert(refid.clone(), s.clone());
+        }
+    }
+    read_structures_files(xml_dir, functions, &new_structures, structure_cache);
+
+    let mut filled_structures = BTreeMap::<String, StructureInfo>::new();
+    for refid in structures.keys() {
+        if let Some(s) = structure_cache.get(refid) {
+            filled_structures.insert(refid.clone(), s.clone());
+        }
+    }
+    filled_structures
+}
+
+// As read_header_xml, but each function is passed to on_function as soon as it has
+// been read instead of being returned, so very large files don't have to be held in memory.
+// Only the general page for the header (and any callback typedefs) is returned at the end.
+// The structures are not read in (on_function should use fill_structures() for the ones it needs).
+pub fn read_header_xml_streaming(xml_dir: &str,
+                                 in_file: &str,
+                                 headerfile: &mut String,
+                                 on_function: FunctionCallback) -> Result<Vec<FunctionInfo>, Error>
+{
+    let (functions, _structures) = read_header_xml_internal(xml_dir, in_file, headerfile, Some(on_function))?;
+    Ok(functions)
+}
+
+fn read_header_xml_internal(xml_dir: &str,
+                            in_file: &str,
+                            headerfile: &mut String,
+                            on_function: Option<FunctionCallback>) -> Result<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>), Error>
+{
+    let mut main_xml_file = String::new();
+    if let Err(e) = write!(main_xml_file, "{}/{}", xml_dir, in_file) {
+        return Err(Error::other(format!("Error making main XML file name for {in_file}: {e}")));
+    }
+
+    let f = match File::open(&main_xml_file) {
+        Ok(f) => f,
+        Err(e) => return Err(Error::new(e.kind(), format!("Cannot open XML file {main_xml_file}: {e}"))),
+    };
+    let mut parser = new_parser(f);
+
+    let mut functions = Vec::<FunctionInfo>::new();
+    let mut structures = BTreeMap::<String, StructureInfo>::new();
+
+    // Read it all into structures
+    if let Err(e) = read_file(&mut parser, headerfile, &mut functions, &mut structures, on_function) {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Error reading XML for {main_xml_file}: {e}")));
+    }
+
+    Ok((functions, structures))
+}
+
+// Quickly read just the function names from a header's XML file (and the header name
+// if it's not known yet), so that SEE ALSO can be written in streaming mode. The
+// documented callback typedefs are there too (with fn_typedef set), as read_file has them.
+pub fn read_function_names(xml_dir: &str,
+                           in_file: &str,
+                           headerfile: &mut String) -> Result<Vec<FunctionInfo>, Error>
+{
+    let main_xml_file = format!("{xml_dir}/{in_file}");
+    let f = match File::open(&main_xml_file) {
+        Ok(f) => f,
+        Err(e) => return Err(Error::new(e.kind(), format!("Cannot open XML file {main_xml_file}: {e}"))),
+    };
+    let parser = new_parser(f);
+
+    let mut names = Vec::<FunctionInfo>::new();
+    // The memberdef we are in, if it's a function or typedef, and what we have of it
+    let mut member: Option<FunctionInfo> = None;
+    let mut argsstring = String::new();
+    let mut brief = String::new();
+    // The element whose text we want
+    let mut in_text = "";
+    for er in parser {
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "memberdef" => {
+                                let kind = get_attr(&e, "kind");
+                                member = (kind == "function" || kind == "typedef").then(|| {
+                                    let mut f = FunctionInfo::new();
+                                    f.fn_typedef = kind == "typedef";
+                                    f
+                                });
+                                argsstring.clear();
+                                brief.clear();
+                            }
+                            "name" if member.is_some() => in_text = "name",
+                            "argsstring" if member.is_some() => in_text = "argsstring",
+                            "briefdescription" if member.is_some() => in_text = "briefdescription",
+                            "compoundname" if headerfile == "unknown.h" => {
+                                in_text = "compoundname";
+                                headerfile.clear();
+                            }
+                            _ => {}
+                        }
+                    }
+                    XmlEvent::Characters(s) => {
+                        match (in_text, member.as_mut()) {
+                            ("name", Some(m)) => m.fn_name += s,
+                            ("argsstring", Some(_)) => argsstring += s,
+                            ("briefdescription", Some(_)) => brief += s,
+                            ("compoundname", _) => *headerfile += s,
+                            _ => {}
+                        }
+                    }
+                    XmlEvent::EndElement {name, ..} => {
+                        // The brief description has <para>s in it
+                        if in_text != "briefdescription" || name.local_name == "briefdescription" {
+                            in_text = "";
+                        }
+                        if name.local_name == "memberdef" {
+                            if let Some(m) = member.take() {
+                                if !m.fn_typedef || (argsstring.starts_with(")(") && !brief.trim().is_empty()) {
+                                    names.push(m);
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(Error::new(ErrorKind::InvalidData, format!("Error reading XML for {main_xml_file}: {e}")));
+            }
+        }
+    }
+    Ok(names)
+}
@@ -0,0 +1,170 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Reads doxygen's page compounds, ie the \mainpage (indexpage.xml) and \page documents
+
+use std::fs::File;
+use std::io::{BufRead, Error, ErrorKind};
+
+use crate::model::DocPage;
+use super::reader::{XmlReader, XmlEvent, XmlName};
+use super::text::{collect_text, collect_text_into, parse_standard_elements};
+use super::{get_attr, new_parser};
+
+// Collect the text of a page, with sect1 titles as .SH lines and
+// anything deeper as .SS
+fn collect_page_text<R: BufRead>(parser: &mut XmlReader<R>,
+                                 elem_name: &XmlName,
+                                 text: &mut String) -> Result<(), super::reader::Error>
+{
+    let mut sect_depth = 0;
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "sect1" | "sect2" | "sect3" | "sect4" => {
+                                sect_depth += 1;
+                            }
+                            "title" => {
+                                let title = collect_text(parser, name)?;
+                                if !text.is_empty() && !text.ends_with('\n') {
+                                    text.push('\n');
+                                }
+                                if sect_depth <= 1 {
+                                    text.push_str(&format!(".SH {}\n", title.trim().to_ascii_uppercase()));
+                                } else {
+                                    text.push_str(&format!(".SS {}\n", title.trim()));
+                                }
+                            }
+                            "para" => {
+                                collect_text_into(parser, name, text)?;
+                                text.push('\n');
+                            }
+                            _ => {
+                                parse_standard_elements(parser, name, &e, text)?;
+                            }
+                        }
+                    }
+                    // Text between the paragraphs is just the XML's indentation
+                    XmlEvent::Characters(s) if !s.trim().is_empty() => {
+                        text.push_str(s);
+                    }
+                    XmlEvent::EndElement {name, ..} if name == elem_name => {
+                        let end = text.trim_end().len();
+                        text.truncate(end);
+                        return Ok(());
+                    }
+                    XmlEvent::EndElement {name, ..} if name.local_name.starts_with("sect") => {
+                        sect_depth -= 1;
+                    }
+                    XmlEvent::EndDocument => {
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn read_page<R: BufRead>(parser: &mut XmlReader<R>) -> Result<DocPage, super::reader::Error>
+{
+    let mut page = DocPage::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "compoundname" => {
+                                page.pg_name = collect_text(parser, name)?;
+                            }
+                            "title" => {
+                                page.pg_title = collect_text(parser, name)?;
+                            }
+                            "briefdescription" => {
+                                collect_text_into(parser, name, &mut page.pg_brief)?;
+                                page.pg_brief = page.pg_brief.trim().to_string();
+                            }
+                            "detaileddescription" => {
+                                collect_page_text(parser, name, &mut page.pg_detail)?;
+                                page.pg_detail = page.pg_detail.trim_start().to_string();
+                            }
+                            _ => {}
+                        }
+                    }
+                    XmlEvent::EndDocument => {
+                        return Ok(page);
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+// Read a page compound, refid is eg "indexpage" for the \mainpage
+pub fn read_page_xml(xml_dir: &str, refid: &str) -> Result<DocPage, Error>
+{
+    let page_xml_file = format!("{xml_dir}/{refid}.xml");
+    let f = match File::open(&page_xml_file) {
+        Ok(f) => f,
+        Err(e) => return Err(Error::new(e.kind(), format!("Cannot open XML file {page_xml_file}: {e}"))),
+    };
+    let mut parser = new_parser(f);
+
+    match read_page(&mut parser) {
+        Ok(p) => Ok(p),
+        Err(e) => Err(Error::new(ErrorKind::InvalidData, format!("Error reading XML for {page_xml_file}: {e}"))),
+    }
+}
+
+// Get the refids of all the \page compounds from doxygen's index.xml, in the order
+// doxygen lists them. The \mainpage (indexpage) is not included.
+pub fn read_page_refids(xml_dir: &str) -> Result<Vec<String>, Error>
+{
+    let index_file = format!("{xml_dir}/index.xml");
+    let f = match File::open(&index_file) {
+        Ok(f) => f,
+        Err(e) => return Err(Error::new(e.kind(), format!("Cannot open XML file {index_file}: {e}"))),
+    };
+    let parser = new_parser(f);
+
+    let mut refids = Vec::<String>::new();
+    for er in parser {
+        match er {
+            Ok(e) => {
+                if let XmlEvent::StartElement {name, ..} = &e {
+                    if name.local_name == "compound" && get_attr(&e, "kind") == "page" {
+                        let refid = get_attr(&e, "refid");
+                        if refid != "indexpage" {
+                            refids.push(refid);
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                return Err(Error::new(ErrorKind::InvalidData, format!("Error reading XML for {index_file}: {e}")));
+            }
+        }
+    }
+    Ok(refids)
+}
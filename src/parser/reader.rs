@@ -0,0 +1,190 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// A thin layer over quick-xml that gives the collectors the same simple
+// pull-parser events as xml-rs did: text, CDATA and entities are joined
+// into a single Characters event and empty elements are reported as a
+// start and an end, so the collectors don't need to care about any of that.
+
+use std::fmt;
+use std::io::BufRead;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::encoding::Decoder;
+use quick_xml::Reader;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlName {
+    pub local_name: String,
+}
+
+impl fmt::Display for XmlName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}", self.local_name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct XmlAttribute {
+    pub name: XmlName,
+    pub value: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum XmlEvent {
+    StartElement {name: XmlName, attributes: Vec<XmlAttribute>},
+    EndElement {name: XmlName},
+    Characters(String),
+    EndDocument,
+}
+
+fn xml_name(name: &[u8]) -> XmlName
+{
+    XmlName {local_name: String::from_utf8_lossy(name).to_string()}
+}
+
+fn start_element(s: &BytesStart, decoder: Decoder) -> Result<XmlEvent, String>
+{
+    let mut attributes = Vec::new();
+    for a in s.attributes() {
+        let a = a.map_err(|e| e.to_string())?;
+        let value = a.decode_and_unescape_value(decoder).map_err(|e| e.to_string())?;
+        attributes.push(XmlAttribute {name: xml_name(a.key.local_name().as_ref()), value: value.to_string()});
+    }
+    Ok(XmlEvent::StartElement {name: xml_name(s.local_name().as_ref()), attributes})
+}
+
+#[derive(Debug)]
+pub struct Error {
+    position: u64,
+    message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "at byte {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub struct XmlReader<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    // An event read while collecting text, to be returned after the text
+    queued: Option<XmlEvent>,
+    finished: bool,
+    // The <ref>s read so far, as (refid, text). The collectors take
+    // the ones for the element they are reading.
+    pub refs: Vec<(String, String)>,
+}
+
+impl<R: BufRead> XmlReader<R> {
+    pub fn new(r: R) -> XmlReader<R>
+    {
+        let mut reader = Reader::from_reader(r);
+        reader.config_mut().expand_empty_elements = true;
+        XmlReader {reader, buf: Vec::new(), queued: None, finished: false, refs: Vec::new()}
+    }
+
+    pub fn next_event(&mut self) -> Result<XmlEvent, Error>
+    {
+        if let Some(e) = self.queued.take() {
+            return Ok(e);
+        }
+        if self.finished {
+            return Ok(XmlEvent::EndDocument);
+        }
+
+        let mut text = String::new();
+        loop {
+            self.buf.clear();
+            let decoder = self.reader.decoder();
+            let res = match self.reader.read_event_into(&mut self.buf) {
+                Ok(Event::Start(s)) => start_element(&s, decoder),
+                Ok(Event::End(e)) => Ok(XmlEvent::EndElement {name: xml_name(e.local_name().as_ref())}),
+                Ok(Event::Text(t)) => {
+                    match t.xml10_content() {
+                        Ok(s) => {
+                            text.push_str(&s);
+                            continue;
+                        }
+                        Err(e) => Err(e.to_string()),
+                    }
+                }
+                Ok(Event::CData(c)) => {
+                    match c.xml10_content() {
+                        Ok(s) => {
+                            text.push_str(&s);
+                            continue;
+                        }
+                        Err(e) => Err(e.to_string()),
+                    }
+                }
+                Ok(Event::GeneralRef(r)) => {
+                    match r.resolve_char_ref() {
+                        Ok(Some(c)) => {
+                            text.push(c);
+                            continue;
+                        }
+                        Ok(None) => {
+                            match r.decode() {
+                                Ok(entity) => match resolve_predefined_entity(&entity) {
+                                    Some(s) => {
+                                        text.push_str(s);
+                                        continue;
+                                    }
+                                    None => Err(format!("unknown entity &{entity};")),
+                                },
+                                Err(e) => Err(e.to_string()),
+                            }
+                        }
+                        Err(e) => Err(e.to_string()),
+                    }
+                }
+                Ok(Event::Eof) => {
+                    self.finished = true;
+                    Ok(XmlEvent::EndDocument)
+                }
+                Ok(_) => continue, // Comments, the XML declaration etc.
+                Err(e) => Err(e.to_string()),
+            };
+            let event = match res {
+                Ok(e) => e,
+                Err(message) => return Err(Error {position: self.reader.error_position(), message}),
+            };
+
+            if text.is_empty() {
+                return Ok(event);
+            }
+            self.queued = Some(event);
+            return Ok(XmlEvent::Characters(text));
+        }
+    }
+}
+
+// Like xml-rs, iterating stops after the end of the document or an error
+impl<R: BufRead> Iterator for XmlReader<R> {
+    type Item = Result<XmlEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        if self.finished && self.queued.is_none() {
+            return None;
+        }
+        let res = self.next_event();
+        if res.is_err() {
+            self.finished = true;
+            self.queued = None;
+        }
+        Some(res)
+    }
+}
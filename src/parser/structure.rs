@@ -0,0 +1,350 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::BufRead;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use super::reader::{XmlReader, XmlEvent};
+
+use crate::diagnostics;
+use crate::model::*;
+use super::get_attr;
+use super::text::collect_text;
+
+// Read a single structure member from a structure file
+fn read_structure_member<R: BufRead>(parser: &mut XmlReader<R>) -> Result<FnParam, super::reader::Error>
+{
+    let mut par_name = String::new();
+    let mut par_type = String::new();
+    let mut par_desc = String::new();
+    let mut par_brief = String::new();
+    let mut par_args = String::new();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "name" => {
+                                par_name = collect_text(parser, name)?;
+                            }
+                            "type" => {
+                                par_type = collect_text(parser, name)?;
+                            }
+                            "argsstring" => {
+                                par_args = collect_text(parser, name)?;
+                            }
+                            "detaileddescription" => {
+                                par_desc = collect_text(parser, name)?.trim().to_string();
+                            }
+                            "briefdescription" => {
+                                par_brief = collect_text(parser, name)?.trim().to_string();
+                            }
+                            _ => {
+                                // Not used but still needs to be collected
+                                let _fntext = collect_text(parser, name)?;
+                            }
+                        }
+                    }
+                    XmlEvent::EndElement {..} => {
+                        return Ok(FnParam {par_name, par_type, par_desc, par_args, par_brief, par_refid: None,
+                                                   par_defval: String::new(), par_direction: String::new()});
+                    },
+                    XmlEvent::Characters(_s) => {
+                    },
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+pub fn collect_enum<R: BufRead>(parser: &mut XmlReader<R>,
+                             str_type: StructureType) -> Result<StructureInfo, super::reader::Error>
+{
+    let mut sinfo = StructureInfo::new();
+    sinfo.str_type = str_type;
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "name" => {
+                                sinfo.str_name = collect_text(parser, name)?;
+                            }
+                            "enumvalue" => {
+                                match read_structure_member(parser) {
+                                    Ok(s) => sinfo.str_members.push(s),
+                                    Err(e) => return Err(e),
+                                }
+                            }
+                            "briefdescription" => {
+                                sinfo.str_brief = collect_text(parser, name)?;
+                            }
+                            "detaileddescription" => {
+                                sinfo.str_description = collect_text(parser, name)?;
+                            }
+                            _ => {
+                                let _ = collect_text(parser, name)?;
+                            }
+                        }
+                    }
+                    XmlEvent::EndElement {..} => {
+                        return Ok(sinfo);
+                    },
+                    XmlEvent::Characters(_s) => {
+                    },
+                    XmlEvent::EndDocument => return Ok(sinfo),
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+
+// Found the point in the struct file where the definition is. Read it in
+fn read_structure<R: BufRead>(parser: &mut XmlReader<R>,
+                           str_type: StructureType) -> Result<StructureInfo, super::reader::Error>
+{
+    let mut sinfo = StructureInfo::new();
+
+    sinfo.str_type = str_type;
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "compoundname" => {
+                                sinfo.str_name = collect_text(parser, name)?;
+                            }
+                            "briefdescription" => {
+                                sinfo.str_brief = collect_text(parser, name)?;
+                            }
+                            "includes" => {
+                                let _ignore = collect_text(parser, name)?;
+                            }
+                            "detaileddescription" => {
+                                sinfo.str_description = collect_text(parser, name)?;
+                            }
+                            "memberdef" => {
+                                match read_structure_member(parser) {
+                                    Ok(s) => sinfo.str_members.push(s),
+                                    Err(e) => return Err(e),
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    XmlEvent::EndElement {name, ..} if name.local_name == "compounddef" => {
+                        return Ok(sinfo);
+                    },
+                    XmlEvent::Characters(_s) => {
+                    },
+                    XmlEvent::EndDocument => {},
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+// Read a single structure from its XML file
+fn read_structure_file<R: BufRead>(parser: &mut XmlReader<R>,
+                                str_type: StructureType) -> Result<(String, StructureInfo), super::reader::Error>
+{
+    let mut sinfo = StructureInfo::new();
+    let mut refid = String::new();
+
+    sinfo.str_type = str_type;
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "compounddef" => {
+                                if let Ok(s) = read_structure(parser, StructureType::Struct) {
+                                    sinfo = s;
+                                    refid = get_attr(&e, "id");
+                                }
+                            }
+                            "briefdescription" => {
+                                sinfo.str_brief = collect_text(parser, name)?;
+                            }
+                            "detaileddescription" => {
+                                sinfo.str_description = collect_text(parser, name)?;
+                            }
+                            _ => {}
+                        }
+                    }
+                    XmlEvent::EndElement {..} => {
+                    },
+                    XmlEvent::Characters(_s) => {
+                    },
+                    XmlEvent::EndDocument => return Ok((refid, sinfo)),
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+
+// Read one structure's XML file. The whole file is read in one go, which is
+// a lot quicker than many small reads on NFS. function is one that uses it, for
+// the warnings.
+fn read_structure_xml(xml_dir: &str, refid: &str, function: &str) -> Option<(String, StructureInfo)>
+{
+    let xml_file = format!("{xml_dir}/{refid}.xml");
+    let data = match fs::read(&xml_file) {
+        Ok(d) => d,
+        Err(e) => {
+            diagnostics::warning("missing-structure", function, &format!("cannot read structure file {xml_file}: {e}"));
+            return None;
+        }
+    };
+    let mut parser = XmlReader::new(data.as_slice());
+    match read_structure_file(&mut parser, StructureType::Struct) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            diagnostics::warning("missing-structure", function, &format!("cannot parse structure file {xml_file}: {e}"));
+            None
+        }
+    }
+}
+
+// Read all the structure files we need for our functions.
+// There can be hundreds of them, so they are read in parallel.
+pub fn read_structures_files(xml_dir: &str,
+                             functions: &[FunctionInfo],
+                             structures: &BTreeMap<String, StructureInfo>,
+                             filled_structures: &mut BTreeMap<String, StructureInfo>)
+{
+    let user = |refid: &str| {
+        functions.iter().find(|f| f.fn_refids.iter().any(|r| r == refid)).map_or("", |f| f.fn_name.as_str())
+    };
+    let mut struct_refids = Vec::<&str>::new();
+    for (refid, s) in structures {
+        match s.str_type {
+            StructureType::Enum => {
+                filled_structures.insert(refid.to_string(), (*s).clone());
+            }
+            StructureType::Unknown => {} // Throw it away
+            StructureType::Struct => struct_refids.push(refid),
+        }
+    }
+
+    let num_threads = thread::available_parallelism().map_or(1, |n| n.get()).min(struct_refids.len());
+    let next_file = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        let mut threads = Vec::new();
+        for _ in 0..num_threads {
+            threads.push(scope.spawn(|| {
+                let mut found = Vec::new();
+                while let Some(refid) = struct_refids.get(next_file.fetch_add(1, Ordering::Relaxed)) {
+                    if let Some(s) = read_structure_xml(xml_dir, refid, user(refid)) {
+                        found.push(s);
+                    }
+                }
+                found
+            }));
+        }
+        for t in threads {
+            if let Ok(found) = t.join() {
+                // Add to the new map
+                filled_structures.extend(found);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enum_values()
+    {
+        // As read_file finds it, after the start of the <memberdef>
+        let mut parser = XmlReader::new(&br#"<memberdef kind="enum" id="test_8h_1a4">
+            <name>test_mode</name>
+            <enumvalue id="test_8h_1a5"><name>TEST_READ</name><initializer>= 1</initializer>
+              <briefdescription><para>Read only</para></briefdescription><detaileddescription></detaileddescription></enumvalue>
+            <enumvalue id="test_8h_1a6"><name>TEST_WRITE</name><initializer>= 2</initializer>
+              <briefdescription></briefdescription><detaileddescription></detaileddescription></enumvalue>
+            <briefdescription><para>How to open a test</para></briefdescription>
+            <detaileddescription></detaileddescription>
+          </memberdef>"#[..]);
+        parser.next_event().unwrap();
+        let e = collect_enum(&mut parser, StructureType::Enum).unwrap();
+
+        assert!(matches!(e.str_type, StructureType::Enum));
+        assert_eq!(e.str_name, "test_mode");
+        assert_eq!(e.str_brief.trim(), "How to open a test");
+        let names: Vec<&str> = e.str_members.iter().map(|m| m.par_name.as_str()).collect();
+        assert_eq!(names, ["TEST_READ", "TEST_WRITE"]);
+        assert_eq!(e.str_members[0].par_brief, "Read only");
+    }
+
+    #[test]
+    fn structure_file()
+    {
+        let mut parser = XmlReader::new(&br#"<?xml version='1.0' encoding='UTF-8' standalone='no'?>
+          <doxygen version="1.9.1">
+            <compounddef id="structtest__info" kind="struct" language="C++" prot="public">
+              <compoundname>test_info</compoundname>
+              <sectiondef kind="public-attrib">
+                <memberdef kind="variable" id="structtest__info_1a1">
+                  <type>int</type><name>fd</name><argsstring></argsstring>
+                  <briefdescription><para>The file descriptor</para></briefdescription>
+                  <detaileddescription></detaileddescription>
+                </memberdef>
+                <memberdef kind="variable" id="structtest__info_1a2">
+                  <type>char</type><name>name</name><argsstring>[64]</argsstring>
+                  <briefdescription></briefdescription><detaileddescription></detaileddescription>
+                </memberdef>
+              </sectiondef>
+              <briefdescription><para>What a test has open</para></briefdescription>
+              <detaileddescription></detaileddescription>
+            </compounddef>
+          </doxygen>"#[..]);
+        let (refid, s) = read_structure_file(&mut parser, StructureType::Struct).unwrap();
+
+        assert_eq!(refid, "structtest__info");
+        assert_eq!(s.str_name, "test_info");
+        assert_eq!(s.str_brief.trim(), "What a test has open");
+        assert_eq!(s.str_members.len(), 2);
+        assert_eq!(s.str_members[0].par_name, "fd");
+        assert_eq!(s.str_members[0].par_type, "int");
+        assert_eq!(s.str_members[0].par_brief, "The file descriptor");
+        assert_eq!(s.str_members[1].par_name, "name");
+        assert_eq!(s.str_members[1].par_args, "[64]");
+    }
+}
@@ -0,0 +1,271 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+use std::io::BufRead;
+use super::reader::{XmlReader, XmlEvent, XmlName};
+
+use crate::diagnostics;
+use super::get_attr;
+
+// Constant-width font, for \c (and \p) words
+pub const CODE_FONT: &str = "\\f(CR";
+
+// The kind of doxygen's highlighting (eg keyword or comment) of the text in
+// program listings is kept between these, just after the \fB that makes the
+// text bold. The HTML pages colour the text by it and everything else drops it.
+// They are control characters, so they can't be in the XML.
+pub const HIGHLIGHT_START: char = '\u{1}';
+pub const HIGHLIGHT_END: char = '\u{2}';
+
+// Some text without the kinds of highlighting in it
+pub fn strip_highlights(text: &str) -> String
+{
+    let mut out = String::with_capacity(text.len());
+    let mut in_class = false;
+    for c in text.chars() {
+        match c {
+            HIGHLIGHT_START => in_class = true,
+            HIGHLIGHT_END => in_class = false,
+            c if !in_class => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+// The kind of highlighting at the start of some bold text, and the rest of the text
+pub fn split_highlight(text: &str) -> (Option<&str>, &str)
+{
+    if let Some(rest) = text.strip_prefix(HIGHLIGHT_START) {
+        if let Some((class, rest)) = rest.split_once(HIGHLIGHT_END) {
+            return (Some(class), rest);
+        }
+    }
+    (None, text)
+}
+
+// doxygen's description markup that we don't do anything with. Only the text in
+// it is kept, so there is a warning (once for each file) in case that matters.
+const UNSUPPORTED_MARKUP: &[&str] = &[
+    "underline", "strike", "s", "del", "ins", "subscript", "superscript", "center", "small",
+    "ulink", "cite", "orderedlist", "variablelist", "table", "heading", "verbatim", "preformatted",
+    "image", "formula", "dot", "msc", "plantuml", "dotfile", "mscfile", "diafile",
+    "htmlonly", "latexonly", "rtfonly", "docbookonly", "xmlonly", "details", "summary",
+];
+
+// Do the easy/common tags here, the text is added to the end of 'text'
+pub fn parse_standard_elements<R: BufRead>(parser: &mut XmlReader<R>, name: &XmlName, e: &XmlEvent, text: &mut String) -> Result<(), super::reader::Error>
+{
+    match name.local_name.as_str() {
+        "para" => {
+            collect_text_into(parser, name, text)?;
+        }
+        "sp" => {
+            text.push(' ');
+        }
+        "emphasis" | "bold" => {
+            text.push_str("\\fB");
+            collect_text_into(parser, name, text)?;
+            text.push_str("\\fR");
+        }
+        "highlight" => {
+            let h_type = get_attr(e, "class");
+            if h_type != "normal" {
+                text.push_str("\\fB");
+                text.push(HIGHLIGHT_START);
+                text.push_str(&h_type);
+                text.push(HIGHLIGHT_END);
+            }
+            collect_text_into(parser, name, text)?;
+            if h_type != "normal" {
+                text.push_str("\\fR");
+            }
+        }
+        "computeroutput" => {
+            text.push_str(CODE_FONT);
+            collect_text_into(parser, name, text)?;
+            text.push_str("\\fR");
+        }
+        "codeline" => {
+            // Each one is a line of the listing, even if the XML has nothing between them
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            collect_text_into(parser, name, text)?;
+        }
+        "programlisting" => {
+            text.push_str("\n.nf\n");
+            collect_text_into(parser, name, text)?;
+            text.push_str("\n.fi\n");
+        }
+        "itemizedlist" => {
+            text.push('\n');
+            collect_text_into(parser, name, text)?;
+            text.push('\n');
+        }
+        "listitem" => {
+            text.push_str("\n* ");
+            collect_text_into(parser, name, text)?;
+        }
+        "parameternamelist" => {
+            collect_text_into(parser, name, text)?;
+        }
+        "parameteritem" => {
+            collect_text_into(parser, name, text)?;
+        }
+        "parameterlist" => {
+            collect_text_into(parser, name, text)?;
+        }
+        "parameterdescription" => {
+            collect_text_into(parser, name, text)?;
+        }
+        "parametername" => {
+            collect_text_into(parser, name, text)?;
+        }
+        "blockquote" => {
+            text.push_str("\n.RS\n");
+            collect_text_into(parser, name, text)?;
+            text.push_str("\n.RE\n");
+        }
+        "note" => {
+            collect_text_into(parser, name, text)?;
+            text.push('\n');
+        }
+        "ref" => {
+            let start = text.len();
+            collect_text_into(parser, name, text)?;
+            parser.refs.push((get_attr(e, "refid"), text[start..].trim().to_string()));
+        }
+        "simplesect" => {
+            collect_text_into(parser, name, text)?;
+        }
+        // doxygen's \emoji, the --symbols policy is applied to the pages
+        "emoji" => {
+            let unicode = get_attr(e, "unicode");
+            if unicode.is_empty() {
+                text.push_str(&get_attr(e, "name"));
+            } else {
+                text.push_str(&unicode);
+            }
+            let _ignore = collect_text(parser, name)?;
+        }
+        "anchor" => {
+            // \anchor targets have no text of their own
+            let _ignore = collect_text(parser, name)?;
+        }
+        // Titles and captions (eg of tables and parameter lists) are a bold line
+        // before what they are the title of
+        "title" | "caption" | "xreftitle" => {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str("\\fB");
+            collect_text_into(parser, name, text)?;
+            text.push_str("\\fR\n");
+        }
+        // xrefsects in function descriptions are collected on their own, these are
+        // the ones in other places
+        "xrefdescription" | "xrefsect" => {
+            collect_text_into(parser, name, text)?;
+        }
+        n if UNSUPPORTED_MARKUP.contains(&n) => {
+            diagnostics::warning_once("unsupported-markup", "", &format!("<{n}> in descriptions is not supported, only the text in it is kept"));
+        }
+        _ => {
+        }
+    }
+    Ok(())
+}
+
+// This returns the string itself (formatted), a refid for the object if appropriate
+// and whether that is a compound (eg a struct) rather than a member (eg an enum or typedef).
+pub fn collect_text_and_refid<R: BufRead>(parser: &mut XmlReader<R>) -> Result<(String, Option<String>, bool), super::reader::Error>
+{
+    let mut text = String::new();
+    let mut refid = None;
+    let mut compound = false;
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        match name.local_name.as_str() {
+                            "ref" => {
+                                refid = Some(get_attr(&e, "refid"));
+                                compound = get_attr(&e, "kindref") == "compound";
+                                let start = text.len();
+                                collect_text_into(parser, name, &mut text)?;
+                                parser.refs.push((get_attr(&e, "refid"), text[start..].trim().to_string()));
+                            }
+                            _ => {
+                                parse_standard_elements(parser, name, &e, &mut text)?;
+                            }
+                        }
+                    }
+                    XmlEvent::Characters(s) => {
+                        text += s;
+                    }
+                    XmlEvent::EndElement {..} => {
+                        return Ok((text.trim_end().to_string(), refid, compound));
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+// This is the main text-collecting routine. It should parse as many XML options as possible.
+// It returns the string itself (formatted).
+// It is called recursively as we descend the XML structures
+pub fn collect_text<R: BufRead>(parser: &mut XmlReader<R>, elem_name: &XmlName) -> Result<String, super::reader::Error>
+{
+    let mut text = String::new();
+    collect_text_into(parser, elem_name, &mut text)?;
+    Ok(text)
+}
+
+// collect_text, but adding the text to the end of an existing string, so the
+// nested elements don't each need a String of their own. Trailing whitespace
+// is trimmed from just the text of this element.
+pub fn collect_text_into<R: BufRead>(parser: &mut XmlReader<R>, elem_name: &XmlName, text: &mut String) -> Result<(), super::reader::Error>
+{
+    let start = text.len();
+
+    loop {
+        let er = parser.next_event();
+        match er {
+            Ok(e) => {
+                match &e {
+                    XmlEvent::StartElement {name, ..} => {
+                        parse_standard_elements(parser, name, &e, text)?;
+                    }
+                    XmlEvent::Characters(s) => {
+                        text.push_str(s);
+                    }
+                    XmlEvent::EndElement {name, ..} if name == elem_name => {
+                        // Only return if we are at the end of the element that called us
+                        let end = start + text[start..].trim_end().len();
+                        text.truncate(end);
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
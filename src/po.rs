@@ -0,0 +1,310 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Translations of the documentation, with gettext PO files. --write-pot
+// extracts the paragraphs of the descriptions as messages, and --po puts the
+// translated ones back before the pages are written. Each paragraph is its own
+// message, so changing one doesn't make the translations of the rest stale.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, Write};
+use crate::model::FunctionInfo;
+use crate::render::write_atomically;
+
+// A message for the POT file, and where it came from
+#[derive(Debug, Clone, Default)]
+pub struct PoMessage {
+    pub msgid: String,
+    pub functions: Vec<String>,
+    pub references: Vec<String>,
+}
+
+// The messages extracted so far, in the order they were found
+#[derive(Debug, Clone, Default)]
+pub struct PotCatalog {
+    pub messages: Vec<PoMessage>,
+    index: BTreeMap<String, usize>,
+}
+
+impl PotCatalog {
+    fn add(&mut self, msgid: &str, function: &str, reference: &str)
+    {
+        let i = match self.index.get(msgid) {
+            Some(&i) => i,
+            None => {
+                self.messages.push(PoMessage {msgid: msgid.to_string(), ..Default::default()});
+                self.index.insert(msgid.to_string(), self.messages.len() - 1);
+                self.messages.len() - 1
+            }
+        };
+        let m = &mut self.messages[i];
+        if !m.functions.iter().any(|f| f == function) {
+            m.functions.push(function.to_string());
+        }
+        if !reference.is_empty() && !m.references.iter().any(|r| r == reference) {
+            m.references.push(reference.to_string());
+        }
+    }
+
+    // Add the messages in a function's documentation
+    pub fn add_function(&mut self, f: &FunctionInfo)
+    {
+        let reference = if f.fn_file.is_empty() { String::new() } else { format!("{}:{}", f.fn_file, f.fn_line) };
+        let mut texts = vec![&f.fn_brief, &f.fn_detail, &f.fn_returnval, &f.fn_note];
+        texts.extend(f.fn_args.iter().map(|p| &p.par_desc));
+        texts.extend(f.fn_retvals.iter().map(|r| &r.ret_desc));
+        texts.extend(f.fn_xrefsects.iter().map(|x| &x.xr_desc));
+        texts.extend(f.fn_simplesects.iter().filter(|s| s.ss_kind != "copyright").map(|s| &s.ss_text));
+        for t in texts {
+            map_paragraphs(t, |p| {
+                self.add(p, &f.fn_name, &reference);
+                None
+            });
+        }
+    }
+}
+
+// Call f on each paragraph of some nroff text, replacing it with what f returns
+// (if anything). A paragraph is a run of text lines, requests and listings are
+// left as they are.
+fn map_paragraphs<F>(text: &str, mut f: F) -> String
+    where F: FnMut(&str) -> Option<String>
+{
+    let mut out = Vec::<String>::new();
+    let mut para = Vec::<&str>::new();
+    let mut in_nf = false;
+    let mut flush = |para: &mut Vec<&str>, out: &mut Vec<String>| {
+        if para.is_empty() {
+            return;
+        }
+        let p = para.join("\n");
+        match f(&p) {
+            Some(t) => out.push(t),
+            None => out.push(p),
+        }
+        para.clear();
+    };
+    for l in text.lines() {
+        if l.starts_with(".nf") || l.starts_with(".EX") || l.starts_with(".TS") {
+            in_nf = true;
+        } else if l.starts_with(".fi") || l.starts_with(".EE") || l.starts_with(".TE") {
+            in_nf = false;
+        }
+        if in_nf || l.trim().is_empty() || l.starts_with('.') || l.starts_with('\'') {
+            flush(&mut para, &mut out);
+            out.push(l.to_string());
+        } else {
+            para.push(l);
+        }
+    }
+    flush(&mut para, &mut out);
+
+    let mut result = out.join("\n");
+    if text.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+fn translate_text(text: &mut String, translations: &BTreeMap<String, String>)
+{
+    if !text.is_empty() {
+        *text = map_paragraphs(text, |p| translations.get(p).cloned());
+    }
+}
+
+// Replace the paragraphs of a function's documentation that have translations
+pub fn translate_function(f: &mut FunctionInfo, translations: &BTreeMap<String, String>)
+{
+    translate_text(&mut f.fn_brief, translations);
+    translate_text(&mut f.fn_detail, translations);
+    translate_text(&mut f.fn_returnval, translations);
+    translate_text(&mut f.fn_note, translations);
+    for p in &mut f.fn_args {
+        translate_text(&mut p.par_desc, translations);
+    }
+    for r in &mut f.fn_retvals {
+        translate_text(&mut r.ret_desc, translations);
+    }
+    for x in &mut f.fn_xrefsects {
+        translate_text(&mut x.xr_desc, translations);
+    }
+    for s in f.fn_simplesects.iter_mut().filter(|s| s.ss_kind != "copyright") {
+        translate_text(&mut s.ss_text, translations);
+    }
+}
+
+fn po_escape(s: &str) -> String
+{
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\t', "\\t")
+}
+
+fn po_unescape(s: &str) -> String
+{
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out
+}
+
+// A PO string, split at its newlines as msgmerge does
+fn write_po_string<W: Write>(f: &mut W, keyword: &str, s: &str) -> Result<(), Error>
+{
+    if !s.contains('\n') {
+        return writeln!(f, "{} \"{}\"", keyword, po_escape(s));
+    }
+    writeln!(f, "{keyword} \"\"")?;
+    let lines: Vec<&str> = s.split('\n').collect();
+    for (i, l) in lines.iter().enumerate() {
+        let nl = if i + 1 == lines.len() { "" } else { "\\n" };
+        writeln!(f, "\"{}{}\"", po_escape(l), nl)?;
+    }
+    Ok(())
+}
+
+// Write the messages as a POT file, for msginit/msgmerge
+pub fn write_pot(pot_file: &str, package: &str, catalog: &PotCatalog) -> Result<(), Error>
+{
+    write_atomically(pot_file, |f| {
+        writeln!(f, "# Translations of the {package} man pages.")?;
+        writeln!(f, "#")?;
+        writeln!(f, "#, fuzzy")?;
+        writeln!(f, "msgid \"\"")?;
+        writeln!(f, "msgstr \"\"")?;
+        writeln!(f, "\"Project-Id-Version: {}\\n\"", po_escape(package))?;
+        writeln!(f, "\"MIME-Version: 1.0\\n\"")?;
+        writeln!(f, "\"Content-Type: text/plain; charset=UTF-8\\n\"")?;
+        writeln!(f, "\"Content-Transfer-Encoding: 8bit\\n\"")?;
+        for m in &catalog.messages {
+            writeln!(f)?;
+            writeln!(f, "#. {}", m.functions.join(", "))?;
+            for r in &m.references {
+                writeln!(f, "#: {r}")?;
+            }
+            write_po_string(f, "msgid", &m.msgid)?;
+            writeln!(f, "msgstr \"\"")?;
+        }
+        Ok(())
+    })
+}
+
+// Read the translations from a PO file. Fuzzy and untranslated messages are left out.
+pub fn read_po(po_file: &str) -> Result<BTreeMap<String, String>, Error>
+{
+    let mut translations = BTreeMap::new();
+    let mut msgid = String::new();
+    let mut msgstr = String::new();
+    let mut fuzzy = false;
+    // Which string the continuation lines are part of
+    let mut in_msgstr = false;
+    let mut add = |msgid: &mut String, msgstr: &mut String, fuzzy: bool| {
+        if !msgid.is_empty() && !msgstr.is_empty() && !fuzzy {
+            translations.insert(std::mem::take(msgid), std::mem::take(msgstr));
+        }
+        msgid.clear();
+        msgstr.clear();
+    };
+
+    for l in fs::read_to_string(po_file)?.lines() {
+        let l = l.trim();
+        let quoted = |s: &str| {
+            let s = s.trim();
+            po_unescape(s.strip_prefix('"').and_then(|s| s.strip_suffix('"')).unwrap_or(s))
+        };
+        if l.starts_with("#,") {
+            add(&mut msgid, &mut msgstr, fuzzy);
+            fuzzy = l.contains("fuzzy");
+            in_msgstr = false;
+        } else if let Some(s) = l.strip_prefix("msgid ") {
+            if in_msgstr {
+                add(&mut msgid, &mut msgstr, fuzzy);
+                fuzzy = false;
+            }
+            msgid = quoted(s);
+            in_msgstr = false;
+        } else if let Some(s) = l.strip_prefix("msgstr ") {
+            msgstr = quoted(s);
+            in_msgstr = true;
+        } else if l.starts_with('"') {
+            if in_msgstr {
+                msgstr += &quoted(l);
+            } else {
+                msgid += &quoted(l);
+            }
+        } else if l.is_empty() && in_msgstr {
+            add(&mut msgid, &mut msgstr, fuzzy);
+            fuzzy = false;
+            in_msgstr = false;
+        }
+    }
+    add(&mut msgid, &mut msgstr, fuzzy);
+    Ok(translations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paragraphs()
+    {
+        let text = "One\ntwo\n.PP\nThree\n.nf\ncode\n.fi\n";
+        assert_eq!(map_paragraphs(text, |p| Some(p.to_uppercase())), "ONE\nTWO\n.PP\nTHREE\n.nf\ncode\n.fi\n");
+        let mut found = Vec::new();
+        assert_eq!(map_paragraphs(text, |p| { found.push(p.to_string()); None }), text);
+        assert_eq!(found, ["One\ntwo", "Three"]);
+    }
+
+    #[test]
+    fn po_file()
+    {
+        let po_file = std::env::temp_dir().join(format!("doxygen2man-test-{}.po", std::process::id()));
+        fs::write(&po_file, r#"# A translation
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+#: test.h:10
+msgid "Open a \"test\""
+msgstr "Ouvrir un \"test\""
+
+#, fuzzy
+msgid "Close it"
+msgstr "Fermer"
+
+msgid ""
+"Two\n"
+"lines"
+msgstr ""
+"Deux\n"
+"lignes"
+
+msgid "Untranslated"
+msgstr ""
+"#).unwrap();
+        let translations = read_po(&po_file.to_string_lossy()).unwrap();
+        fs::remove_file(&po_file).unwrap();
+
+        assert_eq!(translations.len(), 2);
+        assert_eq!(translations["Open a \"test\""], "Ouvrir un \"test\"");
+        assert_eq!(translations["Two\nlines"], "Deux\nlignes");
+    }
+}
@@ -0,0 +1,120 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// A simple API for reading a whole doxygen XML directory
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use crate::parser::XmlEvent;
+
+use crate::model::*;
+use crate::parser::{get_attr, new_parser, read_header_xml};
+
+/// Everything doxygen2man knows about one header file
+pub struct Header
+{
+    /// The header file name, eg qbipcs.h
+    pub name: String,
+    /// The XML file it was read from, eg qbipcs_8h.xml
+    pub xml_file: String,
+    /// Brief, description and #defines for the header file itself
+    pub general: FunctionInfo,
+    pub functions: Vec<FunctionInfo>,
+    /// Documented callback (function pointer) typedefs
+    pub callbacks: Vec<FunctionInfo>,
+    /// Structures & enums used by the functions, keyed by doxygen refid
+    pub structures: BTreeMap<String, StructureInfo>,
+}
+
+/// All the headers in a doxygen XML directory
+pub struct Project
+{
+    pub xml_dir: String,
+    headers: Vec<Header>,
+}
+
+impl Project {
+    /// Load every header file listed in <xml_dir>/index.xml
+    pub fn load(xml_dir: &str) -> Result<Project, Error>
+    {
+        let xml_files = read_index(xml_dir)?;
+        Project::load_files(xml_dir, &xml_files)
+    }
+
+    /// Load just the named XML files (eg qbipcs_8h.xml) from xml_dir
+    pub fn load_files(xml_dir: &str, xml_files: &[String]) -> Result<Project, Error>
+    {
+        let mut headers = Vec::<Header>::new();
+        let mut structure_cache = BTreeMap::<String, StructureInfo>::new();
+
+        for xml_file in xml_files {
+            let mut name = String::from("unknown.h");
+            let (mut functions, structures) = read_header_xml(xml_dir, xml_file, &mut name, &mut structure_cache)?;
+
+            // The general page for the header is always the last one
+            let general = functions.pop().unwrap_or_default();
+            let (callbacks, functions) = functions.into_iter().partition(|f| f.fn_typedef);
+            headers.push(Header {name, xml_file: xml_file.clone(), general, functions, callbacks, structures});
+        }
+        Ok(Project {xml_dir: xml_dir.to_string(), headers})
+    }
+
+    pub fn headers(&self) -> impl Iterator<Item = &Header>
+    {
+        self.headers.iter()
+    }
+
+    /// All functions in all headers
+    pub fn functions(&self) -> impl Iterator<Item = &FunctionInfo>
+    {
+        self.headers.iter().flat_map(|h| h.functions.iter())
+    }
+
+    /// All structures & enums, with their refids. Structures used in
+    /// more than one header are only returned once.
+    pub fn structures(&self) -> impl Iterator<Item = (&String, &StructureInfo)>
+    {
+        let mut all = BTreeMap::<&String, &StructureInfo>::new();
+        for h in &self.headers {
+            for (refid, s) in &h.structures {
+                all.insert(refid, s);
+            }
+        }
+        all.into_iter()
+    }
+}
+
+/// Get the names of the XML files for all the headers from <xml_dir>/index.xml
+pub fn read_index(xml_dir: &str) -> Result<Vec<String>, Error>
+{
+    let index_file = format!("{xml_dir}/index.xml");
+    let f = match File::open(&index_file) {
+        Ok(f) => f,
+        Err(e) => return Err(Error::new(e.kind(), format!("Cannot open XML file {index_file}: {e}"))),
+    };
+    let parser = new_parser(f);
+    let mut xml_files = Vec::<String>::new();
+
+    for er in parser {
+        match er {
+            Ok(e) => {
+                if let XmlEvent::StartElement {name, ..} = &e {
+                    if name.local_name == "compound" && get_attr(&e, "kind") == "file" {
+                        xml_files.push(format!("{}.xml", get_attr(&e, "refid")));
+                    }
+                }
+            }
+            Err(e) => {
+                return Err(Error::new(ErrorKind::InvalidData, format!("Error reading XML for {index_file}: {e}")));
+            }
+        }
+    }
+    Ok(xml_files)
+}
@@ -0,0 +1,333 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// -a. A preview of what the man pages will have in them, on the terminal.
+// Names are bold and types dim (unless NO_COLOR is set or it isn't a
+// terminal) and the text is wrapped to $COLUMNS.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as fmtwrite;
+use std::io::{self, IsTerminal};
+
+use crate::cli::Opt;
+use crate::model::*;
+use crate::parser::strip_highlights;
+use super::{page_defines, synopsis_definition};
+
+// How far the text is indented under the section headings
+const INDENT: usize = 7;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Font {
+    Roman,
+    Bold,
+    Italic,
+}
+
+struct Terminal {
+    color: bool,
+    width: usize,
+}
+
+impl Terminal {
+    fn new() -> Terminal
+    {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let width = env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).unwrap_or(80);
+        Terminal {color: !no_color && io::stdout().is_terminal(), width: width.max(40)}
+    }
+
+    fn styled(&self, s: &str, font: Font) -> String
+    {
+        match font {
+            _ if !self.color || s.is_empty() => s.to_string(),
+            Font::Roman => s.to_string(),
+            Font::Bold => format!("\x1b[1m{s}\x1b[0m"),
+            Font::Italic => format!("\x1b[4m{s}\x1b[0m"),
+        }
+    }
+
+    fn dim(&self, s: &str) -> String
+    {
+        if self.color && !s.is_empty() {
+            format!("\x1b[2m{s}\x1b[0m")
+        } else {
+            s.to_string()
+        }
+    }
+
+    fn heading(&self, out: &mut String, title: &str)
+    {
+        let _ = writeln!(out, "{}", self.styled(title, Font::Bold));
+    }
+}
+
+// The characters of a line of troff text, with the fonts they are in
+fn troff_chars(s: &str) -> Vec<(char, Font)>
+{
+    let mut out = Vec::new();
+    let mut font = Font::Roman;
+    let s = strip_highlights(s);
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push((c, font));
+            continue;
+        }
+        match chars.next() {
+            Some('f') => {
+                font = match chars.next() {
+                    Some('B') => Font::Bold,
+                    Some('I') => Font::Italic,
+                    Some('(') => {
+                        // \f(CR etc, constant width is all the same here
+                        let _name: String = chars.by_ref().take(2).collect();
+                        Font::Roman
+                    }
+                    _ => Font::Roman,
+                };
+            }
+            Some('(') => {
+                let name: String = chars.by_ref().take(2).collect();
+                out.push((if name == "bu" { '\u{2022}' } else { '?' }, font));
+            }
+            Some('e') | Some('\\') => out.push(('\\', font)),
+            Some('&') => {}
+            Some(c) => out.push((c, font)),
+            None => {}
+        }
+    }
+    out
+}
+
+// Some characters with their fonts as text for the terminal
+fn styled_chars(term: &Terminal, chars: &[(char, Font)]) -> String
+{
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let font = chars[i].1;
+        let run: String = chars[i..].iter().take_while(|(_, f)| *f == font).map(|(c, _)| *c).collect();
+        i += run.chars().count();
+        out.push_str(&term.styled(&run, font));
+    }
+    out
+}
+
+// Print a paragraph of troff text wrapped to the width of the terminal.
+// first is put before the first line, eg a bullet, and is part of the indent.
+fn print_wrapped(out: &mut String, term: &Terminal, text: &str, indent: usize, first: &str)
+{
+    let chars = troff_chars(text);
+    let words: Vec<&[(char, Font)]> = chars.split(|(c, _)| *c == ' ').filter(|w| !w.is_empty()).collect();
+    let width = term.width.saturating_sub(indent).max(20);
+
+    let mut line = String::new();
+    let mut len = 0;
+    let mut prefix = format!("{:indent$}", first, indent = indent);
+    for w in words {
+        if len > 0 && len + 1 + w.len() > width {
+            let _ = writeln!(out, "{prefix}{line}");
+            prefix = " ".repeat(indent);
+            line.clear();
+            len = 0;
+        }
+        if len > 0 {
+            line.push(' ');
+            len += 1;
+        }
+        line.push_str(&styled_chars(term, w));
+        len += w.len();
+    }
+    if len > 0 {
+        let _ = writeln!(out, "{prefix}{line}");
+    }
+}
+
+// Print some text from the model: each line of text is a paragraph, program
+// listings are kept as they are, and the requests are left out
+fn print_text(out: &mut String, term: &Terminal, text: &str, indent: usize)
+{
+    let mut in_nf = false;
+    let mut need_blank = false;
+    for l in text.lines() {
+        if l.starts_with(".nf") || l.starts_with(".EX") {
+            in_nf = true;
+            continue;
+        }
+        if l.starts_with(".fi") || l.starts_with(".EE") {
+            in_nf = false;
+            continue;
+        }
+        if in_nf {
+            let _ = writeln!(out, "{:indent$}{}", "", styled_chars(term, &troff_chars(l)), indent = indent + 4);
+            continue;
+        }
+        if l.starts_with('.') || l.starts_with('\'') || l.trim().is_empty() {
+            need_blank = need_blank || l.starts_with(".PP") || l.starts_with(".sp");
+            continue;
+        }
+        if need_blank {
+            let _ = writeln!(out);
+            need_blank = false;
+        }
+        match l.strip_prefix("* ") {
+            Some(item) => print_wrapped(out, term, item.trim_start(), indent + 2, &format!("{:indent$}\u{2022} ", "", indent = indent)),
+            None => print_wrapped(out, term, l, indent, ""),
+        }
+    }
+}
+
+fn print_synopsis(out: &mut String, term: &Terminal, opt: &Opt, f: &FunctionInfo)
+{
+    term.heading(out, "SYNOPSIS");
+    let _ = writeln!(out, "{:indent$}{}", "", term.styled(&format!("#include <{}{}>", opt.header_prefix, opt.headerfile), Font::Bold), indent = INDENT);
+    if f.fn_def.is_empty() {
+        return;
+    }
+    let _ = writeln!(out);
+    let name = if f.fn_typedef {
+        term.styled(&synopsis_definition(f), Font::Bold)
+    } else {
+        format!("{} {}", term.dim(f.fn_type.trim()), term.styled(&f.fn_name, Font::Bold))
+    };
+    let params: Vec<String> = f.fn_args.iter().map(|p| {
+        if p.is_variadic() {
+            "...".to_string()
+        } else {
+            let sep = if p.par_type.ends_with('*') { "" } else { " " };
+            format!("{}{}{}{}", term.dim(&p.par_type), sep, term.styled(&p.par_name, Font::Italic), p.par_args)
+        }
+    }).collect();
+    let _ = writeln!(out, "{:indent$}{}({});", "", name, params.join(", "), indent = INDENT);
+}
+
+// The preview of one page
+fn write_preview(out: &mut String,
+                 term: &Terminal,
+                 opt: &Opt,
+                 f: &FunctionInfo,
+                 functions: &[FunctionInfo],
+                 structures: &BTreeMap<String, StructureInfo>)
+{
+    term.heading(out, "NAME");
+    if f.fn_brief.trim().is_empty() {
+        let _ = writeln!(out, "{:indent$}{}", "", term.styled(&f.fn_name, Font::Bold), indent = INDENT);
+    } else {
+        print_wrapped(out, term, &format!("\\fB{}\\fR - {}", f.fn_name, f.fn_brief.trim()), INDENT, "");
+    }
+    let _ = writeln!(out);
+    print_synopsis(out, term, opt, f);
+
+    let documented: Vec<&FnParam> = f.fn_args.iter().filter(|p| !p.par_desc.is_empty()).collect();
+    if !documented.is_empty() {
+        let _ = writeln!(out);
+        term.heading(out, "PARAMETERS");
+        for p in documented {
+            let _ = writeln!(out, "{:indent$}{}", "", term.styled(&p.par_name, Font::Italic), indent = INDENT);
+            print_text(out, term, &p.par_desc, INDENT + 4);
+        }
+    }
+    if !f.fn_detail.trim().is_empty() {
+        let _ = writeln!(out);
+        term.heading(out, "DESCRIPTION");
+        print_text(out, term, &f.fn_detail, INDENT);
+    }
+    if !f.fn_returnval.trim().is_empty() || !f.fn_retvals.is_empty() {
+        let _ = writeln!(out);
+        term.heading(out, "RETURN VALUE");
+        print_text(out, term, &f.fn_returnval, INDENT);
+        for rv in &f.fn_retvals {
+            let _ = writeln!(out, "{:indent$}{}", "", term.styled(rv.ret_name.trim(), Font::Bold), indent = INDENT);
+            print_text(out, term, &rv.ret_desc, INDENT + 4);
+        }
+    }
+    if !f.fn_note.trim().is_empty() {
+        let _ = writeln!(out);
+        term.heading(out, "NOTE");
+        print_text(out, term, &f.fn_note, INDENT);
+    }
+    for s in f.fn_simplesects.iter().filter(|s| s.ss_kind != "copyright") {
+        let _ = writeln!(out);
+        term.heading(out, &s.ss_kind.to_ascii_uppercase());
+        print_text(out, term, &s.ss_text, INDENT);
+    }
+    if !f.fn_attributes.is_empty() {
+        let _ = writeln!(out);
+        term.heading(out, "ATTRIBUTES");
+        let _ = writeln!(out, "{:indent$}{}", "", f.fn_attributes.join(", "), indent = INDENT);
+    }
+
+    let used: Vec<&StructureInfo> = f.fn_refids.iter().filter_map(|r| structures.get(r)).collect();
+    if !used.is_empty() {
+        let _ = writeln!(out);
+        term.heading(out, "STRUCTURES");
+        for s in used {
+            let kind = if let StructureType::Enum = s.str_type { "enum" } else { "struct" };
+            let _ = writeln!(out, "{:indent$}{} {}", "", term.dim(kind), term.styled(&s.str_name, Font::Bold), indent = INDENT);
+            if !s.str_brief.trim().is_empty() {
+                print_text(out, term, &s.str_brief, INDENT + 4);
+            }
+            for m in &s.str_members {
+                let sep = if m.par_type.is_empty() || m.par_type.ends_with('*') { "" } else { " " };
+                let _ = writeln!(out, "{:indent$}{}{}{}{}", "", term.dim(&m.par_type), sep,
+                         term.styled(&m.par_name, Font::Bold), m.par_args, indent = INDENT + 4);
+            }
+        }
+    }
+
+    let defines = page_defines(opt, f, functions);
+    if !defines.is_empty() {
+        let _ = writeln!(out);
+        term.heading(out, "DEFINES");
+        for d in defines {
+            let _ = writeln!(out, "{:indent$}{} {}", "", term.styled(&d.hd_name, Font::Bold), term.dim(&d.hd_init), indent = INDENT);
+            if !d.hd_brief.trim().is_empty() {
+                print_text(out, term, &d.hd_brief, INDENT + 4);
+            }
+        }
+    }
+}
+
+// The preview of a page as text, in colour or not, wrapped to width. For the browser.
+pub fn preview_page(opt: &Opt,
+                    f: &FunctionInfo,
+                    functions: &[FunctionInfo],
+                    structures: &BTreeMap<String, StructureInfo>,
+                    color: bool,
+                    width: usize) -> String
+{
+    let mut out = String::new();
+    write_preview(&mut out, &Terminal {color, width}, opt, f, functions, structures);
+    out
+}
+
+// Print the preview of one page
+pub fn print_text_function(opt: &Opt,
+                           f: &FunctionInfo,
+                           functions: &[FunctionInfo],
+                           structures: &BTreeMap<String, StructureInfo>)
+{
+    let term = Terminal::new();
+    let mut out = String::new();
+    write_preview(&mut out, &term, opt, f, functions, structures);
+    println!("{out}");
+    println!("{}", term.dim(&"-".repeat(term.width.min(72))));
+}
+
+// Preview all of the pages
+pub fn print_ascii_pages(opt: &Opt,
+                         functions: &[FunctionInfo],
+                         structures: &BTreeMap<String, StructureInfo>)
+{
+    for f in functions {
+        print_text_function(opt, f, functions, structures);
+    }
+}
@@ -0,0 +1,63 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --compat c. The pages are made as close as we can to the ones from the C
+// doxygen2man in libqb, so that the diffs when moving over are small enough to
+// read. Here the markup the C version doesn't have is taken out of the text;
+// the rest (no tidying, no links to the other pages and the commas in SEE ALSO)
+// is done where the pages are written.
+
+use crate::model::*;
+use crate::parser::CODE_FONT;
+
+// Take out the inline fonts for code and parameter names, eg "\f(CRNULL\fR"
+// becomes "NULL". Bold (\fB, from \e and \b) is left alone as the C version had that.
+fn legacy_markup(text: &str) -> String
+{
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    loop {
+        let start = [CODE_FONT, "\\fI"].iter()
+            .filter_map(|m| rest.find(m).map(|p| (p, m.len())))
+            .min();
+        let (pos, len) = match start {
+            Some(s) => s,
+            None => break,
+        };
+        let after = &rest[pos + len..];
+        match after.find("\\fR") {
+            Some(end) => {
+                out.push_str(&rest[..pos]);
+                out.push_str(&after[..end]);
+                rest = &after[end + 3..];
+            }
+            None => break,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// A copy of the function with legacy_markup() applied to all of its text
+pub fn legacy_function(function: &FunctionInfo) -> FunctionInfo
+{
+    let mut f = function.clone();
+    let mut texts = vec![&mut f.fn_brief, &mut f.fn_detail, &mut f.fn_internal_detail,
+                         &mut f.fn_returnval, &mut f.fn_note];
+    texts.extend(f.fn_args.iter_mut().map(|p| &mut p.par_desc));
+    texts.extend(f.fn_retvals.iter_mut().map(|r| &mut r.ret_desc));
+    texts.extend(f.fn_simplesects.iter_mut().map(|s| &mut s.ss_text));
+    texts.extend(f.fn_xrefsects.iter_mut().map(|x| &mut x.xr_desc));
+    for t in texts {
+        if t.contains('\\') {
+            *t = legacy_markup(t);
+        }
+    }
+    f
+}
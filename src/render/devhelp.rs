@@ -0,0 +1,113 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --devhelp. Writes HTML versions of the pages into a GNOME Devhelp book as
+// each header is read, with a chapter per header, and the .devhelp2 index
+// for them all at the end.
+
+use std::collections::BTreeMap;
+use std::io::{Error, Write};
+use std::path::Path;
+
+use crate::cli::Opt;
+use crate::state::RunState;
+use crate::model::*;
+use super::write_atomically;
+use super::html::{html_escape, html_page_name, print_html_pages};
+
+// A search keyword, kw_type is one of devhelp's types, eg function, struct, macro
+#[derive(Debug, Clone)]
+pub struct DevhelpKeyword {
+    pub kw_type: String,
+    pub name: String,
+    pub link: String,
+}
+
+// The pages and keywords for one header
+#[derive(Debug, Clone)]
+pub struct DevhelpChapter {
+    pub name: String,
+    pub link: String,
+    pub pages: Vec<String>,
+    pub keywords: Vec<DevhelpKeyword>,
+}
+
+// Write the HTML pages for one header into the book and return its chapter
+pub fn print_devhelp_pages(opt: &Opt,
+                           state: &RunState,
+                           functions: &[FunctionInfo],
+                           structures: &BTreeMap<String, StructureInfo>) -> Result<DevhelpChapter, Error>
+{
+    let written = print_html_pages(opt, state, Path::new(&opt.devhelp), functions, structures)?;
+
+    let mut chapter = DevhelpChapter {name: opt.headerfile.clone(), link: html_page_name(&opt.headerfile),
+                                      pages: Vec::new(), keywords: Vec::new()};
+    for f in written {
+        let link = html_page_name(&f.fn_name);
+        if f.fn_name == opt.headerfile {
+            for d in &f.fn_defines {
+                chapter.keywords.push(DevhelpKeyword {kw_type: "macro".to_string(), name: d.hd_name.clone(),
+                                                      link: format!("{}#{}", link, d.hd_name)});
+            }
+            continue;
+        }
+        chapter.pages.push(f.fn_name.clone());
+        if f.fn_typedef {
+            chapter.keywords.push(DevhelpKeyword {kw_type: "typedef".to_string(), name: f.fn_name.clone(), link});
+        } else {
+            chapter.keywords.push(DevhelpKeyword {kw_type: "function".to_string(), name: format!("{} ()", f.fn_name), link});
+        }
+    }
+
+    // Types don't have pages of their own, they point at the first function that uses them
+    for (refid, s) in structures {
+        let kw_type = match s.str_type {
+            StructureType::Struct => "struct",
+            StructureType::Enum => "enum",
+            StructureType::Unknown => continue,
+        };
+        if let Some(f) = functions.iter().find(|f| f.fn_refids.contains(refid)) {
+            chapter.keywords.push(DevhelpKeyword {kw_type: kw_type.to_string(), name: format!("{} {}", kw_type, s.str_name),
+                                                  link: format!("{}#{}", html_page_name(&f.fn_name), s.str_name)});
+        }
+    }
+    Ok(chapter)
+}
+
+// Write <package-name>.devhelp2, the index for the book, once all the pages are in it
+pub fn write_devhelp_book(opt: &Opt, chapters: &[DevhelpChapter]) -> Result<(), Error>
+{
+    let book_file = format!("{}/{}.devhelp2", opt.devhelp, opt.package_name);
+    let start = chapters.first().map(|c| c.link.as_str()).unwrap_or_default();
+
+    write_atomically(&book_file, |f| {
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+        writeln!(f, "<book xmlns=\"http://www.devhelp.net/book\" title=\"{} {}\" link=\"{}\" author=\"{}\" name=\"{}\" version=\"2\" language=\"c\">",
+                 html_escape(&opt.package_name), html_escape(&opt.header), html_escape(start),
+                 html_escape(&opt.company), html_escape(&opt.package_name))?;
+        writeln!(f, "  <chapters>")?;
+        for c in chapters {
+            writeln!(f, "    <sub name=\"{}\" link=\"{}\">", html_escape(&c.name), html_escape(&c.link))?;
+            for p in &c.pages {
+                writeln!(f, "      <sub name=\"{}\" link=\"{}\"/>", html_escape(p), html_escape(&html_page_name(p)))?;
+            }
+            writeln!(f, "    </sub>")?;
+        }
+        writeln!(f, "  </chapters>")?;
+        writeln!(f, "  <functions>")?;
+        for c in chapters {
+            for k in &c.keywords {
+                writeln!(f, "    <keyword type=\"{}\" name=\"{}\" link=\"{}\"/>",
+                         k.kw_type, html_escape(&k.name), html_escape(&k.link))?;
+            }
+        }
+        writeln!(f, "  </functions>")?;
+        writeln!(f, "</book>")
+    })
+}
@@ -0,0 +1,119 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --docset. Writes HTML versions of the pages into a Dash/Zeal docset as each
+// header is read, and the SQLite search index for them all at the end.
+// See https://kapeli.com/docsets#dashDocset for the layout.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, Write};
+use std::path::{Path, PathBuf};
+use rusqlite::{params, Connection};
+
+use crate::cli::Opt;
+use crate::state::RunState;
+use crate::model::*;
+use super::write_atomically;
+use super::html::{html_page_name, print_html_pages};
+
+// An entry in the search index. entry_type is one of Dash's types,
+// eg Function, Struct, Macro
+#[derive(Debug, Clone)]
+pub struct DocsetEntry {
+    pub name: String,
+    pub entry_type: String,
+    pub path: String,
+}
+
+fn documents_dir(opt: &Opt) -> PathBuf
+{
+    Path::new(&opt.docset).join("Contents").join("Resources").join("Documents")
+}
+
+// Write the HTML pages for one header into the docset and return the
+// search index entries for them
+pub fn print_docset_pages(opt: &Opt,
+                          state: &RunState,
+                          functions: &[FunctionInfo],
+                          structures: &BTreeMap<String, StructureInfo>) -> Result<Vec<DocsetEntry>, Error>
+{
+    let written = print_html_pages(opt, state, &documents_dir(opt), functions, structures)?;
+
+    let mut entries = Vec::new();
+    for f in written {
+        let page_name = html_page_name(&f.fn_name);
+        let entry_type = if f.fn_name == opt.headerfile {
+            "File"
+        } else if f.fn_typedef {
+            "Callback"
+        } else {
+            "Function"
+        };
+        entries.push(DocsetEntry {name: f.fn_name.clone(), entry_type: entry_type.to_string(), path: page_name.clone()});
+        for d in &f.fn_defines {
+            entries.push(DocsetEntry {name: d.hd_name.clone(), entry_type: "Macro".to_string(), path: format!("{}#{}", page_name, d.hd_name)});
+        }
+    }
+
+    // Types don't have pages of their own, they point at the first function that uses them
+    for (refid, s) in structures {
+        let entry_type = match s.str_type {
+            StructureType::Struct => "Struct",
+            StructureType::Enum => "Enum",
+            StructureType::Unknown => continue,
+        };
+        if let Some(f) = functions.iter().find(|f| f.fn_refids.contains(refid)) {
+            entries.push(DocsetEntry {name: s.str_name.clone(), entry_type: entry_type.to_string(),
+                                      path: format!("{}#{}", html_page_name(&f.fn_name), s.str_name)});
+        }
+    }
+    Ok(entries)
+}
+
+// Write the search index and Info.plist for the docset, once all the pages are in it
+pub fn write_docset_index(opt: &Opt, entries: &[DocsetEntry]) -> Result<(), Error>
+{
+    let resources = Path::new(&opt.docset).join("Contents").join("Resources");
+    fs::create_dir_all(&resources)?;
+
+    let plist_file = Path::new(&opt.docset).join("Contents").join("Info.plist");
+    let bundle_id = opt.package_name.to_ascii_lowercase();
+    write_atomically(&plist_file.to_string_lossy(), |f| {
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(f, "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">")?;
+        writeln!(f, "<plist version=\"1.0\">")?;
+        writeln!(f, "<dict>")?;
+        writeln!(f, "    <key>CFBundleIdentifier</key>\n    <string>{bundle_id}</string>")?;
+        writeln!(f, "    <key>CFBundleName</key>\n    <string>{}</string>", opt.package_name)?;
+        writeln!(f, "    <key>DocSetPlatformFamily</key>\n    <string>{bundle_id}</string>")?;
+        writeln!(f, "    <key>isDashDocset</key>\n    <true/>")?;
+        writeln!(f, "</dict>")?;
+        writeln!(f, "</plist>")
+    })?;
+
+    // The index is made from scratch each time
+    let index_file = resources.join("docSet.dsidx");
+    if index_file.exists() {
+        fs::remove_file(&index_file)?;
+    }
+    let to_io = |e: rusqlite::Error| Error::other(format!("Error writing docset index {}: {}", index_file.display(), e));
+    let mut conn = Connection::open(&index_file).map_err(to_io)?;
+    conn.execute_batch("CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT);
+                        CREATE UNIQUE INDEX anchor ON searchIndex (name, type, path);").map_err(to_io)?;
+    let tx = conn.transaction().map_err(to_io)?;
+    {
+        let mut insert = tx.prepare("INSERT OR IGNORE INTO searchIndex(name, type, path) VALUES (?1, ?2, ?3)").map_err(to_io)?;
+        for e in entries {
+            insert.execute(params![e.name, e.entry_type, e.path]).map_err(to_io)?;
+        }
+    }
+    tx.commit().map_err(to_io)?;
+    Ok(())
+}
@@ -0,0 +1,220 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Man pages for things that are documented somewhere else, eg another library.
+// <ref>s to these are written as page(section) and added to SEE ALSO.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind};
+
+use crate::parser::{get_attr, new_parser, XmlEvent};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalPage {
+    pub page: String,
+    pub section: String,
+}
+
+// Keyed on doxygen refid or symbol name
+#[derive(Debug, Default)]
+pub struct ExternalRefs {
+    pages: BTreeMap<String, ExternalPage>,
+}
+
+impl ExternalRefs {
+    // Read a --ref-map file. Each line is a refid or symbol name and the
+    // page for it, eg "qb_loop_create qb_loop_create(3)". Blank lines and
+    // lines starting with # are ignored.
+    pub fn read_ref_map(&mut self, map_file: &str) -> Result<(), Error>
+    {
+        let f = File::open(map_file)?;
+        for (n, l) in BufReader::new(f).lines().enumerate() {
+            let line = l?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let parsed = match (words.next(), words.next(), words.next()) {
+                (Some(key), Some(page), None) => parse_page(page).map(|p| (key, p)),
+                _ => None,
+            };
+            match parsed {
+                Some((key, page)) => {
+                    self.pages.insert(key.to_string(), page);
+                }
+                None => {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                                          format!("line {}: expected \"<refid or name> <page>(<section>)\"", n + 1)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Read a doxygen tag file (from another project's GENERATE_TAGFILE). Its functions
+    // and #defines are assumed to have pages of their own in 'section'. Things that
+    // are already known (eg from --ref-map) are left alone.
+    pub fn read_tagfile(&mut self, tag_file: &str, section: &str) -> Result<(), Error>
+    {
+        let f = File::open(tag_file)?;
+        let parser = new_parser(f);
+
+        // The member we are in, and the element in it we are reading
+        let mut in_member = false;
+        let mut element = String::new();
+        let mut name = String::new();
+        let mut anchorfile = String::new();
+        let mut anchor = String::new();
+        for er in parser {
+            match er {
+                Ok(e) => {
+                    match &e {
+                        XmlEvent::StartElement {name: n, ..} => {
+                            if n.local_name == "member" {
+                                let kind = get_attr(&e, "kind");
+                                in_member = kind == "function" || kind == "define";
+                                name.clear();
+                                anchorfile.clear();
+                                anchor.clear();
+                            }
+                            element = n.local_name.clone();
+                        }
+                        XmlEvent::Characters(s) if in_member => {
+                            match element.as_str() {
+                                "name" => name.push_str(s.trim()),
+                                "anchorfile" => anchorfile.push_str(s.trim()),
+                                "anchor" => anchor.push_str(s.trim()),
+                                _ => {}
+                            }
+                        }
+                        XmlEvent::EndElement {name: n} => {
+                            if n.local_name == "member" && in_member && !name.is_empty() {
+                                let page = ExternalPage {page: name.clone(), section: section.to_string()};
+                                // doxygen makes the refids for external members from these
+                                if !anchor.is_empty() {
+                                    let refid = format!("{}_1{}", anchorfile.trim_end_matches(".html"), anchor);
+                                    self.pages.entry(refid).or_insert_with(|| page.clone());
+                                }
+                                self.pages.entry(name.clone()).or_insert(page);
+                                in_member = false;
+                            }
+                            element.clear();
+                        }
+                        _ => {}
+                    }
+                }
+                Err(e) => {
+                    return Err(Error::new(ErrorKind::InvalidData, e.to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.pages.is_empty()
+    }
+
+    // Find the page for a <ref>, by its refid and then by its name
+    pub fn lookup(&self, refid: &str, name: &str) -> Option<&ExternalPage>
+    {
+        self.pages.get(refid).or_else(|| self.pages.get(name.trim_end_matches("()")))
+    }
+}
+
+// "name(section)" -> ExternalPage
+fn parse_page(s: &str) -> Option<ExternalPage>
+{
+    let open = s.find('(')?;
+    let section = s[open + 1..].strip_suffix(')')?;
+    if open == 0 || section.is_empty() {
+        return None;
+    }
+    Some(ExternalPage {page: s[..open].to_string(), section: section.to_string()})
+}
+
+fn is_ident_char(c: char) -> bool
+{
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Whether some text ends with a font change, eg \fB or \f(CR, so that the letter
+// before a word is part of that and not of the word
+fn ends_with_font(text: &str) -> bool
+{
+    let b = text.as_bytes();
+    (b.len() >= 3 && &b[b.len() - 3..b.len() - 1] == b"\\f") ||
+        (b.len() >= 5 && &b[b.len() - 5..b.len() - 2] == b"\\f(")
+}
+
+// Replace the word 'name' (and any () after it) in a line with the reference to 'page'.
+// If the name is something else, eg the text of a \link, the reference goes after it.
+fn link_word(line: &str, name: &str, page: &ExternalPage) -> String
+{
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().next_back();
+        let mut end = pos + name.len();
+        let after = rest[end..].chars().next();
+        if (before.is_some_and(is_ident_char) && !ends_with_font(&rest[..pos])) || after.is_some_and(is_ident_char) {
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        if rest[end..].starts_with("()") {
+            end += 2;
+        }
+        out.push_str(&rest[..pos]);
+        if name != page.page {
+            out.push_str(&format!("{name} ("));
+        }
+        out.push_str(&format!("\\fB{}\\fP({})", page.page, page.section));
+        if name != page.page {
+            out.push(')');
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// Turn the names of the external refs in some text into references to their pages.
+// Program listings (.nf to .fi) are left alone.
+pub fn link_external_refs(text: &str, refs: &[(&str, &ExternalPage)]) -> String
+{
+    if refs.is_empty() {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut in_nf = false;
+    for (i, l) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        if l.starts_with(".nf") {
+            in_nf = true;
+        } else if l.starts_with(".fi") {
+            in_nf = false;
+        }
+        if in_nf || l.starts_with('.') {
+            out.push_str(l);
+            continue;
+        }
+        let mut line = l.to_string();
+        for (name, page) in refs {
+            line = link_word(&line, name, page);
+        }
+        out.push_str(&line);
+    }
+    out
+}
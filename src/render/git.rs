@@ -0,0 +1,77 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Dates from the git history of the header files, so they don't have to be
+// kept up to date by hand in the Makefiles. If the header isn't in a git
+// repository (eg in a release tarball) or git isn't installed, these return
+// None and the usual options are used.
+
+use std::path::Path;
+use std::process::Command;
+use chrono::prelude::*;
+
+// The times of the commits that touched a file, newest first. format is the
+// git log placeholder for the time, eg %at for the author's or %ct for the committer's.
+fn commit_times(path: &Path, format: &str) -> Option<Vec<DateTime<Utc>>>
+{
+    let dir = match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let output = Command::new("git")
+        .arg("-C").arg(dir)
+        .arg("log").arg(format!("--format={format}"))
+        .arg("--").arg(path.file_name()?)
+        .output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let times: Vec<DateTime<Utc>> = String::from_utf8_lossy(&output.stdout).lines()
+        .filter_map(|l| l.trim().parse::<i64>().ok())
+        .filter_map(|secs| Utc.timestamp_opt(secs, 0).single())
+        .collect();
+    if times.is_empty() {
+        None
+    } else {
+        Some(times)
+    }
+}
+
+// The name of the commit a directory is at, from git describe (eg "v2.0.3-12-gabc1234")
+pub fn describe(dir: &Path) -> Option<String>
+{
+    let output = Command::new("git")
+        .arg("-C").arg(dir)
+        .arg("describe").arg("--tags").arg("--always")
+        .output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if version.is_empty() {
+        None
+    } else {
+        Some(version)
+    }
+}
+
+// The date of the last commit to a file, for the top of the pages
+pub fn last_commit_date(path: &Path) -> Option<NaiveDate>
+{
+    commit_times(path, "%ct")?.first().map(|t| t.date_naive())
+}
+
+// The years of the first and last commits to a file, for the copyright line
+pub fn copyright_years(path: &Path) -> Option<(i32, i32)>
+{
+    let times = commit_times(path, "%at")?;
+    let first = times.iter().map(|t| t.year()).min()?;
+    let last = times.iter().map(|t| t.year()).max()?;
+    Some((first, last))
+}
@@ -0,0 +1,417 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// HTML versions of the pages, for docsets. The text in the model already
+// has troff markup in it, so that is turned into HTML as we go.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as fmtwrite;
+use std::fs;
+use std::io::{Error, Write};
+use std::path::Path;
+
+use crate::cli::Opt;
+use crate::state::RunState;
+use crate::model::*;
+use crate::parser::{split_highlight, strip_highlights};
+use crate::timings::Phase;
+use super::tidy::tidy_html_page;
+use super::{SIMPLESECT_SECTIONS, deprecation_note, page_copyright, page_defines, param_direction, function_detail, is_noreturn, param_description, get_date_and_copyright, synopsis_definition, synopsis_prototype, write_atomically};
+
+pub fn html_escape(s: &str) -> String
+{
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// Convert the troff font changes and escapes in a line of text to HTML
+pub fn troff_inline_to_html(s: &str) -> String
+{
+    let mut out = String::new();
+    let mut font: Option<&str> = None;
+    let s = strip_highlights(s);
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                _ => out.push(c),
+            }
+            continue;
+        }
+        match chars.next() {
+            Some('f') => {
+                if let Some(f) = font.take() {
+                    let _ = write!(out, "</{f}>");
+                }
+                font = match chars.next() {
+                    Some('B') => Some("b"),
+                    Some('I') => Some("i"),
+                    Some('(') => {
+                        // Two letter names, the C ones are constant-width
+                        let name: String = chars.by_ref().take(2).collect();
+                        if name.starts_with('C') { Some("code") } else { None }
+                    }
+                    _ => None,
+                };
+                if let Some(f) = font {
+                    let _ = write!(out, "<{f}>");
+                }
+            }
+            Some('-') => out.push('-'),
+            Some('e') | Some('\\') => out.push('\\'),
+            Some('&') => {}
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    if let Some(f) = font {
+        let _ = write!(out, "</{f}>");
+    }
+    out
+}
+
+// Colours for doxygen's kinds of highlighting in program listings
+const HIGHLIGHT_STYLE: &str = "<style>
+pre .keyword, pre .keywordflow { color: #7f0055; font-weight: bold; }
+pre .keywordtype { color: #005f87; font-weight: bold; }
+pre .preprocessor { color: #806020; }
+pre .comment { color: #3f7f5f; font-style: italic; }
+pre .stringliteral, pre .charliteral { color: #2a00ff; }
+</style>";
+
+// A line of a program listing. The parser made doxygen's highlighted text bold,
+// with the kind of highlight it was, that gets a <span> for the stylesheet.
+fn listing_line_to_html(l: &str) -> String
+{
+    let mut out = String::new();
+    let mut rest = l;
+    while let Some(p) = rest.find("\\fB") {
+        out.push_str(&troff_inline_to_html(&rest[..p]));
+        let bold = &rest[p + 3..];
+        let end = bold.find("\\fR").unwrap_or(bold.len());
+        let (class, text) = split_highlight(&bold[..end]);
+        match class {
+            Some(class) => {
+                let _ = write!(out, "<span class=\"{}\">{}</span>", html_escape(class), troff_inline_to_html(text));
+            }
+            None => {
+                let _ = write!(out, "<b>{}</b>", troff_inline_to_html(text));
+            }
+        }
+        rest = &bold[(end + 3).min(bold.len())..];
+    }
+    out.push_str(&troff_inline_to_html(rest));
+    out
+}
+
+// Convert a block of text with troff requests in it (.nf, .PP etc) to HTML
+pub fn troff_to_html(s: &str) -> String
+{
+    let mut out = String::new();
+    let mut in_nf = false;
+    let mut in_para = false;
+
+    for l in s.lines() {
+        if l.starts_with(".nf") {
+            if in_para {
+                out.push_str("</p>\n");
+                in_para = false;
+            }
+            out.push_str("<pre>");
+            in_nf = true;
+        } else if l.starts_with(".fi") {
+            out.push_str("</pre>\n");
+            in_nf = false;
+        } else if in_nf {
+            let _ = writeln!(out, "{}", listing_line_to_html(l));
+        } else if l.starts_with(".SH ") || l.starts_with(".SS ") {
+            if in_para {
+                out.push_str("</p>\n");
+                in_para = false;
+            }
+            let h = if l.starts_with(".SH") { "h2" } else { "h3" };
+            let _ = writeln!(out, "<{}>{}</{}>", h, troff_inline_to_html(&l[4..]), h);
+        } else if l.starts_with(".PP") || l.trim().is_empty() {
+            if in_para {
+                out.push_str("</p>\n");
+                in_para = false;
+            }
+        } else if l.starts_with(".RS") || l.starts_with(".RE") {
+            if in_para {
+                out.push_str("</p>\n");
+                in_para = false;
+            }
+            out.push_str(if l.starts_with(".RS") { "<blockquote>\n" } else { "</blockquote>\n" });
+        } else if l.starts_with(".br") {
+            out.push_str("<br>\n");
+        } else if l.starts_with('.') {
+            // Other requests (eg .TP) don't have an HTML equivalent we need
+        } else {
+            if !in_para {
+                out.push_str("<p>");
+                in_para = true;
+            }
+            let _ = writeln!(out, "{}", troff_inline_to_html(l));
+        }
+    }
+    if in_nf {
+        out.push_str("</pre>\n");
+    }
+    if in_para {
+        out.push_str("</p>\n");
+    }
+    out
+}
+
+// The C definition of a structure or enum, to go in a <pre>
+fn structure_definition_html(si: &StructureInfo) -> String
+{
+    let mut out = String::new();
+    let keyword = match si.str_type {
+        StructureType::Enum => "enum",
+        StructureType::Struct => "struct",
+        StructureType::Unknown => "???",
+    };
+    let delim = match si.str_type {
+        StructureType::Enum => ",",
+        _ => ";",
+    };
+    let _ = writeln!(out, "<b>{} {}</b> {{", keyword, html_escape(&si.str_name));
+    for (i, m) in si.str_members.iter().enumerate() {
+        let d = if i + 1 == si.str_members.len() && delim == "," { "" } else { delim };
+        out.push_str("    ");
+        if !m.par_type.is_empty() {
+            let _ = write!(out, "{} ", html_escape(&m.par_type));
+        }
+        let _ = write!(out, "<i>{}</i>{}{}", html_escape(&m.par_name), html_escape(&m.par_args), d);
+        if !m.par_desc.is_empty() {
+            let _ = write!(out, " /* {} */", troff_inline_to_html(&m.par_desc));
+        }
+        out.push('\n');
+    }
+    out.push_str("};");
+    out
+}
+
+// The HTML file name for a page
+pub fn html_page_name(name: &str) -> String
+{
+    format!("{name}.html")
+}
+
+// Make the HTML version of the man page for a function (or the general page for a header)
+pub fn render_html_page(opt: &Opt,
+                        function: &FunctionInfo,
+                        functions: &[FunctionInfo],
+                        structures: &BTreeMap<String, StructureInfo>,
+                        copyright: &str) -> String
+{
+    let copyright = &page_copyright(opt, function, functions, copyright);
+    let mut out = String::new();
+    let name = html_escape(&function.fn_name);
+
+    let _ = writeln!(out, "<!DOCTYPE html>");
+    let _ = writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{name}</title>\n{HIGHLIGHT_STYLE}\n</head>\n<body>");
+    let _ = writeln!(out, "<h1>{name}</h1>");
+    if !function.fn_brief.trim().is_empty() {
+        let _ = writeln!(out, "<p>{} - {}</p>", name, troff_inline_to_html(function.fn_brief.trim()));
+    }
+
+    let _ = writeln!(out, "<h2>SYNOPSIS</h2>");
+    let _ = write!(out, "<pre><b>#include &lt;{}{}&gt;</b>", html_escape(&opt.header_prefix), html_escape(&opt.headerfile));
+    if let Some(lines) = synopsis_prototype(opt, function).filter(|_| !function.fn_def.is_empty()) {
+        out.push_str("\n\n");
+        let lines: Vec<String> = lines.iter().map(|l| troff_inline_to_html(l)).collect();
+        out.push_str(&lines.join("\n"));
+    } else if !function.fn_def.is_empty() {
+        let _ = writeln!(out, "\n\n<b>{}</b>(", html_escape(&synopsis_definition(function)));
+        for (i, p) in function.fn_args.iter().enumerate() {
+            let delim = if i + 1 == function.fn_args.len() { "" } else { "," };
+            if p.is_variadic() {
+                let _ = writeln!(out, "    ...{delim}");
+                continue;
+            }
+            let defval = if p.par_defval.is_empty() { String::new() } else { format!(" = {}", p.par_defval) };
+            let _ = writeln!(out, "    {} <i>{}</i>{}{}{}", html_escape(&p.par_type), html_escape(&p.par_name), html_escape(&p.par_args), html_escape(&defval), delim);
+        }
+        out.push_str(");");
+    }
+    let _ = writeln!(out, "</pre>");
+
+    let descs: Vec<String> = (0..function.fn_args.len()).map(|i| param_description(function, i)).collect();
+    if opt.print_params && opt.params_table && descs.iter().any(|d| !d.is_empty()) {
+        let directions = function.fn_args.iter().any(|p| !p.par_direction.is_empty());
+        let _ = writeln!(out, "<h2>PARAMETERS</h2>\n<table>");
+        let _ = writeln!(out, "<tr><th>Name</th>{}<th>Description</th></tr>", if directions { "<th>Direction</th>" } else { "" });
+        for (p, desc) in function.fn_args.iter().zip(&descs) {
+            if p.is_variadic() && desc.is_empty() {
+                continue;
+            }
+            let name = if p.is_variadic() { "..." } else { p.par_name.as_str() };
+            let direction = if directions { format!("<td>{}</td>", param_direction(p)) } else { String::new() };
+            let _ = writeln!(out, "<tr><td><b>{}</b></td>{}<td>{}</td></tr>", html_escape(name), direction, troff_inline_to_html(desc));
+        }
+        let _ = writeln!(out, "</table>");
+    } else if opt.print_params && descs.iter().any(|d| !d.is_empty()) {
+        let _ = writeln!(out, "<h2>PARAMETERS</h2>\n<dl>");
+        for (p, desc) in function.fn_args.iter().zip(&descs) {
+            if p.is_variadic() && desc.is_empty() {
+                continue;
+            }
+            let name = if p.is_variadic() { "..." } else { p.par_name.as_str() };
+            let _ = writeln!(out, "<dt><b>{}</b></dt><dd>{}</dd>", html_escape(name), troff_inline_to_html(desc));
+        }
+        let _ = writeln!(out, "</dl>");
+    }
+
+    let detail = function_detail(opt, function);
+    if !detail.is_empty() {
+        let _ = writeln!(out, "<h2>DESCRIPTION</h2>");
+        out.push_str(&troff_to_html(&detail));
+    }
+
+    // Each structure has an id so the docset index can point at it
+    let used: Vec<&StructureInfo> = function.fn_refids.iter().filter_map(|r| structures.get(r)).collect();
+    if !used.is_empty() {
+        let _ = writeln!(out, "<h2>STRUCTURES</h2>");
+        for s in used {
+            let _ = writeln!(out, "<div id=\"{}\">", html_escape(&s.str_name));
+            if !s.str_brief.is_empty() {
+                out.push_str(&troff_to_html(&s.str_brief));
+            }
+            if !s.str_description.is_empty() {
+                out.push_str(&troff_to_html(&s.str_description));
+            }
+            let _ = writeln!(out, "<pre>{}</pre>\n</div>", structure_definition_html(s));
+        }
+    }
+
+    let noreturn = is_noreturn(function) && function.fn_returnval.is_empty();
+    if !function.fn_returnval.is_empty() || !function.fn_retvals.is_empty() || noreturn {
+        let _ = writeln!(out, "<h2>RETURN VALUE</h2>");
+        out.push_str(&troff_to_html(&function.fn_returnval));
+        if noreturn {
+            let _ = writeln!(out, "<p>This function does not return.</p>");
+        }
+        if !function.fn_retvals.is_empty() && opt.retval_table {
+            let _ = writeln!(out, "<table>\n<tr><th>Value</th><th>Meaning</th></tr>");
+            for rv in &function.fn_retvals {
+                let _ = writeln!(out, "<tr><td><b>{}</b></td><td>{}</td></tr>", html_escape(rv.ret_name.trim()), troff_inline_to_html(rv.ret_desc.trim()));
+            }
+            let _ = writeln!(out, "</table>");
+        } else if !function.fn_retvals.is_empty() {
+            let _ = writeln!(out, "<dl>");
+            for rv in &function.fn_retvals {
+                let _ = writeln!(out, "<dt><b>{}</b></dt><dd>{}</dd>", html_escape(&rv.ret_name), troff_inline_to_html(&rv.ret_desc));
+            }
+            let _ = writeln!(out, "</dl>");
+        }
+    }
+
+    let defines = if function.fn_name != opt.headerfile && opt.link_defines {
+        page_defines(opt, function, functions)
+    } else {
+        function.fn_defines.iter().collect()
+    };
+    if !defines.is_empty() && !opt.minimal {
+        let _ = writeln!(out, "<h2>DEFINES</h2>\n<dl>");
+        for d in defines {
+            let _ = write!(out, "<dt id=\"{}\"><code>#define {} {}</code></dt><dd>",
+                           html_escape(&d.hd_name), html_escape(&d.hd_name), html_escape(&d.hd_init));
+            out.push_str(&troff_inline_to_html(d.hd_brief.trim()));
+            if !d.hd_desc.is_empty() {
+                let _ = write!(out, " {}", troff_inline_to_html(d.hd_desc.trim()));
+            }
+            let _ = writeln!(out, "</dd>");
+        }
+        let _ = writeln!(out, "</dl>");
+    }
+
+    if let Some(note) = deprecation_note(function) {
+        let _ = writeln!(out, "<h2>DEPRECATED</h2>\n<p>{}</p>", html_escape(&note));
+    }
+
+    if !function.fn_note.is_empty() {
+        let _ = writeln!(out, "<h2>NOTE</h2>");
+        out.push_str(&troff_to_html(&function.fn_note));
+    }
+
+    for (kind, title) in SIMPLESECT_SECTIONS {
+        let mut sects = function.fn_simplesects.iter().filter(|s| s.ss_kind == kind).peekable();
+        if sects.peek().is_some() {
+            let _ = writeln!(out, "<h2>{title}</h2>");
+        }
+        for s in sects {
+            out.push_str(&troff_to_html(&s.ss_text));
+        }
+    }
+
+    if opt.version_section && !opt.source_version.is_empty() {
+        let _ = writeln!(out, "<h2>VERSION</h2>\n<p>This page documents {} {}.</p>",
+                         html_escape(&opt.package_name), html_escape(&opt.source_version));
+    }
+
+    let others: Vec<&FunctionInfo> = functions.iter().filter(|f| f.fn_name != function.fn_name).collect();
+    if !others.is_empty() && !opt.minimal {
+        let _ = writeln!(out, "<h2>SEE ALSO</h2>\n<p>");
+        for (i, f) in others.iter().enumerate() {
+            let delim = if i + 1 == others.len() { "" } else { "," };
+            let _ = writeln!(out, "<a href=\"{}\">{}</a>{}", html_escape(&html_page_name(&f.fn_name)), html_escape(&f.fn_name), delim);
+        }
+        let _ = writeln!(out, "</p>");
+    }
+
+    if !copyright.is_empty() && !opt.minimal {
+        let _ = writeln!(out, "<h2>COPYRIGHT</h2>\n<p>{}</p>", html_escape(copyright).replace('\n', "<br>\n"));
+    }
+
+    let _ = writeln!(out, "</body>\n</html>");
+    out
+}
+
+// Write the HTML pages for one header into dir, returns the functions
+// that have pages (ie not left out by --only)
+pub fn print_html_pages<'a>(opt: &Opt,
+                            state: &RunState,
+                            dir: &Path,
+                            functions: &'a [FunctionInfo],
+                            structures: &BTreeMap<String, StructureInfo>) -> Result<Vec<&'a FunctionInfo>, Error>
+{
+    fs::create_dir_all(dir)?;
+    let (_date, copyright) = get_date_and_copyright(opt).map_err(Error::other)?;
+
+    let mut written = Vec::new();
+    for f in functions {
+        if f.fn_name != opt.headerfile && !opt.only.is_empty() && !opt.only.contains(&f.fn_name) {
+            continue;
+        }
+
+        let mut page = state.timer.time(Phase::Render, || render_html_page(opt, f, functions, structures, &copyright));
+        if !opt.no_tidy {
+            page = tidy_html_page(&page);
+        }
+        let page_file = dir.join(html_page_name(&f.fn_name));
+        state.timer.time(Phase::Write, || write_atomically(&page_file.to_string_lossy(), |w| w.write_all(page.as_bytes())))?;
+        written.push(f);
+    }
+    Ok(written)
+}
@@ -0,0 +1,1540 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+use std::collections::BTreeMap;
+use std::fmt::Write as fmtwrite;
+use std::io::{Error, Write};
+use std::path::Path;
+
+use crate::cli::Opt;
+use crate::state::RunState;
+use crate::model::*;
+use crate::parser::{strip_highlights, HIGHLIGHT_START};
+use crate::timings::Phase;
+use super::external::{link_external_refs, ExternalPage};
+use super::compat::legacy_function;
+use super::symbols::normalize_symbols;
+use super::tidy::tidy_man_page;
+use super::wrap::wrap_man_page;
+use super::{add_options_hash, content_hash, old_names, RemovedFunction, GENERATED_MARKER, SIMPLESECT_SECTIONS, TBL_PREPROCESSOR_LINE, UTF8_CODING_LINE, needs_tbl, page_copyright, page_defines, page_source, param_direction, deprecation_note, function_detail, is_nonnull, is_noreturn, param_description, defines_page_name, has_type_page, synopsis_definition, synopsis_prototype, compact_prototype, is_generated_page, is_up_to_date, wants_page, write_atomically, ManPage, PageKind};
+#[cfg(feature = "templates")]
+use super::template::render_template;
+
+// This defines how long a parameter type can get before we
+// decide it's not worth lining everything up.
+// It's mainly to stop function pointer types (which can get VERY long because
+// of all *their* parameters) making everything else 'line-up' over separate lines
+
+const MAX_PRINT_PARAM_LEN: usize = 80;
+
+// Similar for structure member comments
+const MAX_STRUCT_COMMENT_LEN: usize = 50;
+
+// Return the length of a string ignoring any formatting
+fn len_without_formatting(param: &str) -> usize
+{
+    let mut length = 0;
+    let mut last_was_escape = false;
+    let mut chars = param.chars();
+    while let Some(i) = chars.next() {
+	if i == '\\' {
+	    last_was_escape = true;
+	} else if last_was_escape {
+	    last_was_escape = false;
+	    // \f(CR has a two letter font name
+	    if i == 'f' && chars.clone().next() == Some('(') {
+		chars.nth(2);
+	    }
+	} else {
+	    length += 1;
+	}
+    }
+    length
+}
+
+// Print a listing line, .EX/.EE with --example-macros
+fn print_listing_line<W: Write>(f: &mut W, opt: &Opt, l: &str) -> Result<(), std::io::Error>
+{
+    if opt.example_macros && l.starts_with(".nf") {
+        writeln!(f, ".EX")
+    } else if opt.example_macros && l.starts_with(".fi") {
+        writeln!(f, ".EE")
+    } else {
+        writeln!(f, "{l}")
+    }
+}
+
+// --reflow paragraphs or full. Each line of the text is a paragraph (doxygen doesn't
+// break lines inside them), "paragraphs" starts a new one for each of them and
+// "full" only at blank lines, so the rest are filled together.
+fn print_reflowed_string<W: Write>(f: &mut W, opt: &Opt, s: &str, full: bool) -> Result<(), std::io::Error>
+{
+    let mut in_nf = false;
+    // The caller has started the first paragraph
+    let mut need_pp = false;
+
+    for l in s.lines() {
+        if in_nf || l.starts_with(".nf") {
+            if l.starts_with(".nf") {
+                writeln!(f, ".PP")?;
+                in_nf = true;
+            }
+            print_listing_line(f, opt, l)?;
+            if l.starts_with(".fi") {
+                in_nf = false;
+                need_pp = true;
+            }
+            continue;
+        }
+        if l.trim().is_empty() {
+            if full {
+                need_pp = true;
+            }
+            continue;
+        }
+        if l.starts_with('.') {
+            writeln!(f, "{l}")?;
+            // These start paragraphs of their own
+            need_pp = !(l.starts_with(".SH") || l.starts_with(".SS") || l.starts_with(".PP") ||
+                        l.starts_with(".IP") || l.starts_with(".TP"));
+            continue;
+        }
+        if let Some(item) = l.strip_prefix("* ") {
+            writeln!(f, ".IP \\(bu 2")?;
+            writeln!(f, "{}", item.trim_start())?;
+            need_pp = true;
+            continue;
+        }
+        if need_pp {
+            writeln!(f, ".PP")?;
+        }
+        writeln!(f, "{l}")?;
+        need_pp = !full;
+    }
+    Ok(())
+}
+
+// Number the lines of the program listings (between .nf and .fi) in some text,
+// for --listing-line-numbers
+fn number_listing_lines(s: &str) -> String
+{
+    let lines: Vec<&str> = s.lines().collect();
+    let mut out = Vec::<String>::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        out.push(lines[i].to_string());
+        if !lines[i].starts_with(".nf") {
+            i += 1;
+            continue;
+        }
+        let end = lines[i + 1..].iter().position(|l| l.starts_with(".fi")).map_or(lines.len(), |p| i + 1 + p);
+        let width = (end - i - 1).to_string().len();
+        for (n, l) in lines[i + 1..end].iter().enumerate() {
+            out.push(format!("{:>width$}  {}", n + 1, l));
+        }
+        i = end;
+    }
+    let mut result = out.join("\n");
+    if s.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+// Format a long description string
+fn print_long_string<W: Write>(f: &mut W, opt: &Opt, s: &str) -> Result<(), std::io::Error>
+{
+    let numbered;
+    let s = if opt.listing_line_numbers {
+        numbered = number_listing_lines(s);
+        numbered.as_str()
+    } else {
+        s
+    };
+    match opt.reflow.as_str() {
+        "paragraphs" => return print_reflowed_string(f, opt, s, false),
+        "full" => return print_reflowed_string(f, opt, s, true),
+        _ => {}
+    }
+    let mut in_nf = false;
+
+    // Check for .nf / .fi and don't format those!
+    for l in s.lines() {
+        if l.starts_with(".nf") {
+            writeln!(f)?;
+            in_nf = true;
+        }
+
+        print_listing_line(f, opt, l)?;
+
+        if !in_nf {
+            writeln!(f,".PP")?;
+        }
+
+        if l.starts_with(".fi") {
+            writeln!(f)?;
+            in_nf = false;
+        }
+    }
+    Ok(())
+}
+
+fn print_long_structure_comment<W: Write>(f: &mut W, comment: &str) -> Result<(), std::io::Error>
+{
+    writeln!(f, "    \\fP/*")?;
+    write!(f, "     *")?;
+
+    let mut column = 7;
+    for word in comment.split_whitespace() {
+	column += word.len();
+	if column > 80 {
+	    write!(f, "\n     *")?;
+	    column = 7;
+	}
+	write!(f, " {word}")?;
+    }
+    writeln!(f, "\n     */")?;
+    Ok(())
+}
+
+// Prints a structure member or a function param given
+// a field width. Also reformats pointers to look nicer (IMHO)
+fn print_param<W: Write>(f: &mut W, pi: &FnParam, type_field_width: usize,
+                         name_field_width: usize, bold: bool, delimeter: String) -> Result<(), std::io::Error>
+{
+    // Nothing to line up for the ...
+    if pi.is_variadic() {
+        writeln!(f, "    ...{delimeter}")?;
+        return Ok(());
+    }
+
+    let mut asterisks = "  ".to_string();
+    let mut formatted_type = pi.par_type.clone();
+    let typelen: usize = formatted_type.len();
+
+    // Reformat pointer params so they look nicer
+    // these unwrap()s are safe because we check the length before doing the get()
+    if !formatted_type.is_empty() && formatted_type.get(typelen-1..typelen).unwrap() == "*" {
+        asterisks = " *".to_string();
+        formatted_type = pi.par_type.get(..typelen-1).unwrap().to_string();
+
+        // Cope with double pointers
+        if typelen > 1 && formatted_type.get(typelen-2..typelen-1).unwrap() == "*" {
+            asterisks = "**".to_string();
+            formatted_type = pi.par_type.get(..typelen-2).unwrap().to_string();
+        } else {
+            // Tidy function pointers
+            if typelen > 1 && formatted_type.get(typelen-2..typelen-1).unwrap() == "(" {
+                asterisks = "(*".to_string();
+                formatted_type = pi.par_type.get(..typelen-2).unwrap().to_string();
+            }
+	}
+    }
+
+    // Put long comments on their own line for clarity
+    let comment_len = len_without_formatting(&pi.par_desc);
+    if comment_len > MAX_STRUCT_COMMENT_LEN {
+	print_long_structure_comment(f, &pi.par_desc)?;
+    }
+
+    if bold {
+        write!(f, "    \\fB")?;
+    } else {
+        write!(f, "    \\fR")?;
+    }
+    let defval = if pi.par_defval.is_empty() { String::new() } else { format!(" = {}", pi.par_defval) };
+    write!(f, "{:<width$}{}\\fI{}\\fB{}\\fR{}{}",
+           formatted_type, asterisks,
+           pi.par_name, pi.par_args, defval, delimeter, width=type_field_width)?;
+
+    // Field description */
+    if comment_len > 0 && comment_len <= MAX_STRUCT_COMMENT_LEN && name_field_width > 0 {
+	let pad_width = 1 + (name_field_width - pi.par_name.len() - pi.par_args.len()) - delimeter.len();
+	write!(f, "\\fP {:>width$} /* {} */", "", pi.par_desc, width=pad_width)?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+// --params-table, the parameters in a tbl table. There is only a direction
+// column if some of them have one.
+fn print_parameter_table<W: Write>(f: &mut W, function: &FunctionInfo, ext_refs: &[(&str, &ExternalPage)]) -> Result<(), std::io::Error>
+{
+    let directions = function.fn_args.iter().any(|p| !p.par_direction.is_empty());
+    writeln!(f, ".PP")?;
+    writeln!(f, ".TS")?;
+    if directions {
+        writeln!(f, "lB lB lB")?;
+        writeln!(f, "lB l lx.")?;
+        writeln!(f, "Name\tDirection\tDescription")?;
+    } else {
+        writeln!(f, "lB lB")?;
+        writeln!(f, "lB lx.")?;
+        writeln!(f, "Name\tDescription")?;
+    }
+    writeln!(f, "_")?;
+    for (i, p) in function.fn_args.iter().enumerate() {
+        let desc = param_description(function, i);
+        if p.is_variadic() && desc.is_empty() {
+            continue;
+        }
+        let name = if p.is_variadic() { "..." } else { p.par_name.as_str() };
+        if directions {
+            write!(f, "{}\t{}\t", name, param_direction(p))?;
+        } else {
+            write!(f, "{name}\t")?;
+        }
+        print_table_text(f, &link_external_refs(desc.trim(), ext_refs))?;
+    }
+    writeln!(f, ".TE")?;
+    Ok(())
+}
+
+// Print the descriptions of a function's parameters, as a list or a table
+fn print_parameters<W: Write>(f: &mut W, function: &FunctionInfo, ext_refs: &[(&str, &ExternalPage)],
+                              table: bool) -> Result<(), std::io::Error>
+{
+    if table {
+        return print_parameter_table(f, function, ext_refs);
+    }
+    writeln!(f, ".PP")?;
+    for (i, p) in function.fn_args.iter().enumerate() {
+        let desc = param_description(function, i);
+        if p.is_variadic() {
+            if !desc.is_empty() {
+                writeln!(f, ".TP")?;
+                writeln!(f, "\\fB...\\fP {}", link_external_refs(&desc, ext_refs))?;
+            }
+            continue;
+        }
+        writeln!(f, ".TP")?;
+        writeln!(f, "\\fB{}\\fP {}",
+                 p.par_name, link_external_refs(&desc, ext_refs))?;
+    }
+    Ok(())
+}
+
+// The last cell of a table row. Text goes in a text block so it can wrap.
+fn print_table_text<W: Write>(f: &mut W, text: &str) -> Result<(), std::io::Error>
+{
+    if text.is_empty() {
+        return writeln!(f);
+    }
+    writeln!(f, "T{{")?;
+    writeln!(f, "{text}")?;
+    writeln!(f, "T}}")
+}
+
+// --retval-table, the \\retval values and their descriptions in a tbl table
+fn print_retval_table<W: Write>(f: &mut W, retvals: &[&ReturnVal], ext_refs: &[(&str, &ExternalPage)]) -> Result<(), std::io::Error>
+{
+    writeln!(f, ".PP")?;
+    writeln!(f, ".TS")?;
+    writeln!(f, "lB lB")?;
+    writeln!(f, "lB lx.")?;
+    writeln!(f, "Value\tMeaning")?;
+    writeln!(f, "_")?;
+    for rv in retvals {
+        write!(f, "{}\t", rv.ret_name.trim())?;
+        print_table_text(f, &link_external_refs(rv.ret_desc.trim(), ext_refs))?;
+    }
+    writeln!(f, ".TE")?;
+    Ok(())
+}
+
+// The --ref-map pages for the <ref>s in a function's documentation,
+// leaving out the ones to functions that we have pages for
+fn external_refs_for<'a>(state: &'a RunState,
+                         function: &'a FunctionInfo,
+                         functions: &[FunctionInfo]) -> Vec<(&'a str, &'a ExternalPage)>
+{
+    let mut refs = Vec::<(&str, &ExternalPage)>::new();
+    if state.external_refs.is_empty() {
+        return refs;
+    }
+    for r in &function.fn_refs {
+        let name = r.ref_name.trim_end_matches("()");
+        if functions.iter().any(|f| f.fn_name == name) {
+            continue;
+        }
+        if let Some(page) = state.external_refs.lookup(&r.ref_id, name) {
+            if !refs.iter().any(|(n, _)| *n == name) {
+                refs.push((name, page));
+            }
+        }
+    }
+    refs
+}
+
+// The <ref>s in a function's documentation to the other functions that have
+// pages in this run, so that they can be written as page(section) too. They are
+// found by refid, as the text of a \link can be anything, and then by name.
+fn local_refs_for(opt: &Opt,
+                  state: &RunState,
+                  function: &FunctionInfo,
+                  functions: &[FunctionInfo]) -> Vec<(String, ExternalPage)>
+{
+    let mut refs = Vec::<(String, ExternalPage)>::new();
+    // The C version didn't link them
+    if opt.compat == "c" {
+        return refs;
+    }
+    for r in &function.fn_refs {
+        let name = r.ref_name.trim_end_matches("()");
+        if name.is_empty() || refs.iter().any(|(n, _)| n == name) {
+            continue;
+        }
+        let target = functions.iter().find(|f| !r.ref_id.is_empty() && f.fn_id == r.ref_id)
+            .or_else(|| functions.iter().find(|f| f.fn_name == name));
+        if let Some(t) = target {
+            if t.fn_name != function.fn_name && t.fn_name != opt.headerfile && wants_page(opt, state, t) {
+                refs.push((name.to_string(), ExternalPage {page: t.fn_name.clone(), section: opt.man_section.to_string()}));
+            }
+        }
+    }
+    refs
+}
+
+// Returns true if a return value looks like an errno value, eg EINVAL or -EAGAIN
+fn is_errno_name(name: &str) -> bool
+{
+    let name = name.trim().trim_start_matches('-');
+    name.len() > 1 && name.starts_with('E') &&
+        name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+// Returns true if name is one of the words in par_type
+fn type_uses(par_type: &str, name: &str) -> bool
+{
+    par_type.split(|c: char| !c.is_ascii_alphanumeric() && c != '_').any(|w| w == name)
+}
+
+// Print a structure or enum
+fn print_structure<W: Write>(f: &mut W, si: &StructureInfo) -> Result<(), std::io::Error>
+{
+    if !si.str_brief.is_empty() {
+        writeln!(f, "{}", si.str_brief)?;
+    }
+    if !si.str_description.is_empty() {
+        writeln!(f, "{}", si.str_description)?;
+    }
+    print_structure_definition(f, si)
+}
+
+// Print just the C definition of a structure or enum
+fn print_structure_definition<W: Write>(f: &mut W, si: &StructureInfo) -> Result<(), std::io::Error>
+{
+    let mut max_param_type_length = 0;
+    let mut max_param_name_length = 0;
+    for p in &si.str_members {
+        if p.par_type.len() > max_param_type_length {
+            max_param_type_length = p.par_type.len();
+	}
+        if p.par_name.len() + p.par_args.len() > max_param_name_length {
+            max_param_name_length = p.par_name.len() + p.par_args.len();
+        }
+    }
+
+    writeln!(f,)?;
+    writeln!(f, ".nf")?;
+    writeln!(f, "\\fB")?;
+    match si.str_type {
+        StructureType::Enum =>  writeln!(f, "enum {} {{", si.str_name)?,
+        StructureType::Struct => writeln!(f, "struct {} {{", si.str_name)?,
+        StructureType::Unknown => writeln!(f, "??? {} {{", si.str_name)?,
+    };
+
+    let mut i=0;
+    for p in &si.str_members {
+        i += 1;
+        if i == si.str_members.len() {
+            print_param(f, p, max_param_type_length, max_param_name_length, false, "".to_string())?;
+        } else {
+            print_param(f, p, max_param_type_length, max_param_name_length, false, ";".to_string())?;
+        }
+    }
+
+    writeln!(f, "}};\\fP")?;
+    writeln!(f, ".PP")?;
+    writeln!(f, ".fi")?;
+
+    Ok(())
+}
+
+// The brief description of a constant on one line, or the first line of its description
+fn constant_brief(brief: &str, desc: &str) -> String
+{
+    let text = if brief.trim().is_empty() { desc.lines().next().unwrap_or("") } else { brief };
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// The CONSTANTS section of the general page, with --constants. The values of
+// each enum, then the (ALLCAPS) #defines, one per line with their briefs
+// lined up after them, so they can all be found in one place.
+fn print_constants<W: Write>(f: &mut W,
+                             opt: &Opt,
+                             function: &FunctionInfo,
+                             functions: &[FunctionInfo],
+                             structures: &BTreeMap<String, StructureInfo>) -> Result<(), std::io::Error>
+{
+    let mut enums: Vec<&StructureInfo> = structures.values()
+        .filter(|s| matches!(s.str_type, StructureType::Enum) && !s.str_members.is_empty())
+        .collect();
+    enums.sort_by(|a, b| a.str_name.cmp(&b.str_name));
+    let defines = page_defines(opt, function, functions);
+    if enums.is_empty() && defines.is_empty() {
+        return Ok(());
+    }
+
+    let mut groups = Vec::<(String, Vec<(&str, String)>)>::new();
+    for e in enums {
+        // Anonymous enums are called @0 etc by doxygen
+        let title = if e.str_name.starts_with('@') { "enum".to_string() } else { format!("enum {}", e.str_name) };
+        groups.push((title, e.str_members.iter().map(|m| (m.par_name.as_str(), constant_brief(&m.par_brief, &m.par_desc))).collect()));
+    }
+    if !defines.is_empty() {
+        groups.push(("#define".to_string(), defines.iter().map(|d| (d.hd_name.as_str(), constant_brief(&d.hd_brief, &d.hd_desc))).collect()));
+    }
+    // Wide enough for most names, the longer ones have their brief on the next line
+    let width = groups.iter().flat_map(|(_, c)| c.iter().map(|(n, _)| n.len() + 2)).max().unwrap_or(0).min(32);
+
+    writeln!(f, ".SH CONSTANTS")?;
+    for (title, constants) in groups {
+        writeln!(f, ".PP")?;
+        writeln!(f, "\\fB{title}\\fP")?;
+        writeln!(f, ".PD 0")?;
+        for (name, brief) in constants {
+            writeln!(f, ".TP {width}n")?;
+            writeln!(f, "\\fB{name}\\fP")?;
+            writeln!(f, "{brief}")?;
+        }
+        writeln!(f, ".PD")?;
+    }
+    Ok(())
+}
+
+// Print a single man page. Returns the name of the page file, or None
+// if there isn't one for this function
+pub fn print_man_page(opt: &Opt,
+                      state: &RunState,
+                      man_date: &str,
+                      function: &FunctionInfo,
+                      functions: &[FunctionInfo],
+                      structures: &BTreeMap<String, StructureInfo>,
+                      copyright: &str) -> Result<Option<String>, std::io::Error>
+{
+    if function.fn_name == opt.headerfile && !opt.print_general {
+        return Ok(None);
+    }
+    if function.fn_name != opt.headerfile && !opt.only.is_empty() && !opt.only.contains(&function.fn_name) {
+        return Ok(None);
+    }
+
+    // DO IT!
+    let mut man_file = String::new();
+    if let Err(e) = write!(man_file, "{}/{}.{}", &opt.output_dir, function.fn_name, opt.man_section) {
+        eprintln!("Error making manpage filename: {e:?}");
+        return Err(Error::other("Error making filename"));
+    }
+
+    let dateptr = man_date;
+
+    if opt.no_clobber && !is_generated_page(&man_file) {
+        eprintln!("Not overwriting hand-written man page {}", &man_file);
+        return Ok(None);
+    }
+
+    if !opt.force && is_up_to_date(opt, state, &man_file, &function.fn_refids) {
+        return Ok(Some(man_file));
+    }
+
+    // Rendered into memory first so that --timings can tell rendering and writing apart
+    let page = state.timer.time(Phase::Render, || render_man_page(opt, state, dateptr, function, functions, structures, copyright))?;
+    state.timer.time(Phase::Write, || write_atomically(&man_file, |f| f.write_all(&page)))?;
+    Ok(Some(man_file))
+}
+
+// Write a .so page for an old name of a function (from --rename-map) that
+// shows the page for its new name. Returns the name of the page file, or None
+// if it's a hand-written page that we mustn't overwrite
+pub fn print_alias_page(opt: &Opt,
+                        old_name: &str,
+                        function: &FunctionInfo) -> Result<Option<String>, std::io::Error>
+{
+    let man_file = format!("{}/{}.{}", opt.output_dir, old_name, opt.man_section);
+    if opt.no_clobber && !is_generated_page(&man_file) {
+        eprintln!("Not overwriting hand-written man page {}", &man_file);
+        return Ok(None);
+    }
+    write_atomically(&man_file, |f| {
+        writeln!(f, ".\\\"  {GENERATED_MARKER}, do not edit")?;
+        writeln!(f, ".so man{}/{}.{}", opt.man_section, function.fn_name, opt.man_section)
+    })?;
+    Ok(Some(man_file))
+}
+
+// Write a --removed page, saying when a function was removed and what to use
+// instead. Returns the name of the page file, or None if it's a hand-written
+// page that we mustn't overwrite
+pub fn print_removed_page(opt: &Opt,
+                          state: &RunState,
+                          man_date: &str,
+                          removed: &RemovedFunction,
+                          copyright: &str) -> Result<Option<String>, std::io::Error>
+{
+    let man_file = format!("{}/{}.{}", opt.output_dir, removed.name, opt.man_section);
+    if opt.no_clobber && !is_generated_page(&man_file) {
+        eprintln!("Not overwriting hand-written man page {}", &man_file);
+        return Ok(None);
+    }
+
+    let page = state.timer.time(Phase::Render, || {
+        let mut page = Vec::<u8>::new();
+        write_removed_page(&mut page, opt, man_date, removed, copyright).map(|_| finish_page(opt, state, page))
+    })?;
+    state.timer.time(Phase::Write, || write_atomically(&man_file, |f| f.write_all(&page)))?;
+    Ok(Some(man_file))
+}
+
+// Write the contents of a --removed page
+fn write_removed_page<W: Write>(f: &mut W,
+                                opt: &Opt,
+                                dateptr: &str,
+                                removed: &RemovedFunction,
+                                copyright: &str) -> Result<(), std::io::Error>
+{
+    writeln!(f, ".\\\"  {GENERATED_MARKER}, do not edit")?;
+    writeln!(f, ".TH {} {} {} \"{}\" \"{}\"",
+             removed.name.to_ascii_uppercase(), opt.man_section, dateptr, page_source(opt), opt.header)?;
+
+    writeln!(f, ".SH NAME")?;
+    writeln!(f, ".PP")?;
+    writeln!(f, "{} \\- removed in {} {}", removed.name, opt.package_name, removed.version)?;
+
+    writeln!(f, ".SH DESCRIPTION")?;
+    writeln!(f, ".PP")?;
+    writeln!(f, "\\fB{}\\fP() was removed from {} in version {}.", removed.name, opt.package_name, removed.version)?;
+    if !removed.replacement.is_empty() {
+        writeln!(f, "Use \\fB{}\\fP() instead.", removed.replacement)?;
+        writeln!(f, ".SH SEE ALSO")?;
+        writeln!(f, ".PP")?;
+        writeln!(f, "\\fI{}\\fP({})", removed.replacement, opt.man_section)?;
+    }
+
+    if !copyright.is_empty() {
+        writeln!(f, ".SH COPYRIGHT")?;
+        writeln!(f, ".PP")?;
+        writeln!(f, "{}", copyright.replace('\n', "\n.br\n"))?;
+    }
+    Ok(())
+}
+
+// Print #defines with their descriptions
+fn print_defines<W: Write>(f: &mut W, defines: &[&HashDefine]) -> Result<(), std::io::Error>
+{
+    for d in defines {
+        if !d.hd_brief.is_empty() {
+            writeln!(f, ".PP")?;
+            writeln!(f, "{}", d.hd_brief)?;
+            writeln!(f, ".br")?;
+        }
+        if !d.hd_desc.is_empty() {
+            writeln!(f, ".br")?;
+            writeln!(f, "{}", d.hd_desc)?;
+            writeln!(f, ".br")?;
+        }
+
+        writeln!(f, "#define {} {}", d.hd_name, d.hd_init)?;
+        writeln!(f, ".br")?;
+    }
+    Ok(())
+}
+
+// Write the --defines-page page, with the #defines that would otherwise be on
+// the general page. Returns the name of the page file, or None if it's a
+// hand-written page that we mustn't overwrite
+pub fn print_defines_page(opt: &Opt,
+                          state: &RunState,
+                          man_date: &str,
+                          general: &FunctionInfo,
+                          defines: &[&HashDefine],
+                          copyright: &str) -> Result<Option<String>, std::io::Error>
+{
+    let name = defines_page_name(opt);
+    let man_file = format!("{}/{}.{}", opt.output_dir, name, opt.defines_page);
+    if opt.no_clobber && !is_generated_page(&man_file) {
+        eprintln!("Not overwriting hand-written man page {}", &man_file);
+        return Ok(None);
+    }
+
+    if !opt.force && is_up_to_date(opt, state, &man_file, &general.fn_refids) {
+        return Ok(Some(man_file));
+    }
+
+    let page = state.timer.time(Phase::Render, || {
+        let mut page = Vec::<u8>::new();
+        write_defines_page(&mut page, opt, man_date, &name, defines, copyright).map(|_| finish_page(opt, state, page))
+    })?;
+    state.timer.time(Phase::Write, || write_atomically(&man_file, |f| f.write_all(&page)))?;
+    Ok(Some(man_file))
+}
+
+// Write the contents of a --defines-page page
+fn write_defines_page<W: Write>(f: &mut W,
+                                opt: &Opt,
+                                dateptr: &str,
+                                name: &str,
+                                defines: &[&HashDefine],
+                                copyright: &str) -> Result<(), std::io::Error>
+{
+    writeln!(f, ".\\\"  {GENERATED_MARKER}, do not edit")?;
+    writeln!(f, ".TH {} {} {} \"{}\" \"{}\"",
+             name.to_ascii_uppercase(), opt.defines_page, dateptr, page_source(opt), opt.header)?;
+
+    writeln!(f, ".SH NAME")?;
+    writeln!(f, ".PP")?;
+    writeln!(f, "{} \\- constants defined in <{}{}>", name, opt.header_prefix, opt.headerfile)?;
+
+    writeln!(f, ".SH SYNOPSIS")?;
+    writeln!(f, ".PP")?;
+    writeln!(f, ".nf")?;
+    writeln!(f, ".B #include <{}{}>", opt.header_prefix, opt.headerfile)?;
+    writeln!(f, ".fi")?;
+
+    writeln!(f, ".SH DEFINES")?;
+    writeln!(f, ".PP")?;
+    print_defines(f, defines)?;
+
+    writeln!(f, ".SH SEE ALSO")?;
+    writeln!(f, ".PP")?;
+    writeln!(f, "\\fI{}\\fP({})", opt.headerfile, opt.man_section)?;
+
+    if !copyright.is_empty() {
+        writeln!(f, ".SH COPYRIGHT")?;
+        writeln!(f, ".PP")?;
+        writeln!(f, "{}", copyright.replace('\n', "\n.br\n"))?;
+    }
+    Ok(())
+}
+
+// Make the troff for a page, with a template if there is one for it
+pub fn render_man_page(opt: &Opt,
+                       state: &RunState,
+                       man_date: &str,
+                       function: &FunctionInfo,
+                       functions: &[FunctionInfo],
+                       structures: &BTreeMap<String, StructureInfo>,
+                       copyright: &str) -> Result<Vec<u8>, std::io::Error>
+{
+    let copyright = &page_copyright(opt, function, functions, copyright);
+
+    #[cfg(feature = "templates")]
+    if let Some(t) = &state.templates {
+        let template_name = if function.fn_name == opt.headerfile { "general" } else { "function" };
+        if t.get_template_names().any(|n| n == template_name) {
+            let page = render_template(t, template_name, opt, man_date, function, functions, structures, copyright)?;
+            return Ok(finish_page(opt, state, page.into_bytes()));
+        }
+    }
+
+    let mut page = Vec::<u8>::new();
+    write_man_page(&mut page, opt, state, man_date, function, functions, structures, copyright)?;
+    Ok(finish_page(opt, state, page))
+}
+
+// The passes over every page before it is written, --symbols, tidying up,
+// --source-width, --section-markers, --no-hyphenation and --preamble, --utf8,
+// the options hash (not with --compat c, to keep the pages as the C version
+// makes them) and --metadata
+fn finish_page(opt: &Opt, state: &RunState, page: Vec<u8>) -> Vec<u8>
+{
+    // The kinds of highlighting in program listings are only for the HTML pages
+    let page = if page.contains(&(HIGHLIGHT_START as u8)) {
+        strip_highlights(&String::from_utf8_lossy(&page)).into_bytes()
+    } else {
+        page
+    };
+    let tidy = !opt.no_tidy && opt.compat != "c";
+    let options_hash = opt.compat != "c";
+    // UTF-8 pages keep all their characters, the others are plain ASCII
+    let symbols = !opt.utf8 && !page.is_ascii();
+    let preamble = page_preamble(opt, state);
+    if !symbols && !tidy && opt.source_width == 0 && !opt.section_markers &&
+        preamble.is_empty() && !opt.utf8 && !options_hash && !opt.metadata {
+        return page;
+    }
+    let mut text = String::from_utf8_lossy(&page).to_string();
+    if symbols {
+        text = normalize_symbols(&text, &opt.symbols);
+    }
+    if tidy {
+        text = tidy_man_page(&text);
+    }
+    if opt.source_width > 0 {
+        text = wrap_man_page(&text, opt.source_width);
+    }
+    if opt.section_markers {
+        text = add_section_markers(&text);
+    }
+    if !preamble.is_empty() {
+        text = add_after_th(&text, &preamble);
+    }
+    if opt.utf8 {
+        text = add_coding_line(&text);
+    }
+    if options_hash {
+        text = add_options_hash(opt, &text);
+    }
+    if opt.metadata {
+        text = add_metadata(state, &text);
+    }
+    text.into_bytes()
+}
+
+// Requests for the whole page, that go after the .TH line (the man macros reset
+// things like hyphenation there)
+fn page_preamble(opt: &Opt, state: &RunState) -> String
+{
+    let mut preamble = String::new();
+    if opt.no_hyphenation {
+        preamble += ".nh\n";
+    }
+    if !state.preamble_text.is_empty() {
+        preamble += &state.preamble_text;
+        if !preamble.ends_with('\n') {
+            preamble.push('\n');
+        }
+    }
+    preamble
+}
+
+// Put a comment before each .SH and at the end of its section, for tools that
+// replace or add to sections of the pages
+fn add_section_markers(text: &str) -> String
+{
+    let mut out = String::with_capacity(text.len() + 500);
+    let mut section: Option<String> = None;
+    for l in text.lines() {
+        if let Some(heading) = l.strip_prefix(".SH") {
+            if let Some(s) = &section {
+                out.push_str(&format!(".\\\" d2m:section {s} end\n"));
+            }
+            let name = heading.trim().trim_matches('"').to_string();
+            out.push_str(&format!(".\\\" d2m:section {name} begin\n"));
+            section = Some(name);
+        }
+        out.push_str(l);
+        out.push('\n');
+    }
+    if let Some(s) = &section {
+        out.push_str(&format!(".\\\" d2m:section {s} end\n"));
+    }
+    out
+}
+
+// Put some lines straight after the .TH line
+fn add_after_th(text: &str, lines: &str) -> String
+{
+    let mut out = String::with_capacity(text.len() + lines.len());
+    let mut added = false;
+    for l in text.lines() {
+        out.push_str(l);
+        out.push('\n');
+        if !added && l.starts_with(".TH ") {
+            out.push_str(lines);
+            added = true;
+        }
+    }
+    out
+}
+
+// Put the --utf8 encoding declaration at the top, after the tbl line if there is one
+fn add_coding_line(text: &str) -> String
+{
+    match text.strip_prefix(TBL_PREPROCESSOR_LINE).and_then(|t| t.strip_prefix('\n')) {
+        Some(rest) => format!("{TBL_PREPROCESSOR_LINE}\n{UTF8_CODING_LINE}\n{rest}"),
+        None => format!("{UTF8_CODING_LINE}\n{text}"),
+    }
+}
+
+// Add comments saying what made the page after the "Automatically generated"
+// line: our version, the XML file and a hash of the rest of the page
+fn add_metadata(state: &RunState, text: &str) -> String
+{
+    let hash = content_hash(text);
+    let mut out = String::with_capacity(text.len() + 200);
+    let mut added = false;
+    for l in text.lines() {
+        out.push_str(l);
+        out.push('\n');
+        if !added && l.contains(GENERATED_MARKER) {
+            out.push_str(&format!(".\\\"  Generated by doxygen2man {}\n", env!("CARGO_PKG_VERSION")));
+            out.push_str(&format!(".\\\"  Source: {}\n", state.current_input));
+            out.push_str(&format!(".\\\"  Content hash: {hash:016x}\n"));
+            added = true;
+        }
+    }
+    out
+}
+
+// The list of pages in SEE ALSO isn't hyphenated or justified, the whole page
+// isn't hyphenated with --no-hyphenation so that only needs .ad l
+fn see_also_adjustment<W: Write>(f: &mut W, opt: &Opt) -> Result<(), std::io::Error>
+{
+    if !opt.no_hyphenation {
+        writeln!(f, ".nh")?;
+    }
+    writeln!(f, ".ad l")
+}
+
+// Write the contents of a man page
+#[allow(clippy::too_many_arguments)]
+fn write_man_page<W: Write>(f: &mut W,
+                            opt: &Opt,
+                            state: &RunState,
+                            dateptr: &str,
+                            function: &FunctionInfo,
+                            functions: &[FunctionInfo],
+                            structures: &BTreeMap<String, StructureInfo>,
+                            copyright: &str) -> Result<(), std::io::Error>
+{
+    let legacy;
+    let function = if opt.compat == "c" {
+        legacy = legacy_function(function);
+        &legacy
+    } else {
+        function
+    };
+
+    // Work out the length of the parameters, so we can line them up
+    let mut max_param_type_len: usize = 0;
+    let mut max_param_name_len: usize = 0;
+    let mut num_param_descs: usize = 0;
+    let mut param_count: usize = 0;
+
+    for (i, p) in function.fn_args.iter().enumerate() {
+        param_count += 1;
+        if p.is_variadic() {
+            num_param_descs += usize::from(!p.par_desc.is_empty());
+            continue;
+        }
+        if p.par_desc.is_empty() && (is_nonnull(function, i) || !p.par_defval.is_empty()) {
+            num_param_descs += 1;
+        }
+        if (p.par_type.len() < MAX_PRINT_PARAM_LEN) &&
+            (p.par_type.len() > max_param_type_len) {
+                max_param_type_len = p.par_type.len();
+            }
+        if p.par_name.len() > max_param_name_len {
+            max_param_name_len = p.par_name.len();
+        }
+        if !p.par_desc.is_empty() && !p.par_type.is_empty() {
+            num_param_descs += 1;
+        }
+    }
+
+    // <ref>s to pages in other libraries, from --ref-map
+    let ext_refs = external_refs_for(state, function, functions);
+    // and to our own pages, both are linked in the text
+    let local_refs = local_refs_for(opt, state, function, functions);
+    let mut text_refs = ext_refs.clone();
+    text_refs.extend(local_refs.iter().map(|(n, p)| (n.as_str(), p)));
+
+    if needs_tbl(opt, function) {
+        writeln!(f, "{TBL_PREPROCESSOR_LINE}")?;
+    }
+    writeln!(f, ".\\\"  {GENERATED_MARKER}, do not edit")?;
+    writeln!(f, ".TH {} {} {} \"{}\" \"{}\"",
+             function.fn_name.to_ascii_uppercase(), opt.man_section, dateptr, page_source(opt), opt.header)?;
+
+    writeln!(f, ".SH NAME")?;
+    writeln!(f, ".PP")?;
+    if !function.fn_brief.is_empty()  {
+        writeln!(f, "{} \\- {}", function.fn_name, function.fn_brief)?;
+    } else {
+        writeln!(f, "{}", function.fn_name)?;
+    }
+
+    writeln!(f, ".SH SYNOPSIS")?;
+    writeln!(f, ".PP")?;
+    writeln!(f, ".nf")?;
+    writeln!(f, ".B #include <{}{}>", opt.header_prefix, opt.headerfile)?;
+    if let Some(lines) = synopsis_prototype(opt, function).filter(|_| !function.fn_def.is_empty()) {
+        writeln!(f, ".sp")?;
+        for l in lines {
+            writeln!(f, "{l}")?;
+        }
+        writeln!(f, ".fi")?;
+    } else if !function.fn_def.is_empty() {
+        writeln!(f, ".sp")?;
+        writeln!(f, "\\fB{}\\fP(", synopsis_definition(function))?;
+
+        let mut i=0;
+        for p in &function.fn_args {
+            i += 1;
+            if i == param_count {
+                print_param(f, p, max_param_type_len, 0, true, "".to_string())?;
+            } else {
+                print_param(f, p, max_param_type_len, 0, true, ",".to_string())?;
+            }
+        }
+
+        writeln!(f, ");")?;
+        writeln!(f, ".fi")?;
+    } else {
+        // The general page just has the #include, and maybe all of the prototypes
+        let prototypes: Vec<String> = functions.iter()
+            .filter(|t| opt.general_synopsis && t.fn_name != opt.headerfile && !t.fn_typedef && !t.fn_def.is_empty())
+            .filter(|t| wants_page(opt, state, t))
+            .map(compact_prototype)
+            .collect();
+        if !prototypes.is_empty() {
+            writeln!(f, ".sp")?;
+            for p in prototypes {
+                writeln!(f, "{p}")?;
+            }
+        }
+        writeln!(f, ".fi")?;
+    }
+
+    // Section 2 pages follow the man-pages(7) layout for system calls: the
+    // parameters and structures are part of the DESCRIPTION, and the errno
+    // return values get their own ERRORS section
+    let syscall_style = opt.man_section == 2;
+    let print_params = opt.print_params && num_param_descs > 0;
+    let has_structures = function.fn_refids.iter().any(|r| structures.contains_key(r));
+
+    if print_params && !syscall_style {
+        writeln!(f, ".SH PARAMETERS")?;
+        print_parameters(f, function, &text_refs, opt.params_table)?;
+    }
+    // Callback typedefs say which functions they are passed to
+    let users: Vec<&FunctionInfo> = if function.fn_typedef {
+        functions.iter().filter(|u| !u.fn_typedef && u.fn_args.iter().any(|p| type_uses(&p.par_type, &function.fn_name))).collect()
+    } else {
+        Vec::new()
+    };
+    let detail = function_detail(opt, function);
+    if !detail.is_empty() || !users.is_empty() || (syscall_style && (print_params || has_structures)) {
+        writeln!(f, ".SH DESCRIPTION")?;
+        writeln!(f, ".PP")?;
+        print_long_string(f, opt, &link_external_refs(&detail, &text_refs))?;
+        if !users.is_empty() {
+            write!(f, "Callbacks of this type are passed to")?;
+            for (i, u) in users.iter().enumerate() {
+                let delim = if i + 1 == users.len() { "." } else { "," };
+                write!(f, " \\fB{}\\fP({}){}", u.fn_name, opt.man_section, delim)?;
+            }
+            writeln!(f)?;
+        }
+    }
+
+    if print_params && syscall_style {
+        writeln!(f, ".SS Parameters")?;
+        print_parameters(f, function, &text_refs, opt.params_table)?;
+    }
+
+    // In case we can't find the refids, don't print the header
+    if has_structures {
+        if syscall_style {
+            writeln!(f, ".SS Structures")?;
+        } else {
+            writeln!(f, ".SH STRUCTURES")?;
+        }
+        writeln!(f, ".PP")?;
+        for fs in &function.fn_refids {
+            if let Some(s) = structures.get(fs) {
+                print_structure(f, s)?;
+            }
+        }
+    }
+
+    let (errors, retvals): (Vec<&ReturnVal>, Vec<&ReturnVal>) =
+        function.fn_retvals.iter().partition(|rv| syscall_style && is_errno_name(&rv.ret_name));
+    let noreturn = is_noreturn(function) && function.fn_returnval.is_empty();
+    if !function.fn_returnval.is_empty() || !errors.is_empty() || noreturn {
+        writeln!(f, ".SH RETURN VALUE")?;
+        writeln!(f, ".PP")?;
+        if !function.fn_returnval.is_empty() {
+            writeln!(f, "{}", link_external_refs(&function.fn_returnval, &text_refs))?;
+            writeln!(f, ".br")?;
+        }
+        if noreturn {
+            writeln!(f, "This function does not return.")?;
+        }
+        if opt.retval_table && !retvals.is_empty() {
+            print_retval_table(f, &retvals, &text_refs)?;
+        } else {
+            for rv in retvals {
+                writeln!(f, ".TP")?;
+                writeln!(f, "\\fB{}\\fR {}", rv.ret_name, link_external_refs(&rv.ret_desc, &text_refs))?;
+            }
+        }
+        if !errors.is_empty() {
+            writeln!(f, ".PP")?;
+            if errors.iter().all(|rv| rv.ret_name.trim().starts_with('-')) {
+                writeln!(f, "On error, a negative error number is returned (see ERRORS).")?;
+            } else {
+                writeln!(f, "On error, \\fIerrno\\fP is set to indicate the error (see ERRORS).")?;
+            }
+        }
+        writeln!(f, ".PP")?;
+    }
+    if !errors.is_empty() {
+        writeln!(f, ".SH ERRORS")?;
+        for rv in errors {
+            writeln!(f, ".TP")?;
+            writeln!(f, "\\fB{}\\fR", rv.ret_name.trim().trim_start_matches('-'))?;
+            writeln!(f, "{}", rv.ret_desc)?;
+        }
+    }
+
+    if opt.constants && function.fn_name == opt.headerfile && !opt.minimal {
+        print_constants(f, opt, function, functions, structures)?;
+    }
+
+    // #defines - all of them on the General manpage (unless they have their
+    // own page), the ones it uses on a function's
+    let defines = page_defines(opt, function, functions);
+    let own_page = function.fn_name == opt.headerfile && !opt.defines_page.is_empty();
+    if (!function.fn_defines.is_empty() || !defines.is_empty()) && !opt.minimal && !own_page {
+        writeln!(f, ".SH DEFINES")?;
+        writeln!(f, ".PP")?;
+        print_defines(f, &defines)?;
+    }
+
+    // From the deprecated attribute, doxygen's \deprecated is an xrefsect.
+    // The old names from --rename-map are deprecated too. With -x the
+    // \deprecated text goes in this section too, rather than one of its own.
+    let note = deprecation_note(function);
+    let old = old_names(state, &function.fn_name);
+    let deprecated_section = note.is_some() || !old.is_empty();
+    if deprecated_section {
+        writeln!(f, ".SH DEPRECATED")?;
+        writeln!(f, ".PP")?;
+        let mut started = false;
+        if let Some(note) = &note {
+            writeln!(f, "{note}")?;
+            started = true;
+        }
+        if opt.xref_sections {
+            for xr in function.fn_xrefsects.iter().filter(|x| x.xr_title.eq_ignore_ascii_case("deprecated")) {
+                if started {
+                    writeln!(f, ".PP")?;
+                }
+                print_long_string(f, opt, &xr.xr_desc)?;
+                started = true;
+            }
+        }
+        if !old.is_empty() {
+            if started {
+                writeln!(f, ".PP")?;
+            }
+            let old_list: Vec<String> = old.iter().map(|o| format!("\\fB{o}\\fP()")).collect();
+            if old.len() == 1 {
+                writeln!(f, "The old name of this function, {}, is a deprecated alias for it.", old_list[0])?;
+            } else {
+                writeln!(f, "The old names of this function, {}, are deprecated aliases for it.", old_list.join(", "))?;
+            }
+        }
+    }
+
+    if !function.fn_note.is_empty() {
+        if syscall_style {
+            writeln!(f, ".SH NOTES")?;
+        } else {
+            writeln!(f, ".SH NOTE")?;
+        }
+        writeln!(f, ".PP")?;
+        print_long_string(f, opt, &link_external_refs(&function.fn_note, &text_refs))?;
+    }
+
+    // \attention, \remark and \invariant
+    for (kind, title) in SIMPLESECT_SECTIONS {
+        let mut sects = function.fn_simplesects.iter().filter(|s| s.ss_kind == kind).peekable();
+        if sects.peek().is_some() {
+            writeln!(f, ".SH {title}")?;
+            writeln!(f, ".PP")?;
+        }
+        for s in sects {
+            print_long_string(f, opt, &link_external_refs(&s.ss_text, &text_refs))?;
+        }
+    }
+
+    // xrefsects get a section per title, in the order they first appear
+    if opt.xref_sections {
+        let mut titles = Vec::<&str>::new();
+        for xr in &function.fn_xrefsects {
+            if deprecated_section && xr.xr_title.eq_ignore_ascii_case("deprecated") {
+                continue;
+            }
+            if !titles.contains(&xr.xr_title.as_str()) {
+                titles.push(&xr.xr_title);
+            }
+        }
+        for t in titles {
+            writeln!(f, ".SH {}", t.to_ascii_uppercase())?;
+            writeln!(f, ".PP")?;
+            for xr in function.fn_xrefsects.iter().filter(|x| x.xr_title == t) {
+                print_long_string(f, opt, &xr.xr_desc)?;
+            }
+        }
+    }
+
+    // Print list of related functions, with --minimal just the ones it refers to
+    let mut see_also = Vec::<ExternalPage>::new();
+    if opt.minimal {
+        see_also.extend(local_refs.iter().map(|(_, page)| page.clone()));
+    } else {
+        for func in functions {
+            if func.fn_name != function.fn_name {
+                see_also.push(ExternalPage {page: func.fn_name.clone(), section: opt.man_section.to_string()});
+            }
+        }
+    }
+    // The type pages of the structures and enums it takes go first
+    let mut type_pages = Vec::<ExternalPage>::new();
+    for p in &function.fn_args {
+        if let Some(s) = p.par_refid.as_ref().and_then(|r| structures.get(r)).filter(|s| has_type_page(opt, s)) {
+            let page = ExternalPage {page: s.str_name.clone(), section: format!("{}type", opt.man_section)};
+            if !type_pages.contains(&page) {
+                type_pages.push(page);
+            }
+        }
+    }
+    see_also.splice(0..0, type_pages);
+    if own_page && !defines.is_empty() {
+        see_also.insert(0, ExternalPage {page: defines_page_name(opt), section: opt.defines_page.clone()});
+    }
+    // And the pages in other libraries
+    for (_, page) in &ext_refs {
+        if !see_also.contains(page) {
+            see_also.push((*page).clone());
+        }
+    }
+    if opt.version_section && !opt.source_version.is_empty() {
+        writeln!(f, ".SH VERSION")?;
+        writeln!(f, ".PP")?;
+        writeln!(f, "This page documents {} {}.", opt.package_name, opt.source_version)?;
+    }
+
+    if !see_also.is_empty() {
+        writeln!(f, ".SH SEE ALSO")?;
+        writeln!(f, ".PP")?;
+        see_also_adjustment(f, opt)?;
+        for (i, page) in see_also.iter().enumerate() {
+            // The C version only left the comma off the last function in the
+            // header, so if that was this one the list ended with a comma
+            let last = if opt.compat == "c" {
+                functions.last().is_some_and(|l| l.fn_name == page.page)
+            } else {
+                i + 1 == see_also.len()
+            };
+            let delim = if last { "" } else { ", " };
+            writeln!(f, "\\fI{}\\fP({}){}", page.page, page.section, delim)?;
+        }
+    }
+
+    if !copyright.is_empty() && !opt.minimal {
+        writeln!(f, ".SH COPYRIGHT")?;
+        writeln!(f, ".PP")?;
+        // One line each if there are several (from -c)
+        writeln!(f, "{}", copyright.replace('\n', "\n.br\n"))?;
+    }
+
+    Ok(())
+}
+
+// Print a --type-pages page for a structure or enum, eg foo_info.3type.
+// Returns the name of the page file, or None if there isn't one for it
+pub fn print_type_page(opt: &Opt,
+                       state: &RunState,
+                       man_date: &str,
+                       refid: &str,
+                       structure: &StructureInfo,
+                       functions: &[FunctionInfo],
+                       copyright: &str) -> Result<Option<String>, std::io::Error>
+{
+    if let StructureType::Unknown = structure.str_type {
+        return Ok(None);
+    }
+    if structure.str_name.is_empty() {
+        return Ok(None);
+    }
+
+    // The functions that use it, for SEE ALSO
+    let users: Vec<&FunctionInfo> = functions.iter().filter(|f| f.fn_refids.iter().any(|r| r == refid)).collect();
+    if !opt.only.is_empty() && !users.iter().any(|f| opt.only.contains(&f.fn_name)) {
+        return Ok(None);
+    }
+
+    let man_file = format!("{}/{}.{}type", opt.output_dir, structure.str_name, opt.man_section);
+
+    if opt.no_clobber && !is_generated_page(&man_file) {
+        eprintln!("Not overwriting hand-written man page {}", &man_file);
+        return Ok(None);
+    }
+
+    if !opt.force && is_up_to_date(opt, state, &man_file, &[refid.to_string()]) {
+        return Ok(Some(man_file));
+    }
+
+    let page = state.timer.time(Phase::Render, || {
+        let mut page = Vec::<u8>::new();
+        write_type_page(&mut page, opt, man_date, structure, &users, copyright).map(|_| finish_page(opt, state, page))
+    })?;
+    state.timer.time(Phase::Write, || write_atomically(&man_file, |f| f.write_all(&page)))?;
+    Ok(Some(man_file))
+}
+
+// Write the contents of a type page
+fn write_type_page<W: Write>(f: &mut W,
+                             opt: &Opt,
+                             dateptr: &str,
+                             structure: &StructureInfo,
+                             users: &[&FunctionInfo],
+                             copyright: &str) -> Result<(), std::io::Error>
+{
+    writeln!(f, ".\\\"  {GENERATED_MARKER}, do not edit")?;
+    writeln!(f, ".TH {} {}type {} \"{}\" \"{}\"",
+             structure.str_name.to_ascii_uppercase(), opt.man_section, dateptr, page_source(opt), opt.header)?;
+
+    writeln!(f, ".SH NAME")?;
+    writeln!(f, ".PP")?;
+    if !structure.str_brief.is_empty()  {
+        writeln!(f, "{} \\- {}", structure.str_name, structure.str_brief)?;
+    } else {
+        writeln!(f, "{}", structure.str_name)?;
+    }
+
+    writeln!(f, ".SH SYNOPSIS")?;
+    writeln!(f, ".PP")?;
+    writeln!(f, ".nf")?;
+    writeln!(f, ".B #include <{}{}>", opt.header_prefix, opt.headerfile)?;
+    writeln!(f, ".fi")?;
+    print_structure_definition(f, structure)?;
+
+    let documented: Vec<&FnParam> = structure.str_members.iter().filter(|m| !m.par_brief.is_empty() || !m.par_desc.is_empty()).collect();
+    if !structure.str_description.is_empty() || !documented.is_empty() {
+        writeln!(f, ".SH DESCRIPTION")?;
+        writeln!(f, ".PP")?;
+        if !structure.str_description.is_empty() {
+            print_long_string(f, opt, &structure.str_description)?;
+        }
+        for m in documented {
+            writeln!(f, ".TP")?;
+            writeln!(f, "\\fB{}\\fP", m.par_name)?;
+            if !m.par_brief.is_empty() {
+                writeln!(f, "{}", m.par_brief)?;
+            }
+            if !m.par_desc.is_empty() {
+                writeln!(f, "{}", m.par_desc)?;
+            }
+        }
+    }
+
+    if !users.is_empty() {
+        writeln!(f, ".SH SEE ALSO")?;
+        writeln!(f, ".PP")?;
+        see_also_adjustment(f, opt)?;
+        for (i, func) in users.iter().enumerate() {
+            let delim = if i + 1 == users.len() { "" } else { ", " };
+            writeln!(f, "\\fI{}\\fP({}){}", func.fn_name, opt.man_section, delim)?;
+        }
+    }
+
+    if !copyright.is_empty() {
+        writeln!(f, ".SH COPYRIGHT")?;
+        writeln!(f, ".PP")?;
+        writeln!(f, "{}", copyright.replace('\n', "\n.br\n"))?;
+    }
+
+    Ok(())
+}
+
+// Print the --main-page overview page, <package-name>.7, from the \mainpage text (if there
+// is one) and an index of all the other pages. Returns the name of the page file,
+// or None if it's a hand-written page that we mustn't overwrite
+pub fn print_main_page(opt: &Opt,
+                       state: &RunState,
+                       man_date: &str,
+                       main_page: Option<&DocPage>,
+                       pages: &[ManPage],
+                       copyright: &str) -> Result<Option<String>, std::io::Error>
+{
+    // Always rewritten, the index depends on every other page
+    print_doc_page(opt, state, man_date, &opt.package_name, main_page, pages, copyright)
+}
+
+// Print a --doc-pages page for a doxygen \page, eg tutorial.7
+pub fn print_page_page(opt: &Opt,
+                       state: &RunState,
+                       man_date: &str,
+                       page: &DocPage,
+                       copyright: &str) -> Result<Option<String>, std::io::Error>
+{
+    print_doc_page(opt, state, man_date, &page.pg_name, Some(page), &[], copyright)
+}
+
+fn print_doc_page(opt: &Opt,
+                  state: &RunState,
+                  man_date: &str,
+                  page_name: &str,
+                  page: Option<&DocPage>,
+                  pages: &[ManPage],
+                  copyright: &str) -> Result<Option<String>, std::io::Error>
+{
+    let man_file = format!("{}/{}.7", opt.output_dir, page_name);
+
+    if opt.no_clobber && !is_generated_page(&man_file) {
+        eprintln!("Not overwriting hand-written man page {}", &man_file);
+        return Ok(None);
+    }
+
+    let page = state.timer.time(Phase::Render, || {
+        let mut text = Vec::<u8>::new();
+        write_doc_page(&mut text, opt, man_date, page_name, page, pages, copyright).map(|_| finish_page(opt, state, text))
+    })?;
+    state.timer.time(Phase::Write, || write_atomically(&man_file, |f| f.write_all(&page)))?;
+    Ok(Some(man_file))
+}
+
+// Write the contents of a section 7 page made from a doxygen page,
+// with an index of the pages in 'pages' at the end
+fn write_doc_page<W: Write>(f: &mut W,
+                            opt: &Opt,
+                            dateptr: &str,
+                            page_name: &str,
+                            page: Option<&DocPage>,
+                            pages: &[ManPage],
+                            copyright: &str) -> Result<(), std::io::Error>
+{
+    writeln!(f, ".\\\"  {GENERATED_MARKER}, do not edit")?;
+    writeln!(f, ".TH {} 7 {} \"{}\" \"{}\"",
+             page_name.to_ascii_uppercase(), dateptr, page_source(opt), opt.header)?;
+
+    writeln!(f, ".SH NAME")?;
+    writeln!(f, ".PP")?;
+    let summary = match page {
+        Some(p) if !p.pg_title.is_empty() => p.pg_title.as_str(),
+        Some(p) => p.pg_brief.as_str(),
+        None => "",
+    };
+    if !summary.is_empty() {
+        writeln!(f, "{} \\- {}", page_name, summary)?;
+    } else {
+        writeln!(f, "{} \\- overview of the {} API", page_name, opt.package_name)?;
+    }
+
+    if let Some(p) = page {
+        // The text might start with a section of its own
+        if !p.pg_detail.starts_with(".SH") {
+            writeln!(f, ".SH DESCRIPTION")?;
+            writeln!(f, ".PP")?;
+        }
+        if !p.pg_brief.is_empty() && p.pg_brief != summary {
+            writeln!(f, "{}", p.pg_brief)?;
+            writeln!(f, ".PP")?;
+        }
+        print_long_string(f, opt, &p.pg_detail)?;
+    }
+
+    // Not the --rename-map .so pages or the --removed pages, they are only
+    // there for the old names
+    let pages: Vec<&ManPage> = pages.iter().filter(|p| p.kind != PageKind::Alias && p.kind != PageKind::Removed).collect();
+    if !pages.is_empty() {
+        writeln!(f, ".SH PAGES")?;
+        for p in pages {
+            let section = Path::new(&p.file).extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+            writeln!(f, ".TP")?;
+            writeln!(f, "\\fB{}\\fP({})", p.function, section)?;
+            if !p.brief.trim().is_empty() {
+                writeln!(f, "{}", p.brief.trim())?;
+            }
+        }
+    }
+
+    if !copyright.is_empty() {
+        writeln!(f, ".SH COPYRIGHT")?;
+        writeln!(f, ".PP")?;
+        writeln!(f, "{}", copyright.replace('\n', "\n.br\n"))?;
+    }
+
+    Ok(())
+}
+
+// Print the --index-page page, <package-name>_index.<section>, listing all the
+// function pages in alphabetical order. Returns the name of the page file, or None
+// if it's a hand-written page that we mustn't overwrite
+pub fn print_index_page(opt: &Opt,
+                        state: &RunState,
+                        man_date: &str,
+                        pages: &[ManPage],
+                        copyright: &str) -> Result<Option<String>, std::io::Error>
+{
+    let index_name = format!("{}_index", opt.package_name);
+    let man_file = format!("{}/{}.{}", opt.output_dir, index_name, opt.man_section);
+
+    if opt.no_clobber && !is_generated_page(&man_file) {
+        eprintln!("Not overwriting hand-written man page {}", &man_file);
+        return Ok(None);
+    }
+
+    let mut functions: Vec<&ManPage> = pages.iter().filter(|p| p.kind == PageKind::Function || p.kind == PageKind::Callback).collect();
+    functions.sort_by(|a, b| a.function.cmp(&b.function));
+    functions.dedup_by(|a, b| a.function == b.function);
+
+    // Always rewritten, it depends on every other page
+    let page = state.timer.time(Phase::Render, || {
+        let mut page = Vec::<u8>::new();
+        write_index_page(&mut page, opt, man_date, &index_name, &functions, copyright).map(|_| finish_page(opt, state, page))
+    })?;
+    state.timer.time(Phase::Write, || write_atomically(&man_file, |f| f.write_all(&page)))?;
+    Ok(Some(man_file))
+}
+
+// Write the contents of the index page
+fn write_index_page<W: Write>(f: &mut W,
+                              opt: &Opt,
+                              dateptr: &str,
+                              index_name: &str,
+                              functions: &[&ManPage],
+                              copyright: &str) -> Result<(), std::io::Error>
+{
+    writeln!(f, ".\\\"  {GENERATED_MARKER}, do not edit")?;
+    writeln!(f, ".TH {} {} {} \"{}\" \"{}\"",
+             index_name.to_ascii_uppercase(), opt.man_section, dateptr, page_source(opt), opt.header)?;
+
+    writeln!(f, ".SH NAME")?;
+    writeln!(f, ".PP")?;
+    writeln!(f, "{} \\- index of the {} functions", index_name, opt.package_name)?;
+
+    writeln!(f, ".SH FUNCTIONS")?;
+    for p in functions {
+        writeln!(f, ".TP")?;
+        writeln!(f, "\\fB{}\\fP({})", p.function, opt.man_section)?;
+        if !p.brief.trim().is_empty() {
+            writeln!(f, "{}", p.brief.trim())?;
+        }
+    }
+
+    if opt.main_page {
+        writeln!(f, ".SH SEE ALSO")?;
+        writeln!(f, ".PP")?;
+        writeln!(f, "\\fI{}\\fP(7)", opt.package_name)?;
+    }
+
+    if !copyright.is_empty() {
+        writeln!(f, ".SH COPYRIGHT")?;
+        writeln!(f, ".PP")?;
+        writeln!(f, "{}", copyright.replace('\n', "\n.br\n"))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,951 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Writes the model out as man pages (or other formats)
+
+mod ascii;
+mod compat;
+#[cfg(feature = "docset")]
+pub mod docset;
+pub mod devhelp;
+pub mod external;
+mod git;
+pub mod html;
+mod man;
+mod symbols;
+mod tidy;
+mod wrap;
+#[cfg(feature = "templates")]
+pub mod template;
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt::Write as fmtwrite;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, BufRead, Write};
+use std::path::Path;
+use chrono::prelude::*;
+
+use crate::cli::Opt;
+use crate::state::RunState;
+use crate::model::*;
+use crate::parser::read_header_copyright;
+pub use man::{print_alias_page, print_defines_page, print_index_page, print_removed_page, print_main_page, print_man_page, print_page_page, print_type_page, render_man_page};
+pub use ascii::{preview_page, print_ascii_pages, print_text_function};
+
+// Written at the top of every page we generate, and used by --no-clobber
+// to tell our pages apart from hand-written ones
+pub const GENERATED_MARKER: &str = "Automatically generated man page";
+
+// The first line of pages with tables in, so that man runs them through tbl
+pub const TBL_PREPROCESSOR_LINE: &str = "'\\\" t";
+
+// The encoding declaration for --utf8, that groff's preconv and man-db look
+// for in the first two lines
+pub const UTF8_CODING_LINE: &str = ".\\\" -*- coding: UTF-8 -*-";
+
+// Comment near the top of the pages with a hash of the options they were made
+// with, so a page isn't taken to be up to date after the options change
+pub const OPTIONS_HASH_PREFIX: &str = ".\\\"  Options hash: ";
+
+// Returns true if it's OK to overwrite this file, ie it doesn't exist
+// or it has our marker comment at the top (after the tbl and coding lines, if it has them)
+pub fn is_generated_page(man_file: &str) -> bool
+{
+    match File::open(man_file) {
+        Ok(f) => {
+            let mut reader = BufReader::new(f);
+            let mut first_line = String::new();
+            if reader.read_line(&mut first_line).is_err() {
+                return false;
+            }
+            if first_line.trim_end() == TBL_PREPROCESSOR_LINE {
+                first_line.clear();
+                if reader.read_line(&mut first_line).is_err() {
+                    return false;
+                }
+            }
+            if first_line.trim_end() == UTF8_CODING_LINE {
+                first_line.clear();
+                if reader.read_line(&mut first_line).is_err() {
+                    return false;
+                }
+            }
+            first_line.contains(GENERATED_MARKER)
+        }
+        Err(e) => e.kind() == std::io::ErrorKind::NotFound,
+    }
+}
+
+// Whether a function should have a page, callback typedefs only get one with
+// --callback-pages, static functions don't get one with --exclude-static,
+// internal ones need --include-internal, ones in \cond blocks need --enabled-sections
+// and with --public-prefix only the public API gets them
+pub fn wants_page(opt: &Opt, state: &RunState, function: &FunctionInfo) -> bool
+{
+    if function.fn_name != opt.headerfile && !is_public(opt, &function.fn_name) {
+        return false;
+    }
+    if function.fn_typedef && !opt.callback_pages {
+        return false;
+    }
+    if function.fn_static && opt.exclude_static {
+        return false;
+    }
+    if is_internal(function) && !opt.include_internal {
+        return false;
+    }
+    if !opt.enabled_sections.is_empty() && function.fn_line != 0 &&
+        state.cond_blocks.hides(&source_file(opt, &function.fn_file), function.fn_line, &opt.enabled_sections) {
+        return false;
+    }
+    true
+}
+
+// Read a --rename-map file, one "<old name> <new name>" per line
+pub fn read_rename_map(map_file: &str) -> Result<Vec<(String, String)>, std::io::Error>
+{
+    let mut renames = Vec::new();
+    for (n, l) in fs::read_to_string(map_file)?.lines().enumerate() {
+        let l = l.trim();
+        if l.is_empty() || l.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = l.split_whitespace().collect();
+        if words.len() != 2 {
+            return Err(std::io::Error::other(format!("line {} should be \"<old name> <new name>\": {}", n + 1, l)));
+        }
+        renames.push((words[0].to_string(), words[1].to_string()));
+    }
+    Ok(renames)
+}
+
+// The old names of a function, from --rename-map
+pub fn old_names<'a>(state: &'a RunState, name: &str) -> Vec<&'a str>
+{
+    state.renames.iter().filter(|(_, new)| new == name).map(|(old, _)| old.as_str()).collect()
+}
+
+// A function that has been removed, for --removed and --removed-file
+#[derive(Debug, Clone)]
+pub struct RemovedFunction {
+    pub name: String,
+    pub version: String,
+    pub replacement: String,
+}
+
+// A --removed option, <name>=<version>[:<replacement>]
+pub fn parse_removed(spec: &str) -> Option<RemovedFunction>
+{
+    let (name, rest) = spec.split_once('=')?;
+    let (version, replacement) = rest.split_once(':').unwrap_or((rest, ""));
+    if name.is_empty() || version.is_empty() {
+        return None;
+    }
+    Some(RemovedFunction {name: name.to_string(), version: version.to_string(), replacement: replacement.to_string()})
+}
+
+// Read a --removed-file, one "<name> <version> [<replacement>]" per line
+pub fn read_removed_file(removed_file: &str) -> Result<Vec<RemovedFunction>, std::io::Error>
+{
+    let mut removed = Vec::new();
+    for (n, l) in fs::read_to_string(removed_file)?.lines().enumerate() {
+        let l = l.trim();
+        if l.is_empty() || l.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = l.split_whitespace().collect();
+        if words.len() != 2 && words.len() != 3 {
+            return Err(std::io::Error::other(format!("line {} should be \"<name> <version> [<replacement>]\": {}", n + 1, l)));
+        }
+        removed.push(RemovedFunction {name: words[0].to_string(), version: words[1].to_string(),
+                                      replacement: words.get(2).unwrap_or(&"").to_string()});
+    }
+    Ok(removed)
+}
+
+// Whether a function or type is in the public API, ie its name starts with one of
+// the --public-prefix prefixes (or doesn't, with --invert-prefix)
+pub fn is_public(opt: &Opt, name: &str) -> bool
+{
+    if opt.public_prefix.is_empty() {
+        return true;
+    }
+    opt.public_prefix.iter().any(|p| name.starts_with(p.as_str())) != opt.invert_prefix
+}
+
+// Find a header file named in the XML, as it is or in --header_src_dir
+fn source_file(opt: &Opt, file: &str) -> String
+{
+    let mut candidates = vec![file.to_string(), format!("{}/{}", opt.header_src_dir, file)];
+    if let Some(name) = Path::new(file).file_name() {
+        candidates.push(format!("{}/{}", opt.header_src_dir, name.to_string_lossy()));
+    }
+    candidates.iter().find(|c| Path::new(c).is_file()).cloned().unwrap_or_else(|| file.to_string())
+}
+
+// The arguments of each of a function's compiler attributes called name,
+// eg "1, 2" for nonnull(1, 2) and "" for deprecated
+fn attribute_args<'a>(function: &'a FunctionInfo, name: &'a str) -> impl Iterator<Item = &'a str>
+{
+    function.fn_attributes.iter().filter_map(move |a| {
+        let args = a.strip_prefix(name)?;
+        if args.is_empty() {
+            return Some("");
+        }
+        args.strip_prefix('(')?.strip_suffix(')')
+    })
+}
+
+// What to say about a deprecated function, with its message if it has one
+pub fn deprecation_note(function: &FunctionInfo) -> Option<String>
+{
+    let args = attribute_args(function, "deprecated").next()?;
+    let message = args.trim().trim_matches('"').trim();
+    if message.is_empty() {
+        Some("This function is deprecated.".to_string())
+    } else {
+        Some(format!("This function is deprecated: {message}"))
+    }
+}
+
+pub fn is_noreturn(function: &FunctionInfo) -> bool
+{
+    attribute_args(function, "noreturn").next().is_some()
+}
+
+// Whether an argument (counting from 0) is declared nonnull. nonnull
+// on its own means all of the pointers.
+pub fn is_nonnull(function: &FunctionInfo, arg: usize) -> bool
+{
+    attribute_args(function, "nonnull").any(|args| {
+        if args.trim().is_empty() {
+            function.fn_args.get(arg).is_some_and(|p| p.par_type.contains('*'))
+        } else {
+            args.split(',').any(|n| n.trim().parse::<usize>() == Ok(arg + 1))
+        }
+    })
+}
+
+// A parameter's description, with its default value (in C++) and a note if it must not be NULL
+pub fn param_description(function: &FunctionInfo, arg: usize) -> String
+{
+    let mut desc = function.fn_args.get(arg).map(|p| p.par_desc.clone()).unwrap_or_default();
+    if let Some(p) = function.fn_args.get(arg).filter(|p| !p.par_defval.is_empty()) {
+        if desc.trim().is_empty() {
+            desc = format!("(default: {})", p.par_defval);
+        } else {
+            desc = format!("{} (default: {})", desc.trim_end(), p.par_defval);
+        }
+    }
+    if !is_nonnull(function, arg) {
+        return desc;
+    }
+    if desc.trim().is_empty() {
+        return "Must not be NULL.".to_string();
+    }
+    let desc = desc.trim_end();
+    if desc.ends_with(['.', '!', '?']) {
+        format!("{desc} Must not be NULL.")
+    } else {
+        format!("{desc}. Must not be NULL.")
+    }
+}
+
+// Whether a function's page has tbl tables in it
+pub fn needs_tbl(opt: &Opt, function: &FunctionInfo) -> bool
+{
+    (opt.retval_table && !function.fn_retvals.is_empty()) ||
+        (opt.params_table && opt.print_params && !function.fn_args.is_empty())
+}
+
+// How a parameter's direction is shown, eg "in/out"
+pub fn param_direction(p: &FnParam) -> &str
+{
+    match p.par_direction.as_str() {
+        "inout" => "in/out",
+        d => d,
+    }
+}
+
+// A function is internal if all of its documentation is in \internal sections
+pub fn is_internal(function: &FunctionInfo) -> bool
+{
+    !function.fn_internal_detail.is_empty() &&
+        function.fn_brief.trim().is_empty() &&
+        function.fn_detail.trim().is_empty()
+}
+
+// The DESCRIPTION text for a function, with the \internal sections if --include-internal
+pub fn function_detail(opt: &Opt, function: &FunctionInfo) -> String
+{
+    if !opt.include_internal || function.fn_internal_detail.is_empty() {
+        return function.fn_detail.clone();
+    }
+    if function.fn_detail.is_empty() {
+        return function.fn_internal_detail.clone();
+    }
+    format!("{}\n{}", function.fn_detail.trim_end(), function.fn_internal_detail)
+}
+
+// The #defines printed in a page's DEFINES section. The general page has all of
+// them, and with --link-defines a function's page has the ones its prototype or
+// documentation mentions. Only ALLCAPS ones are printed, for neatness.
+pub fn page_defines<'a>(opt: &Opt, function: &'a FunctionInfo, functions: &'a [FunctionInfo]) -> Vec<&'a HashDefine>
+{
+    let is_shown = |d: &&HashDefine| d.hd_name == d.hd_name.to_ascii_uppercase();
+    if function.fn_name == opt.headerfile || !opt.link_defines {
+        return function.fn_defines.iter().filter(is_shown).collect();
+    }
+    let general = match functions.iter().find(|f| f.fn_name == opt.headerfile) {
+        Some(g) => g,
+        None => return Vec::new(),
+    };
+
+    let mut text = vec![function.fn_type.clone(), function.fn_argsstring.clone(), function_detail(opt, function),
+                        function.fn_returnval.clone(), function.fn_note.clone()];
+    for p in &function.fn_args {
+        text.push(p.par_defval.clone());
+        text.push(p.par_desc.clone());
+    }
+    for rv in &function.fn_retvals {
+        text.push(rv.ret_name.clone());
+        text.push(rv.ret_desc.clone());
+    }
+    let words: Vec<&str> = text.iter().flat_map(|t| t.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')).collect();
+    general.fn_defines.iter().filter(is_shown).filter(|d| words.contains(&d.hd_name.as_str())).collect()
+}
+
+// Whether a structure or enum gets a page of its own, with --type-pages (all
+// of them) or --enum-pages (the enums with any documentation). doxygen calls
+// anonymous enums @0 etc, they can't have one.
+pub fn has_type_page(opt: &Opt, structure: &StructureInfo) -> bool
+{
+    if !is_public(opt, &structure.str_name) || structure.str_name.is_empty() || structure.str_name.starts_with('@') {
+        return false;
+    }
+    match structure.str_type {
+        StructureType::Unknown => false,
+        _ if opt.type_pages => true,
+        StructureType::Enum if opt.enum_pages => {
+            !structure.str_brief.is_empty() || !structure.str_description.is_empty() ||
+                structure.str_members.iter().any(|m| !m.par_brief.is_empty() || !m.par_desc.is_empty())
+        }
+        _ => false,
+    }
+}
+
+// The name of the --defines-page page, eg foo_defines for foo.h
+pub fn defines_page_name(opt: &Opt) -> String
+{
+    let stem = Path::new(&opt.headerfile).file_stem().map_or(opt.headerfile.clone(), |s| s.to_string_lossy().to_string());
+    format!("{stem}_defines")
+}
+
+// The sections for the SimpleSect kinds, in the order they are printed
+pub const SIMPLESECT_SECTIONS: [(&str, &str); 3] = [("attention", "ATTENTION"), ("remark", "REMARKS"), ("invariant", "INVARIANTS")];
+
+// What --source-version is: git describe in --header_src_dir for "auto",
+// otherwise what it says
+pub fn resolve_source_version(opt: &Opt) -> String
+{
+    if opt.source_version != "auto" {
+        return opt.source_version.clone();
+    }
+    match git::describe(Path::new(&opt.header_src_dir)) {
+        Some(v) => v,
+        None => {
+            eprintln!("Cannot get the version from git describe in {}, pages will not have one", opt.header_src_dir);
+            String::new()
+        }
+    }
+}
+
+// The source field of the .TH line: the package, and its version if we know it
+pub fn page_source(opt: &Opt) -> String
+{
+    if opt.source_version.is_empty() {
+        opt.package_name.clone()
+    } else {
+        format!("{} {}", opt.package_name, opt.source_version)
+    }
+}
+
+// The copyright for a page: the \copyright in the function's documentation or the
+// header's, or the one from the command line (or -c)
+pub fn page_copyright(opt: &Opt, function: &FunctionInfo, functions: &[FunctionInfo], copyright: &str) -> String
+{
+    let own = |f: &FunctionInfo| f.fn_simplesects.iter().find(|s| s.ss_kind == "copyright").map(|s| s.ss_text.trim().to_string());
+    let text = own(function).or_else(|| functions.iter().find(|f| f.fn_name == opt.headerfile).and_then(own));
+    match text {
+        Some(t) if t.to_ascii_lowercase().starts_with("copyright") => t,
+        Some(t) => format!("Copyright {t}"),
+        None => copyright.to_string(),
+    }
+}
+
+// The definition of a function for the SYNOPSIS. doxygen doesn't always
+// put "static" and "inline" in it, but people calling it need to know
+pub fn synopsis_definition(function: &FunctionInfo) -> String
+{
+    let has_word = |def: &str, word: &str| def.split(|c: char| !c.is_ascii_alphanumeric() && c != '_').any(|w| w == word);
+
+    let mut def = function.fn_def.clone();
+    if function.fn_inline && !has_word(&def, "inline") && !has_word(&def, "__always_inline") {
+        def = match def.strip_prefix("static ") {
+            Some(rest) => format!("static inline {rest}"),
+            None => format!("inline {def}"),
+        };
+    }
+    if function.fn_static && !has_word(&def, "static") {
+        def = format!("static {def}");
+    }
+    def
+}
+
+// A parameter as (type, name, default value), with the space between the type
+// and name on the end of the type
+fn param_parts(p: &FnParam) -> (String, String, String)
+{
+    let defval = if p.par_defval.is_empty() { String::new() } else { format!(" = {}", p.par_defval) };
+    if p.is_variadic() {
+        return ("...".to_string(), String::new(), defval);
+    }
+    let name = format!("{}{}", p.par_name, p.par_args);
+    if name.is_empty() || p.par_type.ends_with('*') || p.par_type.ends_with('(') {
+        (p.par_type.clone(), name, defval)
+    } else {
+        (format!("{} ", p.par_type), name, defval)
+    }
+}
+
+// A prototype on one line, with the argsstring as doxygen read it from the header
+pub fn compact_prototype(function: &FunctionInfo) -> String
+{
+    let def = synopsis_definition(function);
+    // Callback typedefs' argsstrings start with the ) of (*name)
+    let args = if function.fn_typedef {
+        function.fn_argsstring.trim_start_matches(')')
+    } else {
+        function.fn_argsstring.as_str()
+    };
+    format!("\\fB{def}\\fP{args};")
+}
+
+// The prototype for the SYNOPSIS as lines of troff, for the --prototype-style
+// that aren't one parameter per line. "fit" puts it on one line if it fits in
+// --prototype-width and wraps it at the commas if not, "compact" uses the
+// argsstring as doxygen read it from the header.
+pub fn synopsis_prototype(opt: &Opt, function: &FunctionInfo) -> Option<Vec<String>>
+{
+    let def = synopsis_definition(function);
+    match opt.prototype_style.as_str() {
+        "compact" => Some(vec![compact_prototype(function)]),
+        "fit" => {
+            let mut lines = Vec::new();
+            let mut line = format!("\\fB{def}\\fP(");
+            let mut width = def.len() + 1;
+            // Line up continuation lines after the (, unless that's most of the line already
+            let indent = if width < opt.prototype_width / 2 { width } else { 4 };
+
+            let params: Vec<(String, String, String)> = function.fn_args.iter().map(param_parts).collect();
+            for (i, (par_type, name, defval)) in params.iter().enumerate() {
+                let end = if i + 1 == params.len() { ");" } else { "," };
+                let len = par_type.len() + name.len() + defval.len() + end.len();
+                if i > 0 {
+                    if width + 1 + len > opt.prototype_width {
+                        lines.push(line);
+                        line = " ".repeat(indent);
+                        width = indent;
+                    } else {
+                        line.push(' ');
+                        width += 1;
+                    }
+                }
+                if name.is_empty() && par_type == "..." {
+                    line.push_str(par_type);
+                } else if name.is_empty() {
+                    line.push_str(&format!("\\fB{par_type}\\fP"));
+                } else {
+                    line.push_str(&format!("\\fB{par_type}\\fI{name}\\fP"));
+                }
+                line.push_str(defval);
+                line.push_str(end);
+                width += len;
+            }
+            if params.is_empty() {
+                line.push_str(");");
+            }
+            lines.push(line);
+            Some(lines)
+        }
+        _ => None,
+    }
+}
+
+// What a page is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageKind {
+    Function,
+    Callback,
+    General,
+    Type,
+    Doc,
+    Alias,
+    Removed,
+    Defines,
+}
+
+impl PageKind {
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            PageKind::Function => "function",
+            PageKind::Callback => "callback",
+            PageKind::General => "general",
+            PageKind::Type => "type",
+            PageKind::Doc => "doc",
+            PageKind::Alias => "alias",
+            PageKind::Removed => "removed",
+            PageKind::Defines => "defines",
+        }
+    }
+}
+
+// A man page that has been written (or was already up to date) and the files it was made from
+#[derive(Debug, Clone)]
+pub struct ManPage {
+    pub function: String,
+    pub brief: String,
+    pub kind: PageKind,
+    pub file: String,
+    pub inputs: Vec<String>,
+    pub header: String,
+    // The symbols it documents, for --manifest
+    pub symbols: Vec<String>,
+}
+
+impl ManPage {
+    pub fn new(opt: &Opt, state: &RunState, file: String, function: &FunctionInfo) -> ManPage
+    {
+        let mut inputs = page_inputs(opt, state, &function.fn_refids);
+        inputs.retain(|i| Path::new(i).exists());
+        let kind = if function.fn_name == opt.headerfile {
+            PageKind::General
+        } else if function.fn_typedef {
+            PageKind::Callback
+        } else {
+            PageKind::Function
+        };
+        // The general page has the #defines
+        let symbols = if kind == PageKind::General {
+            function.fn_defines.iter().map(|d| d.hd_name.clone()).collect()
+        } else {
+            vec![function.fn_name.clone()]
+        };
+        ManPage {function: function.fn_name.clone(), brief: function.fn_brief.clone(), kind, file, inputs,
+                 header: opt.headerfile.clone(), symbols}
+    }
+
+    // A --type-pages page, for the structure or enum with this refid
+    pub fn for_type(opt: &Opt, state: &RunState, file: String, refid: &str, structure: &StructureInfo) -> ManPage
+    {
+        let mut inputs = page_inputs(opt, state, &[refid.to_string()]);
+        inputs.retain(|i| Path::new(i).exists());
+        ManPage {function: structure.str_name.clone(), brief: structure.str_brief.clone(), kind: PageKind::Type, file, inputs,
+                 header: opt.headerfile.clone(), symbols: vec![structure.str_name.clone()]}
+    }
+
+    // A --main-page or --doc-pages page, made from the page XML file with this refid (if there is one)
+    pub fn for_doc_page(opt: &Opt, file: String, name: &str, refid: &str, brief: &str) -> ManPage
+    {
+        let mut inputs = vec![format!("{}/{}.xml", opt.xml_dir, refid)];
+        inputs.retain(|i| Path::new(i).exists());
+        ManPage {function: name.to_string(), brief: brief.to_string(), kind: PageKind::Doc, file, inputs,
+                 header: String::new(), symbols: Vec::new()}
+    }
+
+    // A --rename-map .so page for the old name of a function
+    pub fn for_alias(opt: &Opt, state: &RunState, file: String, old_name: &str, function: &FunctionInfo) -> ManPage
+    {
+        let mut inputs = page_inputs(opt, state, &function.fn_refids);
+        inputs.retain(|i| Path::new(i).exists());
+        ManPage {function: old_name.to_string(), brief: function.fn_brief.clone(), kind: PageKind::Alias, file, inputs,
+                 header: opt.headerfile.clone(), symbols: vec![old_name.to_string()]}
+    }
+
+    // A --removed page for a function that isn't there any more
+    pub fn for_removed(opt: &Opt, file: String, removed: &RemovedFunction) -> ManPage
+    {
+        let inputs = if opt.removed_file.is_empty() { Vec::new() } else { vec![opt.removed_file.clone()] };
+        ManPage {function: removed.name.clone(), brief: String::new(), kind: PageKind::Removed, file, inputs,
+                 header: String::new(), symbols: vec![removed.name.clone()]}
+    }
+
+    // The --defines-page page, made from the general page's #defines
+    pub fn for_defines(opt: &Opt, state: &RunState, file: String, general: &FunctionInfo, defines: &[&HashDefine]) -> ManPage
+    {
+        let mut inputs = page_inputs(opt, state, &general.fn_refids);
+        inputs.retain(|i| Path::new(i).exists());
+        ManPage {function: defines_page_name(opt), brief: String::new(), kind: PageKind::Defines, file, inputs,
+                 header: opt.headerfile.clone(), symbols: defines.iter().map(|d| d.hd_name.clone()).collect()}
+    }
+
+    // The --index-page page. It's made from all the other pages rather than any input file
+    pub fn for_index(file: String, name: &str) -> ManPage
+    {
+        ManPage {function: name.to_string(), brief: String::new(), kind: PageKind::Doc, file, inputs: Vec::new(),
+                 header: String::new(), symbols: Vec::new()}
+    }
+}
+
+// The files a page is made from: the main XML file, the XML files for its structures
+// (refids), the header file (if we read the copyright or dates from it), the
+// headers read for --enabled-sections and the files that the options name
+// (--preamble, --po, --ref-map, --tagfile, --rename-map, --removed-file,
+// --template-dir and --wasm-filter).
+// Some of these might not exist (eg enums, which are in the main XML file).
+pub fn page_inputs(opt: &Opt, state: &RunState, refids: &[String]) -> Vec<String>
+{
+    let mut inputs = vec![state.current_input.clone()];
+    for r in refids {
+        inputs.push(format!("{}/{}.xml", opt.xml_dir, r));
+    }
+    if opt.use_header_copyright || opt.years_from_git || opt.date_from_git {
+        inputs.push(format!("{}/{}", opt.header_src_dir, opt.headerfile));
+    }
+    if !opt.preamble.is_empty() {
+        inputs.push(opt.preamble.clone());
+    }
+    if !opt.po.is_empty() {
+        inputs.push(opt.po.clone());
+    }
+    if !opt.enabled_sections.is_empty() {
+        inputs.extend(state.cond_blocks.files());
+    }
+    inputs.extend(opt.ref_map.iter().cloned());
+    for tagfile in &opt.tagfiles {
+        let (file, _section) = tagfile.split_once('=').unwrap_or((tagfile, "3"));
+        inputs.push(file.to_string());
+    }
+    if !opt.rename_map.is_empty() {
+        inputs.push(opt.rename_map.clone());
+    }
+    if !opt.removed_file.is_empty() {
+        inputs.push(opt.removed_file.clone());
+    }
+    #[cfg(feature = "templates")]
+    if !opt.template_dir.is_empty() {
+        for template_name in ["function", "general"] {
+            inputs.push(format!("{}/{}.{}.tera", opt.template_dir, template_name, opt.man_section));
+        }
+    }
+    #[cfg(feature = "wasm")]
+    inputs.extend(opt.wasm_filter.iter().cloned());
+    inputs
+}
+
+// FNV-1a, it only needs to be the same every time, not secure
+fn content_hash(text: &str) -> u64
+{
+    text.bytes().fold(0xcbf29ce484222325, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+// A hash of our version and the options that can change what's in a page.
+// The options that only say which pages to write, where to or what else to
+// do with them are left out, so eg adding --lint doesn't make every page again.
+pub fn options_hash(opt: &Opt) -> u64
+{
+    let page_options = Opt {
+        print_ascii: false, print_man: false, _quiet: false, only: Vec::new(), streaming: false,
+        no_clobber: false, xml_dir: String::new(), output_dir: String::new(), header_src_dir: String::new(),
+        #[cfg(feature = "json")]
+        write_json: false,
+        #[cfg(feature = "tarball")]
+        output_tar: String::new(),
+        #[cfg(feature = "docset")]
+        docset: String::new(),
+        #[cfg(feature = "browse")]
+        browse: false,
+        devhelp: String::new(), depfile: String::new(), write_deps: String::new(), output_list: String::new(),
+        manifest: String::new(), preview: String::new(), preview_command: String::new(), stdout: String::new(),
+        packaging_lists: false, rpm_man_suffix: String::new(), diagnostics: String::new(),
+        diagnostics_file: String::new(), sarif: String::new(), strict_empty: false, coverage: String::new(),
+        min_coverage: 0.0, min_param_coverage: 0.0, baseline: String::new(), write_baseline: String::new(),
+        write_pot: String::new(), serve: false, port: 0, dry_run: false, list: false, verify: String::new(),
+        lint: false, spellcheck: false, spell_dict: String::new(), spell_words: String::new(),
+        update_mandb: false, mandb_command: String::new(), timings: false, force: false, xml_files: Vec::new(),
+        ..opt.clone()
+    };
+    content_hash(&format!("{} {:?}", env!("CARGO_PKG_VERSION"), page_options))
+}
+
+// Add the options hash comment after the "Automatically generated" line. Pages
+// without one (eg from a template that leaves the line out) are always made again.
+pub fn add_options_hash(opt: &Opt, text: &str) -> String
+{
+    if !text.contains(GENERATED_MARKER) {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len() + 40);
+    let mut added = false;
+    for l in text.lines() {
+        out.push_str(l);
+        out.push('\n');
+        if !added && l.contains(GENERATED_MARKER) {
+            out.push_str(&format!("{OPTIONS_HASH_PREFIX}{:016x}\n", options_hash(opt)));
+            added = true;
+        }
+    }
+    out
+}
+
+// The options hash that a page was made with, from near the top of it
+fn page_options_hash(man_file: &str) -> Option<u64>
+{
+    let reader = BufReader::new(File::open(man_file).ok()?);
+    reader.lines().take(8).map_while(Result::ok)
+        .find_map(|l| l.strip_prefix(OPTIONS_HASH_PREFIX).and_then(|h| u64::from_str_radix(h.trim(), 16).ok()))
+}
+
+// Returns true if the page already exists, is newer than all of the files it's made from
+// and was made with the same options. Input files that don't exist are ignored.
+pub fn is_up_to_date(opt: &Opt, state: &RunState, man_file: &str, refids: &[String]) -> bool
+{
+    let modified = |f: &str| fs::metadata(f).and_then(|m| m.modified());
+
+    let page_time = match modified(man_file) {
+        Ok(t) => t,
+        Err(_) => return false,
+    };
+
+    page_options_hash(man_file) == Some(options_hash(opt)) &&
+        page_inputs(opt, state, refids).iter().all(|i| match modified(i) {
+            Ok(t) => t <= page_time,
+            Err(_) => true,
+        })
+}
+
+// Write a file via a temporary file in the same directory and rename it into
+// place when it's complete, so an interrupted run never leaves a partial file behind
+pub fn write_atomically<F>(filename: &str, write_fn: F) -> Result<(), std::io::Error>
+    where F: FnOnce(&mut BufWriter<File>) -> Result<(), std::io::Error>
+{
+    let path = Path::new(filename);
+    let mut tmp_name = OsString::from(".");
+    if let Some(n) = path.file_name() {
+        tmp_name.push(n);
+    }
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_file = path.with_file_name(tmp_name);
+
+    let fl = match File::create(&tmp_file) {
+        Ok(fl) => fl,
+        Err(e) => {
+            println!("Cannot create file {}: {}", tmp_file.display(), e);
+            return Err(e);
+        }
+    };
+
+    let mut f = BufWriter::new(fl);
+    let res = write_fn(&mut f)
+        .and_then(|_| f.flush())
+        .and_then(|_| f.get_ref().sync_all())
+        .and_then(|_| std::fs::rename(&tmp_file, path));
+    if let Err(e) = res {
+        println!("Cannot write file {}: {}", filename, e);
+        let _ = std::fs::remove_file(&tmp_file);
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Today's date, unless SOURCE_DATE_EPOCH is set in which case we use that
+// so that builds are reproducible.
+// See https://reproducible-builds.org/specs/source-date-epoch/
+fn get_today() -> NaiveDate
+{
+    if let Ok(epoch) = std::env::var("SOURCE_DATE_EPOCH") {
+        match epoch.trim().parse::<i64>() {
+            Ok(secs) => {
+                if let Some(d) = Utc.timestamp_opt(secs, 0).single() {
+                    return d.date_naive();
+                }
+                eprintln!("SOURCE_DATE_EPOCH is out of range: {epoch}");
+            }
+            Err(e) => {
+                eprintln!("Cannot parse SOURCE_DATE_EPOCH {epoch}: {e}");
+            }
+        }
+    }
+    Local::now().date_naive()
+}
+
+// Work out the date for the top of the pages and the copyright line for the bottom
+pub fn get_date_and_copyright(opt: &Opt) -> Result<(String, String), std::fmt::Error>
+{
+    let mut date_to_print = String::new();
+    let mut header_copyright = String::new();
+    let mut manpage_year: i32 = opt.manpage_year;
+
+    // Get current date
+    let today = get_today();
+    let header_path = Path::new(&opt.header_src_dir).join(&opt.headerfile);
+
+    if opt.date_from_git {
+        let date = git::last_commit_date(&header_path).unwrap_or(today);
+        write!(date_to_print, "{}-{}-{}", date.year(), date.month(), date.day())?;
+    } else if !opt.manpage_date.is_empty() {
+        date_to_print = opt.manpage_date.clone();
+    } else {
+        write!(date_to_print, "{}-{}-{}", today.year(), today.month(), today.day())?;
+    }
+
+    if manpage_year == 0 {
+        manpage_year = today.year();
+    }
+
+    if opt.use_header_copyright {
+        if let Ok(s) = read_header_copyright(&opt.header_src_dir, &opt.headerfile, &opt.copyright_regex) {
+            header_copyright = s;
+        }
+    } else {
+        let mut start_year = opt.start_year as i32;
+        if opt.years_from_git {
+            if let Some((first, last)) = git::copyright_years(&header_path) {
+                start_year = first;
+                manpage_year = last;
+            }
+        }
+        write!(header_copyright, "Copyright (C) {}-{} {}, All rights reserved",
+               start_year, manpage_year, opt.company)?;
+    }
+    Ok((date_to_print, header_copyright))
+}
+
+// Print all man pages, returns the pages (and the files they were made from)
+// and how many pages couldn't be written
+pub fn print_man_pages(opt: &Opt,
+                       state: &RunState,
+                       functions: &[FunctionInfo],
+                       structures: &BTreeMap<String, StructureInfo>) -> Result<(Vec<ManPage>, usize), std::fmt::Error>
+{
+    let (date_to_print, header_copyright) = get_date_and_copyright(opt)?;
+
+    let mut pages = Vec::new();
+    let mut failed = 0;
+    for f in functions {
+        match print_man_page(opt, state, &date_to_print, f, functions, structures, &header_copyright) {
+            Ok(Some(file)) => pages.push(ManPage::new(opt, state, file, f)),
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("Error writing man page for {}: {}", f.fn_name, e);
+                failed += 1;
+                continue;
+            }
+        }
+        for old in old_names(state, &f.fn_name) {
+            match print_alias_page(opt, old, f) {
+                Ok(Some(file)) => pages.push(ManPage::for_alias(opt, state, file, old, f)),
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("Error writing man page for {old}: {e}");
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if !opt.defines_page.is_empty() {
+        if let Some(general) = functions.iter().find(|f| f.fn_name == opt.headerfile) {
+            let defines = page_defines(opt, general, functions);
+            if !defines.is_empty() {
+                match print_defines_page(opt, state, &date_to_print, general, &defines, &header_copyright) {
+                    Ok(Some(file)) => pages.push(ManPage::for_defines(opt, state, file, general, &defines)),
+                    Ok(None) => {}
+                    Err(e) => {
+                        eprintln!("Error writing man page for {}: {}", defines_page_name(opt), e);
+                        failed += 1;
+                    }
+                }
+            }
+        }
+    }
+    if opt.type_pages || opt.enum_pages {
+        for (refid, s) in structures.iter().filter(|(_, s)| has_type_page(opt, s)) {
+            match print_type_page(opt, state, &date_to_print, refid, s, functions, &header_copyright) {
+                Ok(Some(file)) => pages.push(ManPage::for_type(opt, state, file, refid, s)),
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("Error writing man page for {}: {}", s.str_name, e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    Ok((pages, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    #[test]
+    fn removed_spec()
+    {
+        let r = parse_removed("foo_open=2.0:foo_open2").unwrap();
+        assert_eq!((r.name.as_str(), r.version.as_str(), r.replacement.as_str()), ("foo_open", "2.0", "foo_open2"));
+        let r = parse_removed("foo_close=1.5").unwrap();
+        assert_eq!((r.name.as_str(), r.version.as_str(), r.replacement.as_str()), ("foo_close", "1.5", ""));
+        assert!(parse_removed("foo_close").is_none());
+        assert!(parse_removed("=1.5").is_none());
+        assert!(parse_removed("foo_close=").is_none());
+        assert!(parse_removed("foo_close=:bar").is_none());
+    }
+
+    fn opt(args: &[&str]) -> Opt
+    {
+        Opt::from_iter(["doxygen2man"].iter().chain(args).chain(&["a_8h.xml"]))
+    }
+
+    #[test]
+    fn options_hash_ignores_output_options()
+    {
+        let hash = options_hash(&opt(&[]));
+        assert_eq!(options_hash(&opt(&["-m", "--force", "-o", "man", "--lint"])), hash);
+        assert_ne!(options_hash(&opt(&["-P"])), hash);
+        assert_ne!(options_hash(&opt(&["--symbols", "strip"])), hash);
+    }
+
+    #[test]
+    fn up_to_date()
+    {
+        let dir = std::env::temp_dir().join(format!("doxygen2man-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("a_8h.xml");
+        let page = dir.join("a.3").to_string_lossy().to_string();
+        fs::write(&input, "<doxygen/>").unwrap();
+        let state = RunState {current_input: input.to_string_lossy().to_string(), ..Default::default()};
+        let options = opt(&["-m"]);
+
+        assert!(!is_up_to_date(&options, &state, &page, &[]));
+        fs::write(&page, add_options_hash(&options, &format!(".\\\" {GENERATED_MARKER}\n.TH a 3\n"))).unwrap();
+        assert!(is_up_to_date(&options, &state, &page, &[]));
+        // The structure files it was made from are inputs too, if they exist
+        assert!(is_up_to_date(&options, &state, &page, &["structnone".to_string()]));
+        // Different options
+        assert!(!is_up_to_date(&opt(&["-m", "-P"]), &state, &page, &[]));
+        // A newer input
+        let later = fs::metadata(&page).unwrap().modified().unwrap() + std::time::Duration::from_secs(10);
+        File::options().write(true).open(&input).unwrap().set_modified(later).unwrap();
+        assert!(!is_up_to_date(&options, &state, &page, &[]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --symbols. Emoji and typographic symbols in the comments that older troffs
+// can't cope with are stripped, written as text or written as \[uXXXX] escapes.
+// Without --utf8 the pages are plain ASCII, so whatever is left is escaped.
+
+// Symbols that have a plain ASCII version, used by strip and text
+fn ascii_symbol(c: char) -> Option<&'static str>
+{
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{2032}' => Some("'"),
+        '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{2033}' => Some("\""),
+        '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2212}' => Some("-"),
+        '\u{2014}' | '\u{2015}' => Some("--"),
+        '\u{2026}' => Some("..."),
+        '\u{2022}' | '\u{00b7}' => Some("*"),
+        '\u{00a0}' | '\u{2002}' | '\u{2003}' | '\u{2009}' | '\u{202f}' => Some(" "),
+        '\u{00a9}' => Some("(C)"),
+        '\u{00ae}' => Some("(R)"),
+        '\u{2122}' => Some("(TM)"),
+        '\u{2192}' => Some("->"),
+        '\u{2190}' => Some("<-"),
+        '\u{21d2}' => Some("=>"),
+        '\u{00d7}' => Some("x"),
+        '\u{2264}' => Some("<="),
+        '\u{2265}' => Some(">="),
+        '\u{2260}' => Some("!="),
+        _ => None,
+    }
+}
+
+// The names of some common emoji, as doxygen's \emoji uses them, for text
+fn emoji_name(c: char) -> Option<&'static str>
+{
+    match c {
+        '\u{26a0}' => Some(":warning:"),
+        '\u{2139}' => Some(":information_source:"),
+        '\u{2705}' => Some(":white_check_mark:"),
+        '\u{2714}' => Some(":heavy_check_mark:"),
+        '\u{274c}' => Some(":x:"),
+        '\u{2b50}' => Some(":star:"),
+        '\u{26d4}' => Some(":no_entry:"),
+        '\u{1f4a1}' => Some(":bulb:"),
+        '\u{1f4dd}' => Some(":memo:"),
+        '\u{1f41b}' => Some(":bug:"),
+        '\u{1f525}' => Some(":fire:"),
+        '\u{1f680}' => Some(":rocket:"),
+        '\u{1f44d}' => Some(":+1:"),
+        '\u{1f6a7}' => Some(":construction:"),
+        _ => None,
+    }
+}
+
+// Characters that only change how the one before looks (variation selectors
+// and joiners), these go with it
+fn is_modifier(c: char) -> bool
+{
+    matches!(c, '\u{200d}' | '\u{fe0e}' | '\u{fe0f}' | '\u{20e3}') || ('\u{1f3fb}'..='\u{1f3ff}').contains(&c)
+}
+
+// Apply a --symbols policy (keep, strip, text or escape) to a page that
+// isn't UTF-8, keep and escape are the same here
+pub fn normalize_symbols(page: &str, policy: &str) -> String
+{
+    if page.is_ascii() {
+        return page.to_string();
+    }
+    let mut out = String::with_capacity(page.len());
+    for c in page.chars() {
+        if c.is_ascii() {
+            out.push(c);
+            continue;
+        }
+        // Letters (eg in people's names) are kept, as escapes
+        if policy == "keep" || policy == "escape" || c.is_alphanumeric() {
+            out.push_str(&format!("\\[u{:04X}]", c as u32));
+            continue;
+        }
+        if is_modifier(c) {
+            continue;
+        }
+        if let Some(a) = ascii_symbol(c) {
+            out.push_str(a);
+        } else if policy == "text" {
+            match emoji_name(c) {
+                Some(name) => out.push_str(name),
+                None => out.push_str(&format!("U+{:04X}", c as u32)),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = "Jos\u{e9} says \u{201c}hi\u{201d} \u{2014} \u{26a0}\u{fe0f} \u{2603}\n";
+
+    #[test]
+    fn keep_and_escape()
+    {
+        let escaped = "Jos\\[u00E9] says \\[u201C]hi\\[u201D] \\[u2014] \\[u26A0]\\[uFE0F] \\[u2603]\n";
+        assert_eq!(normalize_symbols(PAGE, "keep"), escaped);
+        assert_eq!(normalize_symbols(PAGE, "escape"), escaped);
+    }
+
+    #[test]
+    fn strip()
+    {
+        assert_eq!(normalize_symbols(PAGE, "strip"), "Jos\\[u00E9] says \"hi\" --  \n");
+    }
+
+    #[test]
+    fn text()
+    {
+        assert_eq!(normalize_symbols(PAGE, "text"), "Jos\\[u00E9] says \"hi\" -- :warning: U+2603\n");
+    }
+
+    #[test]
+    fn ascii_unchanged()
+    {
+        assert_eq!(normalize_symbols("plain \\(em text\n", "strip"), "plain \\(em text\n");
+    }
+}
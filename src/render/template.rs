@@ -0,0 +1,84 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// User-supplied Tera templates for the page layout
+
+use std::collections::BTreeMap;
+use std::fmt::Write as fmtwrite;
+use std::io::Error;
+use std::path::Path;
+
+use crate::cli::Opt;
+use crate::model::*;
+use super::GENERATED_MARKER;
+
+// Load any user-supplied page templates from --template-dir
+pub fn load_templates(opt: &Opt) -> Option<tera::Tera>
+{
+    if opt.template_dir.is_empty() {
+        return None;
+    }
+
+    let mut tera = tera::Tera::default();
+    for template_name in ["function", "general"] {
+        let mut t_file = String::new();
+        if write!(t_file, "{}/{}.{}.tera", &opt.template_dir, template_name, opt.man_section).is_err() {
+            continue;
+        }
+        if Path::new(&t_file).exists() {
+            if let Err(e) = tera.add_template_file(&t_file, Some(template_name)) {
+                eprintln!("Error loading template {t_file}: {e}");
+            }
+        }
+    }
+    Some(tera)
+}
+
+// Render a page using a user-supplied template, the model is passed in
+// with the same field names as the JSON model (see README.md)
+#[allow(clippy::too_many_arguments)]
+pub fn render_template(t: &tera::Tera,
+                       template_name: &str,
+                       opt: &Opt,
+                       dateptr: &str,
+                       function: &FunctionInfo,
+                       functions: &[FunctionInfo],
+                       structures: &BTreeMap<String, StructureInfo>,
+                       copyright: &str) -> Result<String, std::io::Error>
+{
+    let fn_structures: Vec<&StructureInfo> = function.fn_refids.iter()
+        .filter_map(|r| structures.get(r))
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert("function", function);
+    context.insert("functions", functions);
+    context.insert("structures", &fn_structures);
+    context.insert("date", dateptr);
+    context.insert("section", &opt.man_section);
+    context.insert("package_name", &opt.package_name);
+    context.insert("source_version", &opt.source_version);
+    context.insert("header", &opt.header);
+    context.insert("headerfile", &opt.headerfile);
+    context.insert("header_prefix", &opt.header_prefix);
+    context.insert("print_params", &opt.print_params);
+    context.insert("copyright", copyright);
+    context.insert("generated_marker", GENERATED_MARKER);
+
+    // tera's own message is only "Failed to render", what went wrong is in its sources
+    t.render(template_name, &context).map_err(|e| {
+        let mut message = format!("Error rendering template {}/{}.{}.tera: {}", opt.template_dir, template_name, opt.man_section, e);
+        let mut source = std::error::Error::source(&e);
+        while let Some(s) = source {
+            message += &format!(": {s}");
+            source = s.source();
+        }
+        Error::other(message)
+    })
+}
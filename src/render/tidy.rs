@@ -0,0 +1,121 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// The last pass over a page before it is written (unless --no-tidy). How much
+// whitespace and how many .PPs end up in a page depends on how the comments
+// were laid out, this makes it the same each time so that diffs of pages that
+// are kept in git only show real changes.
+
+// Requests that start a paragraph of their own, so a .PP just before one does nothing
+fn starts_paragraph(line: &str) -> bool
+{
+    [".PP", ".SH", ".SS", ".TP", ".IP", ".LP", ".P"].iter()
+        .any(|r| line == *r || line.starts_with(&format!("{r} ")))
+}
+
+// Take the whitespace off the end of a line, but not an escaped space ("\\ ")
+fn trim_line(line: &str) -> &str
+{
+    let trimmed = line.trim_end();
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+// Tidy a man page: no trailing whitespace, blank lines outside listings and
+// tables become paragraph breaks, and .PPs that don't do anything are dropped
+pub fn tidy_man_page(page: &str) -> String
+{
+    let mut lines = Vec::<&str>::new();
+    let mut in_nf = false;
+    for l in page.lines() {
+        let l = trim_line(l);
+        if l.starts_with(".nf") || l.starts_with(".EX") || l.starts_with(".TS") {
+            in_nf = true;
+        } else if l.starts_with(".fi") || l.starts_with(".EE") || l.starts_with(".TE") {
+            in_nf = false;
+        }
+        if !in_nf && (l.is_empty() || l == ".PP") {
+            // Not at the start, or straight after another paragraph request
+            // (but the pages have always had one after their headings)
+            if lines.last().is_some_and(|p| !starts_paragraph(p) || p.starts_with(".SH") || p.starts_with(".SS")) {
+                lines.push(".PP");
+            }
+            continue;
+        }
+        // A .PP that is followed by another paragraph request does nothing
+        if !in_nf && starts_paragraph(l) && lines.last() == Some(&".PP") {
+            lines.pop();
+        }
+        lines.push(l);
+    }
+    // Nor does one at the end
+    while lines.last() == Some(&".PP") {
+        lines.pop();
+    }
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+// Tidy an HTML page: no trailing whitespace, and no runs of blank lines outside <pre>
+pub fn tidy_html_page(page: &str) -> String
+{
+    let mut out = String::with_capacity(page.len());
+    let mut in_pre = false;
+    let mut last_blank = false;
+    for l in page.lines() {
+        let l = l.trim_end();
+        if l.contains("<pre") {
+            in_pre = true;
+        }
+        if !in_pre && l.is_empty() {
+            if last_blank {
+                continue;
+            }
+            last_blank = true;
+        } else {
+            last_blank = false;
+        }
+        if l.contains("</pre>") {
+            in_pre = false;
+        }
+        out.push_str(l);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paragraphs()
+    {
+        let page = ".SH NAME\n.PP\nfoo  \n\n\nbar\n.PP\n.TP\nx\n.PP\n";
+        assert_eq!(tidy_man_page(page), ".SH NAME\n.PP\nfoo\n.PP\nbar\n.TP\nx\n");
+    }
+
+    #[test]
+    fn escaped_space()
+    {
+        assert_eq!(tidy_man_page("a\\ \nb \n"), "a\\ \nb\n");
+    }
+
+    #[test]
+    fn listings_left_alone()
+    {
+        let page = ".nf\na  b\n\nc\n.fi\n";
+        assert_eq!(tidy_man_page(page), ".nf\na  b\n\nc\n.fi\n");
+    }
+}
@@ -0,0 +1,132 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --source-width. Whole descriptions end up on one line of the page source,
+// which makes diffs of pages that are kept in git hard to read. In fill mode a
+// newline is the same as a space, so the text lines can be split at spaces
+// without changing how the page looks, as long as none of the new lines starts
+// a request or ends a sentence (which gets a wider space after it).
+
+// Whether troff would see the end of a sentence if a line ended with this word.
+// Closing quotes and brackets and font changes after the . don't hide it.
+fn ends_sentence(word: &str) -> bool
+{
+    let mut word = word;
+    loop {
+        let trimmed = word.trim_end_matches(['"', '\'', ')', ']', '*']);
+        // \fB or \f(CR
+        let trimmed = match trimmed.char_indices().rev().nth(2) {
+            Some((p, _)) if trimmed[p..].starts_with("\\f") && !trimmed[p..].starts_with("\\f(") => &trimmed[..p],
+            _ => match trimmed.char_indices().rev().nth(4) {
+                Some((p, _)) if trimmed[p..].starts_with("\\f(") => &trimmed[..p],
+                _ => trimmed,
+            },
+        };
+        if trimmed.len() == word.len() {
+            return word.ends_with(['.', '?', '!']);
+        }
+        word = trimmed;
+    }
+}
+
+// The places a text line can be split: single spaces that aren't escaped,
+// after a word that doesn't end a sentence and before one that isn't a request
+fn can_split(line: &str, space: usize) -> bool
+{
+    let bytes = line.as_bytes();
+    if space == 0 || space + 1 >= bytes.len() {
+        return false;
+    }
+    let before = bytes[space - 1];
+    let after = bytes[space + 1];
+    if before == b' ' || before == b'\\' || after == b' ' || after == b'.' || after == b'\'' {
+        return false;
+    }
+    let word_start = line[..space].rfind(' ').map_or(0, |p| p + 1);
+    !ends_sentence(&line[word_start..space])
+}
+
+// Split one text line into lines of no more than width columns where it can
+fn wrap_line(line: &str, width: usize, out: &mut String)
+{
+    let mut rest = line;
+    while rest.chars().count() > width {
+        // The last place to split that fits, or the first one if nothing does
+        let spaces: Vec<usize> = rest.match_indices(' ').map(|(i, _)| i).filter(|&i| can_split(rest, i)).collect();
+        let split = spaces.iter().rev().find(|&&i| rest[..i].chars().count() <= width)
+            .or_else(|| spaces.first());
+        match split {
+            Some(&i) => {
+                out.push_str(&rest[..i]);
+                out.push('\n');
+                rest = &rest[i + 1..];
+            }
+            None => break,
+        }
+    }
+    out.push_str(rest);
+    out.push('\n');
+}
+
+// Wrap the text lines of a man page at width columns. Requests, comments,
+// listings and tables and the tags of .TP paragraphs are left alone.
+pub fn wrap_man_page(page: &str, width: usize) -> String
+{
+    let mut out = String::with_capacity(page.len() + page.len() / width.max(1));
+    let mut in_nf = false;
+    let mut tag_next = false;
+    for l in page.lines() {
+        if l.starts_with(".nf") || l.starts_with(".EX") || l.starts_with(".TS") {
+            in_nf = true;
+        } else if l.starts_with(".fi") || l.starts_with(".EE") || l.starts_with(".TE") {
+            in_nf = false;
+        }
+        let is_request = l.starts_with('.') || l.starts_with('\'');
+        if in_nf || is_request || tag_next || l.contains("\\\"") {
+            out.push_str(l);
+            out.push('\n');
+        } else {
+            wrap_line(l, width, &mut out);
+        }
+        tag_next = l == ".TP" || l.starts_with(".TP ") || l == ".TQ";
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentence_ends()
+    {
+        assert!(ends_sentence("end."));
+        assert!(ends_sentence("end?)"));
+        assert!(ends_sentence("\\fBend.\\fR"));
+        assert!(ends_sentence("\\fIend.\\f(CR\""));
+        assert!(!ends_sentence("e.g"));
+        assert!(!ends_sentence("\\fBword\\fR"));
+    }
+
+    #[test]
+    fn wrap_text()
+    {
+        assert_eq!(wrap_man_page("aaa bbb ccc ddd\n", 7), "aaa bbb\nccc ddd\n");
+        // Not after the end of a sentence, or before a . or '
+        assert_eq!(wrap_man_page("One. Two three\n", 4), "One. Two\nthree\n");
+        assert_eq!(wrap_man_page("a .b 'c\n", 1), "a .b 'c\n");
+    }
+
+    #[test]
+    fn requests_left_alone()
+    {
+        let page = ".TP\nvery long tag\ntext a b\n.nf\nx y z\n.fi\n";
+        assert_eq!(wrap_man_page(page, 3), ".TP\nvery long tag\ntext\na b\n.nf\nx y z\n.fi\n");
+    }
+}
@@ -0,0 +1,191 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --serve. The pages are rendered as HTML and served on localhost with an
+// index, for looking at the documentation while writing it. When any of the
+// input files changes they are all read again, and the pages in the browser
+// (which ask for the /generation every second) reload themselves.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use doxygen2man::cli::Opt;
+use doxygen2man::state::RunState;
+use doxygen2man::model::*;
+use doxygen2man::render::get_date_and_copyright;
+use doxygen2man::render::html::{html_escape, html_page_name, render_html_page};
+use super::{read_input, set_current_input, translate_functions};
+#[cfg(feature = "json")]
+use super::apply_filters;
+
+// How long a connection can wait before sending its request. Browsers open
+// connections that they might not use, those are dropped after this.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Put into each page so that it reloads when the pages are made again
+const RELOAD_SCRIPT: &str = "<script>
+(function() {
+  var generation = null;
+  setInterval(function() {
+    fetch('/generation').then(function(r) { return r.text(); }).then(function(g) {
+      if (generation === null) { generation = g; } else if (g !== generation) { location.reload(); }
+    }).catch(function() {});
+  }, 1000);
+})();
+</script>
+";
+
+// The pages as they were last made
+struct Site {
+    pages: BTreeMap<String, String>,
+    index: String,
+    generation: u64,
+    // The newest input file when the pages were made
+    newest: Option<SystemTime>,
+}
+
+// The time the newest input file (or anything in the XML directory) was changed
+fn newest_input(opt: &Opt) -> Option<SystemTime>
+{
+    let mut files: Vec<String> = opt.xml_files.iter().map(|f| format!("{}/{}", opt.xml_dir, f)).collect();
+    files.extend(opt.xml_files.iter().cloned());
+    if let Ok(dir) = fs::read_dir(&opt.xml_dir) {
+        files.extend(dir.filter_map(|e| e.ok()).map(|e| e.path().to_string_lossy().to_string()));
+    }
+    files.iter()
+        .filter_map(|f| fs::metadata(f).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+fn add_reload_script(page: &str) -> String
+{
+    match page.rfind("</body>") {
+        Some(p) => format!("{}{}{}", &page[..p], RELOAD_SCRIPT, &page[p..]),
+        None => format!("{page}{RELOAD_SCRIPT}"),
+    }
+}
+
+// Read all of the input files and make the HTML pages and the index
+fn make_site(opt: &mut Opt, state: &mut RunState, generation: u64) -> Site
+{
+    let newest = newest_input(opt);
+    let mut pages = BTreeMap::new();
+    let mut index = String::new();
+    index += "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n";
+    index += &format!("<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n",
+                      html_escape(&opt.package_name), html_escape(&opt.package_name));
+
+    // Everything is read again, the structures might have changed too
+    let mut structure_cache = BTreeMap::<String, StructureInfo>::new();
+    for in_file in &opt.xml_files.clone() {
+        set_current_input(opt, state, in_file);
+        let (functions, structures) = match read_input(opt, state, in_file, &mut structure_cache) {
+            Some(m) => m,
+            None => continue,
+        };
+        #[cfg(feature = "json")]
+        let (functions, structures) = match apply_filters(opt, in_file, functions, structures) {
+            Some(m) => m,
+            None => continue,
+        };
+        let functions = translate_functions(state, functions);
+        let copyright = match get_date_and_copyright(opt) {
+            Ok((_, c)) => c,
+            Err(e) => {
+                eprintln!("Error making date and copyright: {e}");
+                continue;
+            }
+        };
+
+        index += &format!("<h2>{}</h2>\n<ul>\n", html_escape(&opt.headerfile));
+        for f in &functions {
+            let name = html_page_name(&f.fn_name);
+            let page = render_html_page(opt, f, &functions, &structures, &copyright);
+            index += &format!("<li><a href=\"{}\">{}</a> - {}</li>\n",
+                              html_escape(&name), html_escape(&f.fn_name), html_escape(f.fn_brief.trim()));
+            pages.insert(name, add_reload_script(&page));
+        }
+        index += "</ul>\n";
+    }
+    index += "</body>\n</html>\n";
+    Site {pages, index: add_reload_script(&index), generation, newest}
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> Result<(), Error>
+{
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+           status, content_type, body.len(), body)?;
+    stream.flush()
+}
+
+fn handle(stream: &mut TcpStream, site: &Site) -> Result<(), Error>
+{
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(&*stream);
+    let mut request = String::new();
+    // Connections that the browser opened in case it needed them are closed
+    // without a request, or time out
+    match reader.read_line(&mut request) {
+        Ok(0) => return Ok(()),
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => return Ok(()),
+        Err(e) => return Err(e),
+    }
+    // The rest of the headers aren't needed, but they are read so the browser doesn't see a reset
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let path = path.split('?').next().unwrap_or("/");
+    match path {
+        "/" | "/index.html" => respond(stream, "200 OK", "text/html; charset=utf-8", &site.index),
+        "/generation" => respond(stream, "200 OK", "text/plain", &site.generation.to_string()),
+        _ => match site.pages.get(path.trim_start_matches('/')) {
+            Some(page) => respond(stream, "200 OK", "text/html; charset=utf-8", page),
+            None => respond(stream, "404 Not Found", "text/plain", "No such page\n"),
+        },
+    }
+}
+
+// Serve the pages on localhost:port until we are killed. Each connection is
+// handled on a thread of its own, so one that is slow to send its request
+// doesn't hold up the rest.
+pub fn serve(opt: &mut Opt, state: &mut RunState, port: u16) -> Result<(), Error>
+{
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let mut site = Arc::new(make_site(opt, state, 1));
+    eprintln!("Serving {} pages on http://{}/", site.pages.len(), listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Error accepting connection: {e}");
+                continue;
+            }
+        };
+        if newest_input(opt) != site.newest {
+            eprintln!("Input changed, making the pages again");
+            site = Arc::new(make_site(opt, state, site.generation + 1));
+        }
+        let site = Arc::clone(&site);
+        thread::spawn(move || {
+            if let Err(e) = handle(&mut stream, &site) {
+                eprintln!("Error serving request: {e}");
+            }
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --spellcheck. The words in the documentation are run through hunspell -l, which
+// prints the ones it doesn't know, and those are reported with their functions.
+
+use std::collections::BTreeSet;
+use std::io::{Error, Write};
+use std::process::{Command, Stdio};
+use doxygen2man::cli::Opt;
+use doxygen2man::state::RunState;
+use doxygen2man::model::*;
+use doxygen2man::parser::CODE_FONT;
+use doxygen2man::render::wants_page;
+
+// Words that are really code, eg function names, constants or paths
+fn is_code(word: &str) -> bool
+{
+    word.contains(|c: char| c == '_' || c == '/' || c == '.' || c == '@' || c.is_ascii_digit()) ||
+        !word.contains(|c: char| c.is_lowercase()) ||
+        word.chars().skip(1).any(|c| c.is_uppercase())
+}
+
+// The words to check in some nroff text. Listings and requests are skipped.
+fn text_words(text: &str, ignore: &[&str], words: &mut Vec<String>)
+{
+    let mut in_nf = false;
+    for l in text.lines() {
+        if l.starts_with(".nf") {
+            in_nf = true;
+        } else if l.starts_with(".fi") {
+            in_nf = false;
+        }
+        if in_nf || l.starts_with('.') {
+            continue;
+        }
+        let plain = l.replace(CODE_FONT, " ").replace("\\fB", " ").replace("\\fI", " ").replace("\\fR", " ").replace("\\fP", " ")
+            .replace("\\-", "-").replace("\\(bu", " ").replace("\\e", "\\");
+        for w in plain.split_whitespace() {
+            let w = w.trim_matches(|c: char| !c.is_alphanumeric());
+            // eg "(see test_open())" leaves the brackets in the middle
+            if w.len() < 2 || w.contains(|c: char| "()[]{}<>=*&|\\\"".contains(c)) || is_code(w) {
+                continue;
+            }
+            for part in w.split('-') {
+                if part.len() > 1 && !ignore.contains(&part) && !words.iter().any(|x| x == part) {
+                    words.push(part.to_string());
+                }
+            }
+        }
+    }
+}
+
+// All the words in a function's documentation
+fn function_words(opt: &Opt, f: &FunctionInfo) -> Vec<String>
+{
+    let mut ignore = vec![f.fn_name.as_str()];
+    ignore.extend(f.fn_args.iter().map(|p| p.par_name.as_str()));
+
+    let mut words = Vec::new();
+    text_words(&f.fn_brief, &ignore, &mut words);
+    text_words(&f.fn_detail, &ignore, &mut words);
+    if opt.include_internal {
+        text_words(&f.fn_internal_detail, &ignore, &mut words);
+    }
+    text_words(&f.fn_returnval, &ignore, &mut words);
+    text_words(&f.fn_note, &ignore, &mut words);
+    for p in &f.fn_args {
+        text_words(&p.par_desc, &ignore, &mut words);
+    }
+    for r in &f.fn_retvals {
+        text_words(&r.ret_desc, &ignore, &mut words);
+    }
+    for s in &f.fn_simplesects {
+        text_words(&s.ss_text, &ignore, &mut words);
+    }
+    for x in &f.fn_xrefsects {
+        text_words(&x.xr_desc, &ignore, &mut words);
+    }
+    words
+}
+
+// Run the words through hunspell, returning the ones it doesn't know
+fn misspelt_words(opt: &Opt, words: &BTreeSet<&str>) -> Result<BTreeSet<String>, Error>
+{
+    let mut cmd = Command::new("hunspell");
+    cmd.arg("-l");
+    if !opt.spell_dict.is_empty() {
+        cmd.arg("-d").arg(&opt.spell_dict);
+    }
+    if !opt.spell_words.is_empty() {
+        cmd.arg("-p").arg(&opt.spell_words);
+    }
+    let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()
+        .map_err(|e| Error::other(format!("cannot run hunspell: {e}")))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        for w in words {
+            writeln!(stdin, "{w}")?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::other(format!("hunspell failed: {}", output.status)));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+}
+
+// Check the spelling in the functions that have pages. Returns the number of
+// functions with misspellings.
+pub fn spellcheck_functions(opt: &Opt, state: &RunState, functions: &[FunctionInfo]) -> Result<usize, Error>
+{
+    let function_words: Vec<(&FunctionInfo, Vec<String>)> = functions.iter()
+        .filter(|f| wants_page(opt, state, f))
+        .map(|f| (f, function_words(opt, f)))
+        .collect();
+    let all_words: BTreeSet<&str> = function_words.iter().flat_map(|(_, w)| w.iter().map(|s| s.as_str())).collect();
+    if all_words.is_empty() {
+        return Ok(0);
+    }
+    let misspelt = misspelt_words(opt, &all_words)?;
+
+    let mut errors = 0;
+    for (f, words) in &function_words {
+        let bad: Vec<&str> = words.iter().map(|s| s.as_str()).filter(|w| misspelt.contains(*w)).collect();
+        if bad.is_empty() {
+            continue;
+        }
+        if f.fn_file.is_empty() {
+            eprintln!("Spelling: {}: {}", f.fn_name, bad.join(", "));
+        } else {
+            eprintln!("Spelling: {} ({}:{}): {}", f.fn_name, f.fn_file, f.fn_line, bad.join(", "));
+        }
+        errors += 1;
+    }
+    Ok(errors)
+}
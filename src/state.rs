@@ -0,0 +1,87 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// What a run has read and found so far, as opposed to the options it was
+// started with (cli::Opt). The files that the options name are read into here
+// at the start, and the input file being read and the counts and lists for
+// the end of the run are kept up to date as it goes. It's passed alongside
+// the Opt to whatever needs it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use crate::coverage::FunctionCoverage;
+use crate::po::PotCatalog;
+use crate::parser::CondBlocks;
+use crate::timings::Timings;
+use crate::render::devhelp::DevhelpChapter;
+use crate::render::RemovedFunction;
+use crate::render::external::ExternalRefs;
+#[cfg(feature = "docset")]
+use crate::render::docset::DocsetEntry;
+
+#[derive(Debug, Default)]
+pub struct RunState {
+    // The file we are currently reading, for --force checks
+    pub current_input: String,
+
+    // Times for the file we are currently reading, for --timings
+    pub timer: Timings,
+
+    // The contents of --preamble
+    pub preamble_text: String,
+
+    // From --removed and --removed-file
+    pub removed_functions: Vec<RemovedFunction>,
+
+    // The \cond blocks in the headers, for --enabled-sections
+    pub cond_blocks: CondBlocks,
+
+    // The renames from --rename-map, as (old name, new name)
+    pub renames: Vec<(String, String)>,
+
+    // The pages from --ref-map and --tagfile
+    pub external_refs: ExternalRefs,
+
+    // The templates from --template-dir
+    #[cfg(feature = "templates")]
+    pub templates: Option<tera::Tera>,
+
+    // The translations from --po
+    pub translations: BTreeMap<String, String>,
+
+    // The symbols read from --baseline
+    pub baseline_symbols: BTreeSet<String>,
+
+    // Search index entries for all the files read so far, for --docset
+    #[cfg(feature = "docset")]
+    pub docset_entries: Vec<DocsetEntry>,
+
+    // The chapters (one per header) written so far, for --devhelp
+    pub devhelp_chapters: Vec<DevhelpChapter>,
+
+    // The messages read so far, for --write-pot
+    pub pot: PotCatalog,
+
+    // The undocumented symbols found so far, for --write-baseline
+    pub undocumented: BTreeSet<String>,
+
+    // Undocumented symbols that aren't in the baseline, for --baseline
+    pub new_undocumented: usize,
+
+    // The coverage of the functions read so far, for --coverage and --min-coverage
+    pub coverage_entries: Vec<FunctionCoverage>,
+
+    // Functions with misspellings so far, for --spellcheck
+    pub spelling_errors: usize,
+
+    // Input files that had nothing in them, for --strict-empty
+    pub empty_inputs: usize,
+
+    // Pages that couldn't be written, eg because of a mistake in a template
+    pub page_errors: usize,
+}
@@ -0,0 +1,108 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Streaming mode. Each page is written as soon as its function has been read,
+// rather than reading the whole header first, so huge (usually machine-generated)
+// XML files don't need to be held in memory.
+
+use std::collections::BTreeMap;
+use std::io::Error;
+use std::time::Instant;
+use doxygen2man::model::*;
+use doxygen2man::parser::{fill_structures, read_function_names, read_header_xml_streaming};
+
+use doxygen2man::cli::Opt;
+use doxygen2man::state::RunState;
+use doxygen2man::render::{get_date_and_copyright, print_man_page, print_text_function, wants_page, ManPage};
+use doxygen2man::timings::Phase;
+
+// Write the page (and preview) for one function. Returns false if its page couldn't be written
+#[allow(clippy::too_many_arguments)]
+fn print_function(opt: &Opt,
+                  state: &RunState,
+                  date_to_print: &str,
+                  copyright: &str,
+                  function: &FunctionInfo,
+                  see_also: &[FunctionInfo],
+                  structures: &BTreeMap<String, StructureInfo>,
+                  pages: &mut Vec<ManPage>) -> bool
+{
+    if opt.print_ascii {
+        print_text_function(opt, function, see_also, structures);
+    }
+    if opt.print_man {
+        match print_man_page(opt, state, date_to_print, function, see_also, structures, copyright) {
+            Ok(Some(file)) => pages.push(ManPage::new(opt, state, file, function)),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Error writing man page for {}: {}", function.fn_name, e);
+                return false;
+            }
+        }
+    }
+    true
+}
+
+pub fn stream_xml_input(opt: &mut Opt,
+                        state: &mut RunState,
+                        in_file: &str,
+                        structure_cache: &mut BTreeMap<String, StructureInfo>) -> Result<Vec<ManPage>, Error>
+{
+    // A quick first pass to get the names of all the functions for SEE ALSO,
+    // that's all that is needed from the other functions
+    let xml_dir = &opt.xml_dir;
+    let headerfile = &mut opt.headerfile;
+    let mut see_also = state.timer.time(Phase::Parse, || read_function_names(xml_dir, in_file, headerfile))?;
+    let mut general = FunctionInfo::new();
+    general.fn_name = opt.headerfile.clone();
+    see_also.push(general);
+
+    let opt: &Opt = opt;
+    see_also.retain(|f| wants_page(opt, state, f));
+    let (date_to_print, copyright) = match get_date_and_copyright(opt) {
+        Ok(d) => d,
+        Err(e) => return Err(Error::other(format!("Error making date and copyright: {e}"))),
+    };
+
+    let mut pages = Vec::new();
+    let mut failed = 0;
+
+    // Structures are read in as they are first needed
+    let mut on_function = |function: FunctionInfo, structures: &BTreeMap<String, StructureInfo>| {
+        if !wants_page(opt, state, &function) {
+            return;
+        }
+        let mut fn_structures = BTreeMap::<String, StructureInfo>::new();
+        for r in &function.fn_refids {
+            if let Some(s) = structures.get(r) {
+                fn_structures.insert(r.clone(), s.clone());
+            }
+        }
+        let filled_structures = state.timer.time(Phase::Structures, || fill_structures(&opt.xml_dir, std::slice::from_ref(&function), &fn_structures, structure_cache));
+
+        if !print_function(opt, state, &date_to_print, &copyright, &function, &see_also, &filled_structures, &mut pages) {
+            failed += 1;
+        }
+    };
+
+    let mut headerfile = opt.headerfile.clone();
+    let start = Instant::now();
+    let times_before = state.timer.get().total();
+    let general_pages = read_header_xml_streaming(&opt.xml_dir, in_file, &mut headerfile, &mut on_function)?;
+    // The structures and pages were timed as they were done, the rest of the time was parsing
+    let times_during = state.timer.get().total() - times_before;
+    state.timer.add(Phase::Parse, start.elapsed().saturating_sub(times_during));
+    for g in general_pages.iter().filter(|g| wants_page(opt, state, g)) {
+        if !print_function(opt, state, &date_to_print, &copyright, g, &see_also, &BTreeMap::new(), &mut pages) {
+            failed += 1;
+        }
+    }
+    state.page_errors += failed;
+    Ok(pages)
+}
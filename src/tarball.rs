@@ -0,0 +1,74 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --output-tar. The pages are written to a temporary directory as usual
+// and then put into a tar file, in man<section>/ subdirectories.
+
+use std::fs;
+use std::io::{Error, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use tar::{Builder, Header};
+use doxygen2man::cli::Opt;
+use doxygen2man::render::{write_atomically, ManPage};
+
+// Use SOURCE_DATE_EPOCH for the file times if it's set, so the tarball is reproducible
+fn page_mtime(page: &Path) -> u64
+{
+    if let Some(t) = std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|e| e.trim().parse::<u64>().ok()) {
+        return t;
+    }
+    fs::metadata(page)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn append_pages<W: Write>(builder: &mut Builder<W>, opt: &Opt, pages: &[ManPage]) -> Result<(), Error>
+{
+    for p in pages {
+        let path = Path::new(&p.file);
+        let data = fs::read(path)?;
+        let name = match path.file_name() {
+            Some(n) => format!("man{}/{}", opt.man_section, n.to_string_lossy()),
+            None => continue,
+        };
+
+        let mut header = Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(page_mtime(path));
+        header.set_cksum();
+        builder.append_data(&mut header, name, data.as_slice())?;
+    }
+    Ok(())
+}
+
+// Write the tarball, gzipped if the name ends in .gz or .tgz
+pub fn write_tarball(opt: &Opt, tar_file: &str, pages: &[ManPage]) -> Result<(), Error>
+{
+    let compress = tar_file.ends_with(".gz") || tar_file.ends_with(".tgz");
+
+    write_atomically(tar_file, |f| {
+        if compress {
+            let mut builder = Builder::new(GzEncoder::new(f, Compression::default()));
+            append_pages(&mut builder, opt, pages)?;
+            builder.into_inner()?.finish()?;
+        } else {
+            let mut builder = Builder::new(f);
+            append_pages(&mut builder, opt, pages)?;
+            builder.into_inner()?;
+        }
+        Ok(())
+    })
+}
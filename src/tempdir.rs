@@ -0,0 +1,40 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// A private temporary directory, removed (with everything in it) when dropped
+
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+pub struct TempDir {
+    dir: PathBuf,
+}
+
+impl TempDir {
+    // purpose is just to make the name a bit more helpful, eg doxygen2man-preview.1234
+    pub fn new(purpose: &str) -> Result<TempDir, Error>
+    {
+        let dir = std::env::temp_dir().join(format!("doxygen2man-{}.{}", purpose, std::process::id()));
+        fs::create_dir_all(&dir)?;
+        Ok(TempDir {dir})
+    }
+
+    pub fn path(&self) -> &Path
+    {
+        &self.dir
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self)
+    {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
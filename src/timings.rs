@@ -0,0 +1,95 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --timings. Adds up how long is spent in each phase of a run, so slow doc
+// builds can be blamed on the right thing.
+
+use std::cell::Cell;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    Parse,
+    Structures,
+    Render,
+    Write,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PhaseTimes {
+    pub parse: Duration,
+    pub structures: Duration,
+    pub render: Duration,
+    pub write: Duration,
+}
+
+impl PhaseTimes {
+    pub fn add(&mut self, other: &PhaseTimes)
+    {
+        self.parse += other.parse;
+        self.structures += other.structures;
+        self.render += other.render;
+        self.write += other.write;
+    }
+
+    pub fn total(&self) -> Duration
+    {
+        self.parse + self.structures + self.render + self.write
+    }
+}
+
+impl fmt::Display for PhaseTimes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "parse {:.2?}, structures {:.2?}, render {:.2?}, write {:.2?}",
+               self.parse, self.structures, self.render, self.write)
+    }
+}
+
+// The times for the file being read. This lives in Opt so that everything that
+// gets an &Opt can add to it.
+#[derive(Debug, Default)]
+pub struct Timings {
+    times: Cell<PhaseTimes>,
+}
+
+impl Timings {
+    // Run f, adding the time it took to phase
+    pub fn time<T, F: FnOnce() -> T>(&self, phase: Phase, f: F) -> T
+    {
+        let start = Instant::now();
+        let res = f();
+        self.add(phase, start.elapsed());
+        res
+    }
+
+    pub fn add(&self, phase: Phase, d: Duration)
+    {
+        let mut times = self.times.get();
+        match phase {
+            Phase::Parse => times.parse += d,
+            Phase::Structures => times.structures += d,
+            Phase::Render => times.render += d,
+            Phase::Write => times.write += d,
+        }
+        self.times.set(times);
+    }
+
+    pub fn get(&self) -> PhaseTimes
+    {
+        self.times.get()
+    }
+
+    // Get the times so far and start again from zero
+    pub fn take(&self) -> PhaseTimes
+    {
+        self.times.take()
+    }
+}
@@ -0,0 +1,138 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// --verify. The pages are written somewhere private and compared with the
+// ones in a directory of expected pages (eg kept in git), so changes in
+// doxygen2man or doxygen that change the output are noticed.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use doxygen2man::render::ManPage;
+
+// Lines of context around the changes in the diffs
+const CONTEXT: usize = 2;
+
+// A line diff of two pages, as ' ', '-' or '+' and the line.
+// Pages are small enough that the simple longest common subsequence table is fine.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)>
+{
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(('-', old[i]));
+            i += 1;
+        } else {
+            diff.push(('+', new[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+// Print the changed lines (with some context) between the expected page and ours
+fn print_diff(expected_name: &str, page_name: &str, expected: &str, page: &str)
+{
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = page.lines().collect();
+    let diff = diff_lines(&old, &new);
+
+    println!("--- {expected_name}");
+    println!("+++ {page_name}");
+    let changed: Vec<usize> = diff.iter().enumerate().filter(|(_, (c, _))| *c != ' ').map(|(i, _)| i).collect();
+    let mut last_printed = None;
+    for (i, (c, l)) in diff.iter().enumerate() {
+        let near = changed.iter().any(|&d| d + CONTEXT >= i && d <= i + CONTEXT);
+        if !near {
+            continue;
+        }
+        if last_printed.map(|p| p + 1) != Some(i) {
+            // Line number in the expected page
+            let line = diff[..i].iter().filter(|(c, _)| *c != '+').count() + 1;
+            println!("@@ line {line} @@");
+        }
+        println!("{c}{l}");
+        last_printed = Some(i);
+    }
+}
+
+// Compare the pages written with the ones in expected_dir, printing the
+// differences. Returns the number of pages that are different or missing.
+pub fn verify_pages(expected_dir: &str, pages: &[ManPage]) -> usize
+{
+    let mut errors = 0;
+    let mut seen = BTreeSet::new();
+    for p in pages {
+        let name = match Path::new(&p.file).file_name() {
+            Some(n) => n.to_string_lossy().to_string(),
+            None => continue,
+        };
+        let expected_file = format!("{expected_dir}/{name}");
+        seen.insert(name.clone());
+
+        let page = match fs::read_to_string(&p.file) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Verify: cannot read page for {}: {}", p.function, e);
+                errors += 1;
+                continue;
+            }
+        };
+        match fs::read_to_string(&expected_file) {
+            Ok(expected) => {
+                if expected != page {
+                    eprintln!("Verify: {name} is different from {expected_file}");
+                    print_diff(&expected_file, &name, &expected, &page);
+                    errors += 1;
+                }
+            }
+            Err(_) => {
+                eprintln!("Verify: {name} is new, there is no {expected_file}");
+                errors += 1;
+            }
+        }
+    }
+
+    // And the pages that we used to make but don't any more
+    match fs::read_dir(expected_dir) {
+        Ok(dir) => {
+            let mut gone: Vec<String> = dir.filter_map(|e| e.ok())
+                .filter(|e| e.path().is_file())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|n| !n.starts_with('.') && !seen.contains(n))
+                .collect();
+            gone.sort();
+            for n in gone {
+                eprintln!("Verify: {expected_dir}/{n} was not written");
+                errors += 1;
+            }
+        }
+        Err(e) => {
+            eprintln!("Verify: cannot read {expected_dir}: {e}");
+            errors += 1;
+        }
+    }
+    errors
+}
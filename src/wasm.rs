@@ -0,0 +1,68 @@
+// doxygen2man
+//
+// Copyright (C) 2020-2021 Red Hat, Inc.  All rights reserved.
+//
+// Author: Christine Caulfield <ccaulfie@redhat.com>
+//
+// This software licensed under GPL-2.0+
+//
+
+// Model filters loaded as WASM modules. These work just like --filter
+// commands (JSON model in, JSON model out) but are sandboxed and portable,
+// so they can be shipped with a project. The interface is in README.md
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::Error;
+use wasmi::{Engine, Linker, Module, Store};
+use doxygen2man::model::*;
+
+use doxygen2man::cli::Opt;
+use crate::json::{model_from_json, model_to_json};
+
+fn wasm_error(wasm_file: &str, e: impl std::fmt::Display) -> Error
+{
+    Error::other(format!("WASM filter {wasm_file}: {e}"))
+}
+
+pub fn wasm_filter_model(opt: &Opt,
+                         wasm_file: &str,
+                         functions: &[FunctionInfo],
+                         structures: &BTreeMap<String, StructureInfo>) -> Result<(Vec<FunctionInfo>, BTreeMap<String, StructureInfo>), Error>
+{
+    let json = model_to_json(opt, functions, structures)?;
+    let json_len = match i32::try_from(json.len()) {
+        Ok(l) => l,
+        Err(e) => return Err(wasm_error(wasm_file, e)),
+    };
+
+    let wasm = std::fs::read(wasm_file).map_err(|e| wasm_error(wasm_file, e))?;
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm[..]).map_err(|e| wasm_error(wasm_file, e))?;
+    let mut store = Store::new(&engine, ());
+    let linker = <Linker<()>>::new(&engine);
+    let instance = linker.instantiate(&mut store, &module)
+        .and_then(|i| i.start(&mut store))
+        .map_err(|e| wasm_error(wasm_file, e))?;
+
+    let memory = match instance.get_memory(&store, "memory") {
+        Some(m) => m,
+        None => return Err(wasm_error(wasm_file, "does not export 'memory'")),
+    };
+    let alloc = instance.get_typed_func::<i32, i32>(&store, "d2m_alloc")
+        .map_err(|e| wasm_error(wasm_file, e))?;
+    let filter = instance.get_typed_func::<(i32, i32), i64>(&store, "d2m_filter")
+        .map_err(|e| wasm_error(wasm_file, e))?;
+
+    // Copy the model in, filter it and copy the result back out
+    let in_ptr = alloc.call(&mut store, json_len).map_err(|e| wasm_error(wasm_file, e))?;
+    memory.write(&mut store, in_ptr as u32 as usize, &json).map_err(|e| wasm_error(wasm_file, e))?;
+
+    let result = filter.call(&mut store, (in_ptr, json_len)).map_err(|e| wasm_error(wasm_file, e))?;
+    let out_ptr = (result >> 32) as u32 as usize;
+    let out_len = result as u32 as usize;
+
+    let mut out = vec![0u8; out_len];
+    memory.read(&store, out_ptr, &mut out).map_err(|e| wasm_error(wasm_file, e))?;
+    model_from_json(&out).map_err(|e| wasm_error(wasm_file, e))
+}